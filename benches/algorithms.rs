@@ -0,0 +1,17 @@
+// Every algorithm through every entry point, on inputs from 16 bytes to 1 GiB; the same as
+// `hashsum bench`, and taking the same options after `--`:
+//
+//   cargo bench --bench algorithms
+//   cargo bench --bench algorithms -- -a sha256 --sizes=1M,1G --json
+
+use std::env;
+use std::ffi::OsString;
+use std::process;
+
+use rust_crypto::bench;
+
+fn main() {
+    // cargo passes --bench to benchmarks without the standard harness
+    let args: Vec<OsString> = env::args_os().filter(|arg| arg != "--bench").collect();
+    process::exit(bench::run(args));
+}
@@ -0,0 +1,100 @@
+// Compares the original block-at-a-time file hashing (sha_sum_blockwise), reading the file
+// into a large buffer (sha_sum_reader on the file) and mapping it (sha_sum), on one large file:
+//
+//   cargo bench --bench file_hash                  # a 2 GiB scratch file in the temp dir
+//   BENCH_FILE_MB=8192 cargo bench --bench file_hash
+//   cargo bench --bench file_hash -- /some/big/file
+//
+// The file is read once before timing, so every path sees it in the page cache. Two runs on a
+// 4 GiB file, on a 1 vCPU x86_64 VM with the SHA extensions (SHA-256 takes them, SHA-512 has
+// none to take):
+//
+//   SHA256   blockwise    132.4 MB/s   buffered    816.7 MB/s   mapped    873.2 MB/s   speedup 6.59x
+//   SHA512   blockwise    216.2 MB/s   buffered    247.3 MB/s   mapped    264.1 MB/s   speedup 1.22x
+//
+//   SHA256   blockwise    161.7 MB/s   buffered    786.5 MB/s   mapped    931.3 MB/s   speedup 5.76x
+//   SHA512   blockwise    205.4 MB/s   buffered    211.3 MB/s   mapped    268.5 MB/s   speedup 1.31x
+//
+// Before the multi-block compression and the mapping, on the same machine and file, sha_sum
+// did 178.2 MB/s for SHA-256 and 225.2 MB/s for SHA-512.
+
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use rust_crypto::sha::{ShaParams, SHA256, SHA512};
+use rust_crypto::{sha_sum, sha_sum_blockwise, sha_sum_reader};
+
+const RUNS: usize = 3;
+
+fn scratch_file(megabytes: usize) -> io::Result<PathBuf> {
+    let path = env::temp_dir().join(format!("rust-crypto-bench-{}", std::process::id()));
+    let mut f = io::BufWriter::new(File::create(&path)?);
+
+    // cheap pseudo-random content, so nothing along the way can take shortcuts
+    let mut state: u64 = 0x2545f4914f6cdd1d;
+    let mut chunk = vec![0u8; 1 << 20];
+    for _ in 0..megabytes {
+        for word in chunk.chunks_exact_mut(8) {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            word.copy_from_slice(&state.to_le_bytes());
+        }
+        f.write_all(&chunk)?;
+    }
+    f.flush()?;
+
+    Ok(path)
+}
+
+// The best of a few runs, in MB/s; the slower runs are mostly noise from elsewhere.
+fn time<F: Fn() -> io::Result<Vec<u8>>>(size: u64, f: F) -> io::Result<(Vec<u8>, f64)> {
+    let mut digest = Vec::new();
+    let mut best = 0.0f64;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        digest = f()?;
+        best = best.max(size as f64 / start.elapsed().as_secs_f64() / 1e6);
+    }
+    Ok((digest, best))
+}
+
+fn compare<T: Copy>(algo: ShaParams<T>, file: &PathBuf, size: u64) -> io::Result<()> {
+    let (old, old_speed) = time(size, || sha_sum_blockwise(algo, file))?;
+    let (read, read_speed) = time(size, || sha_sum_reader(algo, File::open(file)?))?;
+    let (mapped, mapped_speed) = time(size, || sha_sum(algo, file))?;
+    assert!(old == read && read == mapped, "the paths disagree");
+
+    println!("{:8} blockwise {:8.1} MB/s   buffered {:8.1} MB/s   mapped {:8.1} MB/s   speedup {:.2}x",
+        algo.name, old_speed, read_speed, mapped_speed, mapped_speed / old_speed);
+    Ok(())
+}
+
+fn main() -> io::Result<()> {
+    // cargo passes --bench to benchmarks without the standard harness
+    let given = env::args().skip(1).find(|a| !a.starts_with("--")).map(PathBuf::from);
+
+    let file = match &given {
+        Some(file) => file.clone(),
+        None => {
+            let megabytes = env::var("BENCH_FILE_MB").ok().and_then(|s| s.parse().ok()).unwrap_or(2048);
+            println!("writing a {} MB scratch file", megabytes);
+            scratch_file(megabytes)?
+        }
+    };
+
+    let size = fs::metadata(&file)?.len();
+    io::copy(&mut File::open(&file)?, &mut io::sink())?;
+    println!("hashing {} ({} bytes)", file.display(), size);
+
+    let result = compare(SHA256, &file, size).and_then(|_| compare(SHA512, &file, size));
+
+    if given.is_none() {
+        fs::remove_file(&file)?;
+    }
+    result
+}
@@ -0,0 +1,567 @@
+// Argon2d, Argon2i and Argon2id (RFC 9106, version 0x13), and the PHC string format used to store them.
+//
+// Memory is a lanes x columns matrix of 1 KiB blocks, filled slice by slice. Within a slice the
+// lanes don't depend on each other, so they may be filled by separate threads; each lane's current
+// segment is moved out of the shared memory while it is being filled, which keeps that safe.
+
+use std::convert::TryInto;
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+
+use crate::blake2b::{blake2b, Blake2b};
+use crate::encoding::{base64_decode, base64_encode};
+
+pub const VERSION: u32 = 0x13;
+
+// What verify_encoded allows a stored hash to ask for; any more is refused rather than tried,
+// since the parameters come with the hash. See verify_encoded_with_limits.
+pub const DEFAULT_MAX_MEMORY: u64 = 1 << 30;
+pub const DEFAULT_MAX_TIME_COST: u32 = 32;
+
+const BLOCK_WORDS: usize = 128;
+const SYNC_POINTS: usize = 4;
+
+type Block = [u64;BLOCK_WORDS];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Variant {
+    Argon2d = 0,
+    Argon2i = 1,
+    Argon2id = 2,
+}
+
+impl Variant {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Argon2d => "argon2d",
+            Variant::Argon2i => "argon2i",
+            Variant::Argon2id => "argon2id",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Argon2Error {
+    InvalidMemoryCost,
+    InvalidTimeCost,
+    InvalidParallelism,
+    SaltTooShort,
+    TagTooShort,
+    InvalidPhcString,
+    UnsupportedVersion,
+    LimitExceeded,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Argon2Params {
+    variant: Variant,
+    m_cost: u32,
+    t_cost: u32,
+    parallelism: u32,
+    threads: u32,
+}
+
+impl Argon2Params {
+    // m_cost is in KiB and must be at least 8 per lane
+    pub fn new(variant: Variant, m_cost: u32, t_cost: u32, parallelism: u32) -> Result<Argon2Params, Argon2Error> {
+        if parallelism == 0 || parallelism > 0xffffff {
+            return Err(Argon2Error::InvalidParallelism);
+        }
+        if (m_cost as u64) < 8 * parallelism as u64 {
+            return Err(Argon2Error::InvalidMemoryCost);
+        }
+        if t_cost == 0 {
+            return Err(Argon2Error::InvalidTimeCost);
+        }
+
+        Ok(Argon2Params{variant, m_cost, t_cost, parallelism, threads: 1})
+    }
+
+    // Fill the lanes using up to this many threads; the result does not depend on it.
+    pub fn with_threads(self, threads: u32) -> Argon2Params {
+        Argon2Params{threads: std::cmp::max(threads, 1), ..self}
+    }
+
+    pub fn variant(&self) -> Variant { self.variant }
+    pub fn m_cost(&self) -> u32 { self.m_cost }
+    pub fn t_cost(&self) -> u32 { self.t_cost }
+    pub fn parallelism(&self) -> u32 { self.parallelism }
+}
+
+// The multiplication-hardened BLAKE2b round function
+fn f_bla_mka(x: u64, y: u64) -> u64 {
+    let m = (x & 0xffffffff) * (y & 0xffffffff);
+    x.wrapping_add(y).wrapping_add(m.wrapping_mul(2))
+}
+
+fn gb(v: &mut Block, a: usize, b: usize, c: usize, d: usize) {
+    v[a] = f_bla_mka(v[a], v[b]);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = f_bla_mka(v[c], v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = f_bla_mka(v[a], v[b]);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = f_bla_mka(v[c], v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+fn permute(v: &mut Block, i: [usize;16]) {
+    gb(v, i[0], i[4], i[8], i[12]);
+    gb(v, i[1], i[5], i[9], i[13]);
+    gb(v, i[2], i[6], i[10], i[14]);
+    gb(v, i[3], i[7], i[11], i[15]);
+    gb(v, i[0], i[5], i[10], i[15]);
+    gb(v, i[1], i[6], i[11], i[12]);
+    gb(v, i[2], i[7], i[8], i[13]);
+    gb(v, i[3], i[4], i[9], i[14]);
+}
+
+// The compression function G
+fn compress(x: &Block, y: &Block) -> Block {
+    let mut r = [0u64;BLOCK_WORDS];
+    for i in 0..BLOCK_WORDS {
+        r[i] = x[i] ^ y[i];
+    }
+
+    let mut z = r;
+    // the block is an 8x8 matrix of 16 byte registers; first each row, then each column
+    for row in 0..8 {
+        let mut indices = [0usize;16];
+        for (k, index) in indices.iter_mut().enumerate() {
+            *index = 16 * row + k;
+        }
+        permute(&mut z, indices);
+    }
+    for column in 0..8 {
+        let mut indices = [0usize;16];
+        for (k, index) in indices.iter_mut().enumerate() {
+            *index = 2 * column + 16 * (k / 2) + k % 2;
+        }
+        permute(&mut z, indices);
+    }
+
+    for i in 0..BLOCK_WORDS {
+        z[i] ^= r[i];
+    }
+    z
+}
+
+// H', the variable length hash
+fn h_prime(out_len: usize, input: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = Blake2b::new(std::cmp::min(out_len, 64));
+    hasher.update(&(out_len as u32).to_le_bytes());
+    for part in input {
+        hasher.update(part);
+    }
+
+    if out_len <= 64 {
+        return hasher.finish();
+    }
+
+    let r = out_len.div_ceil(32) - 2;
+    let mut v = hasher.finish();
+    let mut result = v[..32].to_vec();
+    for _ in 1..r {
+        v = blake2b(64, &v);
+        result.extend_from_slice(&v[..32]);
+    }
+    result.extend(blake2b(out_len - 32 * r, &v));
+
+    result
+}
+
+fn bytes_to_block(bytes: &[u8]) -> Block {
+    let mut block = [0u64;BLOCK_WORDS];
+    for (w, c) in block.iter_mut().zip(bytes.chunks_exact(8)) {
+        *w = u64::from_le_bytes(c.try_into().unwrap());
+    }
+    block
+}
+
+struct Geometry {
+    variant: Variant,
+    passes: u32,
+    lanes: usize,
+    lane_length: usize,
+    segment_length: usize,
+}
+
+impl Geometry {
+    fn memory_blocks(&self) -> usize {
+        self.lanes * self.lane_length
+    }
+}
+
+fn next_addresses(input: &mut Block, address: &mut Block) {
+    let zero = [0u64;BLOCK_WORDS];
+    input[6] += 1;
+    *address = compress(&zero, &compress(&zero, input));
+}
+
+fn fill_segment(g: &Geometry, memory: &[Block], segment: &mut [Block], pass: u32, lane: usize, slice: usize) {
+    let data_independent = match g.variant {
+        Variant::Argon2d => false,
+        Variant::Argon2i => true,
+        Variant::Argon2id => pass == 0 && slice < SYNC_POINTS / 2,
+    };
+
+    let mut input = [0u64;BLOCK_WORDS];
+    let mut address = [0u64;BLOCK_WORDS];
+    if data_independent {
+        input[0] = pass as u64;
+        input[1] = lane as u64;
+        input[2] = slice as u64;
+        input[3] = g.memory_blocks() as u64;
+        input[4] = g.passes as u64;
+        input[5] = g.variant as u64;
+    }
+
+    let segment_start = slice * g.segment_length;
+    let mut first = 0;
+    if pass == 0 && slice == 0 {
+        // the first two blocks of each lane are already there
+        first = 2;
+        if data_independent {
+            next_addresses(&mut input, &mut address);
+        }
+    }
+
+    for i in first..g.segment_length {
+        let prev: &Block = if i > 0 {
+            &segment[i - 1]
+        } else {
+            &memory[lane * g.lane_length + (segment_start + g.lane_length - 1) % g.lane_length]
+        };
+
+        let pseudo_rand = if data_independent {
+            if i % BLOCK_WORDS == 0 {
+                next_addresses(&mut input, &mut address);
+            }
+            address[i % BLOCK_WORDS]
+        } else {
+            prev[0]
+        };
+
+        let ref_lane = if pass == 0 && slice == 0 {
+            lane
+        } else {
+            ((pseudo_rand >> 32) % g.lanes as u64) as usize
+        };
+
+        // the blocks that may be referenced: those finished in this lane, or in other lanes' finished segments
+        let same_lane = ref_lane == lane;
+        let finished = if pass == 0 { segment_start } else { g.lane_length - g.segment_length };
+        let reference_area_size = if same_lane {
+            finished + i - 1
+        } else if i == 0 {
+            finished - 1
+        } else {
+            finished
+        };
+
+        let j1 = pseudo_rand & 0xffffffff;
+        let x = (j1 * j1) >> 32;
+        let y = (reference_area_size as u64 * x) >> 32;
+        let relative_position = reference_area_size - 1 - y as usize;
+        let start_position = if pass != 0 && slice != SYNC_POINTS - 1 { segment_start + g.segment_length } else { 0 };
+        let ref_index = (start_position + relative_position) % g.lane_length;
+
+        let reference: &Block = if same_lane && ref_index >= segment_start && ref_index < segment_start + g.segment_length {
+            &segment[ref_index - segment_start]
+        } else {
+            &memory[ref_lane * g.lane_length + ref_index]
+        };
+
+        let new_block = compress(prev, reference);
+        if pass == 0 {
+            segment[i] = new_block;
+        } else {
+            for (old, new) in segment[i].iter_mut().zip(new_block.iter()) {
+                *old ^= new;
+            }
+        }
+    }
+}
+
+pub fn argon2(params: &Argon2Params, password: &[u8], salt: &[u8], secret: &[u8], ad: &[u8], tag_len: usize) -> Result<Vec<u8>, Argon2Error> {
+    if salt.len() < 8 {
+        return Err(Argon2Error::SaltTooShort);
+    }
+    if tag_len < 4 {
+        return Err(Argon2Error::TagTooShort);
+    }
+
+    let lanes = params.parallelism as usize;
+    let lane_length = (params.m_cost as usize / (SYNC_POINTS * lanes)) * SYNC_POINTS;
+    let g = Geometry{
+        variant: params.variant,
+        passes: params.t_cost,
+        lanes,
+        lane_length,
+        segment_length: lane_length / SYNC_POINTS,
+    };
+
+    let mut h0 = Blake2b::new(64);
+    for value in &[params.parallelism, tag_len as u32, params.m_cost, params.t_cost, VERSION, params.variant as u32] {
+        h0.update(&value.to_le_bytes());
+    }
+    for part in &[password, salt, secret, ad] {
+        h0.update(&(part.len() as u32).to_le_bytes());
+        h0.update(part);
+    }
+    let h0 = h0.finish();
+
+    let mut memory = vec![[0u64;BLOCK_WORDS]; g.memory_blocks()];
+    for lane in 0..lanes {
+        let lane_bytes = (lane as u32).to_le_bytes();
+        for column in 0..2 {
+            let column_bytes = (column as u32).to_le_bytes();
+            memory[lane * lane_length + column] = bytes_to_block(&h_prime(1024, &[&h0, &column_bytes, &lane_bytes]));
+        }
+    }
+
+    let threads = std::cmp::min(params.threads as usize, lanes);
+    for pass in 0..params.t_cost {
+        for slice in 0..SYNC_POINTS {
+            let segment_start = slice * g.segment_length;
+            let mut segments: Vec<Vec<Block>> = (0..lanes)
+                .map(|lane| memory[lane * lane_length + segment_start..][..g.segment_length].to_vec())
+                .collect();
+
+            if threads > 1 {
+                let lanes_per_thread = lanes.div_ceil(threads);
+                let shared: &[Block] = &memory;
+                let geometry = &g;
+                thread::scope(|scope| {
+                    for (chunk_index, chunk) in segments.chunks_mut(lanes_per_thread).enumerate() {
+                        scope.spawn(move || {
+                            for (k, segment) in chunk.iter_mut().enumerate() {
+                                fill_segment(geometry, shared, segment, pass, chunk_index * lanes_per_thread + k, slice);
+                            }
+                        });
+                    }
+                });
+            } else {
+                for (lane, segment) in segments.iter_mut().enumerate() {
+                    fill_segment(&g, &memory, segment, pass, lane, slice);
+                }
+            }
+
+            for (lane, segment) in segments.iter().enumerate() {
+                memory[lane * lane_length + segment_start..][..g.segment_length].copy_from_slice(segment);
+            }
+        }
+    }
+
+    let mut last = [0u64;BLOCK_WORDS];
+    for lane in 0..lanes {
+        for (c, b) in last.iter_mut().zip(memory[lane * lane_length + lane_length - 1].iter()) {
+            *c ^= b;
+        }
+    }
+    let last_bytes: Vec<u8> = last.iter().flat_map(|w| w.to_le_bytes().to_vec()).collect();
+
+    Ok(h_prime(tag_len, &[&last_bytes]))
+}
+
+// $argon2id$v=19$m=65536,t=3,p=4$<salt>$<hash>, with unpadded standard base64
+#[derive(Debug, PartialEq)]
+pub struct PhcHash {
+    pub params: Argon2Params,
+    pub salt: Vec<u8>,
+    pub hash: Vec<u8>,
+}
+
+impl fmt::Display for PhcHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "${}$v={}$m={},t={},p={}${}${}",
+            self.params.variant.name(), VERSION,
+            self.params.m_cost, self.params.t_cost, self.params.parallelism,
+            base64_encode(&self.salt, false), base64_encode(&self.hash, false))
+    }
+}
+
+fn parse_u32(s: &str) -> Result<u32, Argon2Error> {
+    // no signs or leading zeros, as the PHC format prescribes for decimal numbers
+    if s.is_empty() || (s.len() > 1 && s.starts_with('0')) || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(Argon2Error::InvalidPhcString);
+    }
+    s.parse::<u32>().map_err(|_| Argon2Error::InvalidPhcString)
+}
+
+impl FromStr for PhcHash {
+    type Err = Argon2Error;
+
+    fn from_str(s: &str) -> Result<PhcHash, Argon2Error> {
+        let fields: Vec<&str> = s.split('$').collect();
+        if fields.len() != 6 || !fields[0].is_empty() {
+            return Err(Argon2Error::InvalidPhcString);
+        }
+
+        let variant = match fields[1] {
+            "argon2d" => Variant::Argon2d,
+            "argon2i" => Variant::Argon2i,
+            "argon2id" => Variant::Argon2id,
+            _ => return Err(Argon2Error::InvalidPhcString),
+        };
+
+        match fields[2].strip_prefix("v=") {
+            Some(version) => if parse_u32(version)? != VERSION {
+                return Err(Argon2Error::UnsupportedVersion);
+            },
+            None => return Err(Argon2Error::InvalidPhcString),
+        }
+
+        let mut m_cost = None;
+        let mut t_cost = None;
+        let mut parallelism = None;
+        for param in fields[3].split(',') {
+            match param.split_once('=') {
+                Some(("m", value)) if m_cost.is_none() => m_cost = Some(parse_u32(value)?),
+                Some(("t", value)) if t_cost.is_none() => t_cost = Some(parse_u32(value)?),
+                Some(("p", value)) if parallelism.is_none() => parallelism = Some(parse_u32(value)?),
+                _ => return Err(Argon2Error::InvalidPhcString),
+            }
+        }
+        let (m_cost, t_cost, parallelism) = match (m_cost, t_cost, parallelism) {
+            (Some(m), Some(t), Some(p)) => (m, t, p),
+            _ => return Err(Argon2Error::InvalidPhcString),
+        };
+
+        let salt = base64_decode(fields[4]).map_err(|_| Argon2Error::InvalidPhcString)?;
+        let hash = base64_decode(fields[5]).map_err(|_| Argon2Error::InvalidPhcString)?;
+
+        Ok(PhcHash{params: Argon2Params::new(variant, m_cost, t_cost, parallelism)?, salt, hash})
+    }
+}
+
+pub fn hash_encoded(params: &Argon2Params, password: &[u8], salt: &[u8], tag_len: usize) -> Result<String, Argon2Error> {
+    let hash = argon2(params, password, salt, &[], &[], tag_len)?;
+    Ok(PhcHash{params: *params, salt: salt.to_vec(), hash}.to_string())
+}
+
+pub fn verify_encoded(encoded: &str, password: &[u8]) -> Result<bool, Argon2Error> {
+    verify_encoded_with_limits(encoded, password, DEFAULT_MAX_MEMORY, DEFAULT_MAX_TIME_COST)
+}
+
+// max_memory is in bytes; a hash asking for more, or for more than max_t_cost passes, is
+// refused with LimitExceeded before anything is allocated.
+pub fn verify_encoded_with_limits(encoded: &str, password: &[u8], max_memory: u64, max_t_cost: u32) -> Result<bool, Argon2Error> {
+    let phc: PhcHash = encoded.parse()?;
+    if phc.params.m_cost as u64 * 1024 > max_memory || phc.params.t_cost > max_t_cost {
+        return Err(Argon2Error::LimitExceeded);
+    }
+    let hash = argon2(&phc.params, password, &phc.salt, &[], &[], phc.hash.len())?;
+
+    // compare without an early exit
+    let difference = hash.iter().zip(&phc.hash).fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    Ok(difference == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    extern crate hex;
+
+    // RFC 9106, section 5
+    #[rstest(variant, expected,
+        case::argon2d(Variant::Argon2d, "512b391b6f1162975371d30919734294f868e3be3984f3c1a13a4db9fabe4acb"),
+        case::argon2i(Variant::Argon2i, "c814d9d1dc7f37aa13f0d77f2494bda1c8de6b016dd388d29952a4c4672b6ce8"),
+        case::argon2id(Variant::Argon2id, "0d640df58d78766c08c037a34a8b53c9d01ef0452d75b65eb52520e96b01e659"),
+    )]
+    fn test_rfc9106(variant: Variant, expected: &str) {
+        let params = Argon2Params::new(variant, 32, 3, 4).unwrap();
+        let tag = argon2(&params, &[0x01; 32], &[0x02; 16], &[0x03; 8], &[0x04; 12], 32).unwrap();
+        assert_eq!(hex::encode(tag), expected);
+    }
+
+    #[rstest(variant,
+        case::argon2d(Variant::Argon2d),
+        case::argon2i(Variant::Argon2i),
+        case::argon2id(Variant::Argon2id),
+    )]
+    fn test_threads_give_same_result(variant: Variant) {
+        let params = Argon2Params::new(variant, 64, 2, 4).unwrap();
+        let single = argon2(&params, b"password", b"somesalt", &[], &[], 32).unwrap();
+        let threaded = argon2(&params.with_threads(3), b"password", b"somesalt", &[], &[], 32).unwrap();
+        assert_eq!(single, threaded);
+    }
+
+    #[test]
+    fn test_long_tag() {
+        // exercises H' beyond a single BLAKE2b output (checked against libargon2)
+        let params = Argon2Params::new(Variant::Argon2id, 32, 1, 1).unwrap();
+        let tag = argon2(&params, b"password", b"somesalt", &[], &[], 100).unwrap();
+        assert_eq!(hex::encode(tag), "25a61afdcc32bf7bccc6b666d9c8b601a74aface2447324c78b4c63a33e5a8eecefeffae48ceb4a9194d52557b76c38b9bc2fb206e05f326baaf3bb25fce1be3b8c74dfb55ead0092318f7826fb24e48351c3b35da6ee79fff1f7cb9ca1ee0191fd77456");
+    }
+
+    #[test]
+    fn test_hash_encoded() {
+        let params = Argon2Params::new(Variant::Argon2id, 64, 2, 1).unwrap();
+        let encoded = hash_encoded(&params, b"password", b"somesalt", 32).unwrap();
+        assert_eq!(encoded, "$argon2id$v=19$m=64,t=2,p=1$c29tZXNhbHQ$FqGkmHNGCd0BRW2kBt6fPZ2pPmyGwwChL8FGUhTOSSI");
+    }
+
+    #[test]
+    fn test_verify_encoded() {
+        let encoded = "$argon2id$v=19$m=64,t=2,p=1$c29tZXNhbHQ$FqGkmHNGCd0BRW2kBt6fPZ2pPmyGwwChL8FGUhTOSSI";
+        assert_eq!(verify_encoded(encoded, b"password"), Ok(true));
+        assert_eq!(verify_encoded(encoded, b"passw0rd"), Ok(false));
+    }
+
+    #[rstest(encoded,
+        case::memory("$argon2id$v=19$m=4294967295,t=2,p=1$c29tZXNhbHQ$AAECAw"),
+        case::just_over("$argon2id$v=19$m=1048577,t=1,p=1$c29tZXNhbHQ$AAECAw"),
+        case::time("$argon2id$v=19$m=64,t=4294967295,p=1$c29tZXNhbHQ$AAECAw"),
+    )]
+    fn test_verify_default_limits(encoded: &str) {
+        assert_eq!(verify_encoded(encoded, b"password"), Err(Argon2Error::LimitExceeded));
+    }
+
+    #[test]
+    fn test_verify_with_limits() {
+        let encoded = "$argon2id$v=19$m=64,t=2,p=1$c29tZXNhbHQ$FqGkmHNGCd0BRW2kBt6fPZ2pPmyGwwChL8FGUhTOSSI";
+        assert_eq!(verify_encoded_with_limits(encoded, b"password", 64 * 1024, 2), Ok(true));
+        assert_eq!(verify_encoded_with_limits(encoded, b"password", 64 * 1024 - 1, 2), Err(Argon2Error::LimitExceeded));
+        assert_eq!(verify_encoded_with_limits(encoded, b"password", 64 * 1024, 1), Err(Argon2Error::LimitExceeded));
+    }
+
+    #[test]
+    fn test_phc_roundtrip() {
+        let phc: PhcHash = "$argon2i$v=19$m=4096,t=3,p=2$c29tZXNhbHQ$AAECAw".parse().unwrap();
+        assert_eq!(phc.params, Argon2Params::new(Variant::Argon2i, 4096, 3, 2).unwrap());
+        assert_eq!(phc.salt, b"somesalt");
+        assert_eq!(phc.hash, vec![0, 1, 2, 3]);
+        assert_eq!(phc.to_string(), "$argon2i$v=19$m=4096,t=3,p=2$c29tZXNhbHQ$AAECAw");
+    }
+
+    #[rstest(encoded, expected,
+        case::unknown_algorithm("$argon3$v=19$m=64,t=2,p=1$c29tZXNhbHQ$AAECAw", Argon2Error::InvalidPhcString),
+        case::old_version("$argon2id$v=16$m=64,t=2,p=1$c29tZXNhbHQ$AAECAw", Argon2Error::UnsupportedVersion),
+        case::missing_version("$argon2id$m=64,t=2,p=1$c29tZXNhbHQ$AAECAw", Argon2Error::InvalidPhcString),
+        case::missing_param("$argon2id$v=19$m=64,t=2$c29tZXNhbHQ$AAECAw", Argon2Error::InvalidPhcString),
+        case::repeated_param("$argon2id$v=19$m=64,m=64,t=2,p=1$c29tZXNhbHQ$AAECAw", Argon2Error::InvalidPhcString),
+        case::leading_zero("$argon2id$v=19$m=064,t=2,p=1$c29tZXNhbHQ$AAECAw", Argon2Error::InvalidPhcString),
+        case::bad_base64("$argon2id$v=19$m=64,t=2,p=1$c29tZXNhbHQ$AA*CAw", Argon2Error::InvalidPhcString),
+        case::too_little_memory("$argon2id$v=19$m=7,t=2,p=1$c29tZXNhbHQ$AAECAw", Argon2Error::InvalidMemoryCost),
+    )]
+    fn test_invalid_phc(encoded: &str, expected: Argon2Error) {
+        assert_eq!(encoded.parse::<PhcHash>(), Err(expected));
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        assert_eq!(Argon2Params::new(Variant::Argon2id, 31, 1, 4), Err(Argon2Error::InvalidMemoryCost));
+        assert_eq!(Argon2Params::new(Variant::Argon2id, 32, 0, 4), Err(Argon2Error::InvalidTimeCost));
+        assert_eq!(Argon2Params::new(Variant::Argon2id, 32, 1, 0), Err(Argon2Error::InvalidParallelism));
+
+        let params = Argon2Params::new(Variant::Argon2id, 32, 1, 1).unwrap();
+        assert_eq!(argon2(&params, b"password", b"short", &[], &[], 32), Err(Argon2Error::SaltTooShort));
+        assert_eq!(argon2(&params, b"password", b"somesalt", &[], &[], 3), Err(Argon2Error::TagTooShort));
+    }
+}
@@ -0,0 +1,421 @@
+// Measuring how fast the algorithms are, through each of the ways into them: the iterator
+// based sha(), sha_sum on a file, and the incremental Hasher. `hashsum bench` runs this, and
+// so does `cargo bench --bench algorithms`.
+//
+// Each measurement is made the way criterion makes them: a warm-up, which also estimates how
+// long a run takes, then up to SAMPLES samples of as many runs as fit in the time given,
+// reporting the median of the samples. Cycles are read from the time stamp counter, which
+// ticks at a fixed rate rather than the current clock of the core; elsewhere than on x86_64
+// there is no counter, and no cycles/byte.
+
+use std::convert::TryFrom;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::hint::black_box;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::cli::{algorithms, split_args, Arg, OptionSpec};
+use crate::range::parse_size;
+use crate::records::json_string;
+use crate::sha::{sha, Algorithm, Hasher, ShaParams, ALGORITHMS, SHA1, SHA256, SHA384, SHA512};
+use crate::sha_sum;
+
+const SAMPLES: usize = 10;
+
+pub const DEFAULT_SIZES: [u64;8] = [16, 256, 4 << 10, 64 << 10, 1 << 20, 16 << 20, 256 << 20, 1 << 30];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryPoint {
+    // sha(), over an iterator of bytes
+    Iterator,
+    // sha_sum(), reading a file
+    File,
+    // Hasher::new, update and finish
+    Incremental,
+}
+
+pub const ENTRY_POINTS: [EntryPoint;3] = [EntryPoint::Iterator, EntryPoint::File, EntryPoint::Incremental];
+
+impl EntryPoint {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EntryPoint::Iterator => "sha",
+            EntryPoint::File => "sha_sum",
+            EntryPoint::Incremental => "incremental",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<EntryPoint> {
+        ENTRY_POINTS.iter().cloned().find(|e| e.name() == name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Measurement {
+    pub algorithm: Algorithm,
+    pub entry: EntryPoint,
+    pub size: u64,
+    pub samples: usize,
+    // the runs timed, over all samples
+    pub iterations: u64,
+    // for one run, the median over the samples
+    pub seconds: f64,
+    pub cycles: Option<f64>,
+}
+
+impl Measurement {
+    pub fn mb_per_s(&self) -> f64 {
+        self.size as f64 / self.seconds / 1e6
+    }
+
+    pub fn cycles_per_byte(&self) -> Option<f64> {
+        self.cycles.map(|cycles| cycles / self.size as f64)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn cycle_count() -> Option<u64> {
+    Some(unsafe { std::arch::x86_64::_rdtsc() })
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn cycle_count() -> Option<u64> {
+    None
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) { (values[middle - 1] + values[middle]) / 2.0 } else { values[middle] }
+}
+
+struct Timing {
+    samples: usize,
+    iterations: u64,
+    seconds: f64,
+    cycles: Option<f64>,
+}
+
+// Times run, spending about time on it after a warm-up of a tenth of that; a run taking longer
+// than time gives a single sample of one run. The digest of the last run, and the timing.
+fn measure<F: FnMut() -> io::Result<Vec<u8>>>(time: Duration, mut run: F) -> io::Result<(Vec<u8>, Timing)> {
+    let start = Instant::now();
+    let mut digest = run()?;
+    let mut runs = 1u64;
+    while start.elapsed() < time / 10 {
+        digest = black_box(run()?);
+        runs += 1;
+    }
+    let estimate = (start.elapsed().as_secs_f64() / runs as f64).max(1e-9);
+
+    let budget = time.as_secs_f64();
+    let samples = ((budget / estimate) as usize).clamp(1, SAMPLES);
+    let per_sample = ((budget / samples as f64 / estimate) as u64).max(1);
+
+    let mut seconds = Vec::with_capacity(samples);
+    let mut cycles = Vec::with_capacity(samples);
+    for _ in 0..samples {
+        let (start, first_cycle) = (Instant::now(), cycle_count());
+        for _ in 0..per_sample {
+            digest = black_box(run()?);
+        }
+        let elapsed = start.elapsed().as_secs_f64().max(1e-9);
+        seconds.push(elapsed / per_sample as f64);
+        if let (Some(first), Some(last)) = (first_cycle, cycle_count()) {
+            cycles.push(last.wrapping_sub(first) as f64 / per_sample as f64);
+        }
+    }
+    let cycles = if cycles.len() == samples { Some(median(&mut cycles)) } else { None };
+    Ok((digest, Timing{samples, iterations: samples as u64 * per_sample, seconds: median(&mut seconds), cycles}))
+}
+
+fn measure_entry<T: Copy>(params: ShaParams<T>, entry: EntryPoint, data: &[u8], file: &PathBuf, time: Duration)
+    -> io::Result<(Vec<u8>, Timing)>
+{
+    match entry {
+        EntryPoint::Iterator => measure(time, || Ok(sha(params, black_box(data).iter().cloned()))),
+        EntryPoint::File => measure(time, || sha_sum(params, file)),
+        EntryPoint::Incremental => measure(time, || {
+            let mut hasher = Hasher::new(params);
+            hasher.update(black_box(data));
+            Ok(hasher.finish())
+        }),
+    }
+}
+
+// Cheap pseudo-random content, so nothing along the way can take shortcuts.
+fn pseudo_random(size: usize) -> Vec<u8> {
+    let mut state: u64 = 0x2545f4914f6cdd1d;
+    let mut data = Vec::with_capacity(size + 8);
+    while data.len() < size {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        data.extend_from_slice(&state.to_le_bytes());
+    }
+    data.truncate(size);
+    data
+}
+
+// Measures each algorithm through each entry point on each size in turn, telling report about
+// every measurement as it is made. For sha_sum, a file of the size is written to dir, and
+// removed once measured. It is an error if the entry points disagree on a digest.
+pub fn bench<F: FnMut(&Measurement)>(options: &Options, dir: &Path, mut report: F) -> io::Result<()> {
+    let largest = options.sizes.iter().cloned().max().unwrap_or(0);
+    let data = pseudo_random(usize::try_from(largest).map_err(|e| io::Error::new(io::ErrorKind::OutOfMemory, e))?);
+    let file = dir.join(format!("rust-crypto-bench-{}", std::process::id()));
+
+    for &size in &options.sizes {
+        let data = &data[..size as usize];
+        if options.entries.contains(&EntryPoint::File) {
+            fs::write(&file, data)?;
+        }
+        let result = options.algorithms.iter().try_for_each(|&algorithm| {
+            let mut digests: Vec<Vec<u8>> = Vec::new();
+            for &entry in &options.entries {
+                let (digest, timing) = match algorithm {
+                    Algorithm::Sha256 => measure_entry(SHA256, entry, data, &file, options.time)?,
+                    Algorithm::Sha384 => measure_entry(SHA384, entry, data, &file, options.time)?,
+                    Algorithm::Sha512 => measure_entry(SHA512, entry, data, &file, options.time)?,
+                    Algorithm::Sha1 => measure_entry(SHA1, entry, data, &file, options.time)?,
+                };
+                if digests.iter().any(|d| *d != digest) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData,
+                        format!("{} gives another {} digest of {} bytes than the other entry points", entry.name(), algorithm.name(), size)));
+                }
+                digests.push(digest);
+                report(&Measurement{algorithm, entry, size, samples: timing.samples, iterations: timing.iterations,
+                    seconds: timing.seconds, cycles: timing.cycles});
+            }
+            Ok(())
+        });
+        if options.entries.contains(&EntryPoint::File) {
+            let _ = fs::remove_file(&file);
+        }
+        result?;
+    }
+    Ok(())
+}
+
+// 4096 as "4 KiB", but 1000 as "1000 B".
+pub fn size_name(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    let unit = UNITS.iter().enumerate().rev().find(|(i, _)| size > 0 && size.is_multiple_of(1 << (10 * (i + 1))));
+    match unit {
+        Some((i, name)) => format!("{} {}", size >> (10 * (i + 1)), name),
+        None => format!("{} B", size),
+    }
+}
+
+pub fn header() -> String {
+    format!("{:<9} {:<12} {:>8} {:>10} {:>12}\n", "algorithm", "entry point", "size", "MB/s", "cycles/byte")
+}
+
+pub fn format_text(m: &Measurement) -> String {
+    let cycles = m.cycles_per_byte().map_or("-".to_string(), |c| format!("{:.2}", c));
+    format!("{:<9} {:<12} {:>8} {:>10.1} {:>12}\n", m.algorithm.name(), m.entry.name(), size_name(m.size), m.mb_per_s(), cycles)
+}
+
+// A JSON object on a line of its own.
+pub fn format_json(m: &Measurement) -> String {
+    let cycles = m.cycles_per_byte().map_or("null".to_string(), |c| format!("{:.3}", c));
+    format!("{{\"algorithm\":{},\"entry\":{},\"size\":{},\"samples\":{},\"iterations\":{},\"seconds\":{:.4e},\"mb_per_s\":{:.3},\"cycles_per_byte\":{}}}\n",
+        json_string(m.algorithm.name()), json_string(m.entry.name()), m.size, m.samples, m.iterations, m.seconds, m.mb_per_s(), cycles)
+}
+
+// The bench command line.
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub algorithms: Vec<Algorithm>,
+    pub entries: Vec<EntryPoint>,
+    pub sizes: Vec<u64>,
+    // about how long to spend on each measurement
+    pub time: Duration,
+    pub json: bool,
+}
+
+pub fn usage(program: &str) -> String {
+    let mut text = format!("Usage: {} [OPTION]...\n", program);
+    text.push_str("Measure how fast each algorithm hashes, through each entry point, on inputs of each size,\n");
+    text.push_str("and print MB/s and cycles/byte.\n\n");
+    text.push_str("  -a, --algorithm=LIST  the hashes to measure; all of sha256, sha384 and sha512 by default\n");
+    text.push_str("      --entry=LIST      the entry points to measure: sha (the iterator), sha_sum (a file)\n");
+    text.push_str("                        and incremental (Hasher); all of them by default\n");
+    text.push_str("      --sizes=LIST      the input sizes, e.g. 16,4K,1M; 16 bytes to 1G by default\n");
+    text.push_str("      --time=SECONDS    about how long to spend on each measurement; 1 by default\n");
+    text.push_str("      --json            print a JSON object per measurement, a line each\n");
+    text
+}
+
+const OPTIONS: OptionSpec = OptionSpec{
+    long: &["algorithm", "entry", "sizes", "time", "json"],
+    short: &[('a', "algorithm")],
+    with_value: &["algorithm", "entry", "sizes", "time"],
+};
+
+fn entry_points(list: &str) -> Result<Vec<EntryPoint>, String> {
+    let mut result = Vec::new();
+    for name in list.split(',') {
+        let entry = EntryPoint::from_name(name)
+            .ok_or_else(|| format!("unknown entry point '{}'; choose one of: sha, sha_sum, incremental", name))?;
+        if !result.contains(&entry) {
+            result.push(entry);
+        }
+    }
+    Ok(result)
+}
+
+fn sizes(list: &str) -> Result<Vec<u64>, String> {
+    list.split(',').map(|size| match parse_size(size) {
+        Some(size) if size > 0 => Ok(size),
+        _ => Err(format!("invalid size '{}'", size)),
+    }).collect()
+}
+
+fn time(text: &str) -> Result<Duration, String> {
+    match text.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(Duration::from_secs_f64(seconds)),
+        _ => Err(format!("invalid time '{}'", text)),
+    }
+}
+
+// args[0] is the name of the program.
+pub fn parse_args(args: &[OsString]) -> Result<Options, String> {
+    let mut options = Options{
+        algorithms: ALGORITHMS.to_vec(),
+        entries: ENTRY_POINTS.to_vec(),
+        sizes: DEFAULT_SIZES.to_vec(),
+        time: Duration::from_secs(1),
+        json: false,
+    };
+
+    for arg in split_args(args, &OPTIONS)? {
+        match arg {
+            Arg::Operand(operand) => return Err(format!("extra operand '{}'", operand.to_string_lossy())),
+            Arg::Option(name, value) => {
+                let value = value.unwrap_or_default();
+                match name.as_str() {
+                    "algorithm" => options.algorithms = algorithms(&value)?,
+                    "entry" => options.entries = entry_points(&value)?,
+                    "sizes" => options.sizes = sizes(&value)?,
+                    "time" => options.time = time(&value)?,
+                    "json" => options.json = true,
+                    _ => unreachable!("option --{} is in OPTIONS but not handled", name),
+                }
+            }
+        }
+    }
+    Ok(options)
+}
+
+// Runs bench; args[0] is the name to give in messages. Returns the exit code.
+pub fn run(args: Vec<OsString>) -> i32 {
+    let program = args.first().map(|p| p.to_string_lossy().into_owned()).unwrap_or_else(|| "bench".to_string());
+
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{}: {}", program, msg);
+            eprint!("{}", usage(&program));
+            return 1;
+        }
+    };
+
+    if !options.json {
+        print!("{}", header());
+    }
+    let result = bench(&options, &env::temp_dir(), |m| {
+        print!("{}", if options.json { format_json(m) } else { format_text(m) });
+    });
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}: {}", program, e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    fn args(line: &str) -> Vec<OsString> {
+        line.split_whitespace().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse_args(&args("bench")).unwrap();
+        assert_eq!(options.algorithms, ALGORITHMS.to_vec());
+        assert_eq!(options.entries, ENTRY_POINTS.to_vec());
+        assert_eq!((options.sizes.first(), options.sizes.last()), (Some(&16), Some(&(1 << 30))));
+
+        let options = parse_args(&args("bench -a sha512 --entry=sha_sum,sha --sizes=16,4K --time=0.5 --json")).unwrap();
+        assert_eq!(options, Options{algorithms: vec![Algorithm::Sha512], entries: vec![EntryPoint::File, EntryPoint::Iterator],
+            sizes: vec![16, 4096], time: Duration::from_millis(500), json: true});
+    }
+
+    #[rstest(line, expected,
+        case::entry("bench --entry=blockwise", "unknown entry point 'blockwise'; choose one of: sha, sha_sum, incremental"),
+        case::size("bench --sizes=16,0", "invalid size '0'"),
+        case::time("bench --time=-1", "invalid time '-1'"),
+        case::operand("bench file", "extra operand 'file'"),
+    )]
+    fn test_parse_errors(line: &str, expected: &str) {
+        assert_eq!(parse_args(&args(line)), Err(expected.to_string()));
+    }
+
+    #[rstest(size, expected,
+        case::bytes(16, "16 B"),
+        case::decimal(1000, "1000 B"),
+        case::kibi(4096, "4 KiB"),
+        case::gibi(1 << 30, "1 GiB"),
+        case::odd(1536 << 10, "1536 KiB"),
+    )]
+    fn test_size_name(size: u64, expected: &str) {
+        assert_eq!(size_name(size), expected);
+    }
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&mut [3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&mut [4.0, 1.0, 3.0, 2.0]), 2.5);
+    }
+
+    #[test]
+    fn test_bench() {
+        let dir = TempDir::new("bench");
+        let options = Options{algorithms: vec![Algorithm::Sha256, Algorithm::Sha384], entries: ENTRY_POINTS.to_vec(),
+            sizes: vec![16, 1000], time: Duration::from_millis(10), json: false};
+        let mut measured = Vec::new();
+        bench(&options, dir.path(), |m| measured.push(m.clone())).unwrap();
+
+        let order: Vec<(u64, &str, &str)> = measured.iter().map(|m| (m.size, m.algorithm.name(), m.entry.name())).collect();
+        assert_eq!(order[..4], [(16, "SHA256", "sha"), (16, "SHA256", "sha_sum"), (16, "SHA256", "incremental"), (16, "SHA384", "sha")]);
+        assert_eq!(order.len(), 12);
+        assert!(measured.iter().all(|m| m.seconds > 0.0 && m.samples >= 1 && m.iterations >= m.samples as u64));
+        // the scratch file is gone
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_format() {
+        let m = Measurement{algorithm: Algorithm::Sha256, entry: EntryPoint::Incremental, size: 4096, samples: 10,
+            iterations: 2000, seconds: 20.48e-6, cycles: Some(61440.0)};
+        assert_eq!(format_text(&m), "SHA256    incremental     4 KiB      200.0        15.00\n");
+        assert_eq!(format_json(&m), "{\"algorithm\":\"SHA256\",\"entry\":\"incremental\",\"size\":4096,\"samples\":10,\
+            \"iterations\":2000,\"seconds\":2.0480e-5,\"mb_per_s\":200.000,\"cycles_per_byte\":15.000}\n");
+        let m = Measurement{cycles: None, ..m};
+        assert!(format_text(&m).ends_with(" -\n"));
+        assert!(format_json(&m).ends_with("\"cycles_per_byte\":null}\n"));
+    }
+}
@@ -0,0 +1,11 @@
+use std::env;
+use std::ffi::OsString;
+use std::process;
+
+use rust_crypto::hashdiff;
+
+
+fn main() {
+    let args: Vec<OsString> = env::args_os().collect();
+    process::exit(hashdiff::run(args));
+}
@@ -0,0 +1,16 @@
+use std::env;
+use std::ffi::OsString;
+use std::process;
+
+use rust_crypto::cli;
+
+use rust_crypto::sha::Algorithm;
+
+
+// Links named sha256sum, sha384sum, ... pointing to this binary act as those tools.
+fn main() {
+    let args: Vec<OsString> = env::args_os().collect();
+    let default = args.first().and_then(|program| Algorithm::from_program_name(&program.to_string_lossy()));
+
+    process::exit(cli::run(default, args));
+}
@@ -1,20 +1,14 @@
 use std::env;
-use std::io;
+use std::ffi::OsString;
+use std::process;
 
-use rust_crypto::{get_file_names, sha_sum};
+use rust_crypto::cli;
 
-use rust_crypto::sha::SHA512;
+use rust_crypto::sha::Algorithm;
 
-extern crate hex;
 
+fn main() {
+    let args: Vec<OsString> = env::args_os().collect();
 
-fn main() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-
-    for p in get_file_names(args)? {
-        let hash = sha_sum(SHA512, &p).unwrap();
-        println!("{} {}", hex::encode(hash), p.display());
-    }
-
-    Ok(())
+    process::exit(cli::run(Some(Algorithm::Sha512), args));
 }
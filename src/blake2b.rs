@@ -0,0 +1,167 @@
+// BLAKE2b (RFC 7693), with variable output length and optional key.
+// Mainly here as the hash underneath Argon2.
+
+use std::convert::TryInto;
+
+const IV: [u64;8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+const SIGMA: [[usize;16];12] = [
+    [ 0,  1,  2,  3,  4,  5,  6,  7,  8,  9, 10, 11, 12, 13, 14, 15],
+    [14, 10,  4,  8,  9, 15, 13,  6,  1, 12,  0,  2, 11,  7,  5,  3],
+    [11,  8, 12,  0,  5,  2, 15, 13, 10, 14,  3,  6,  7,  1,  9,  4],
+    [ 7,  9,  3,  1, 13, 12, 11, 14,  2,  6,  5, 10,  4,  0, 15,  8],
+    [ 9,  0,  5,  7,  2,  4, 10, 15, 14,  1, 11, 12,  6,  8,  3, 13],
+    [ 2, 12,  6, 10,  0, 11,  8,  3,  4, 13,  7,  5, 15, 14,  1,  9],
+    [12,  5,  1, 15, 14, 13,  4, 10,  0,  7,  6,  3,  9,  2,  8, 11],
+    [13, 11,  7, 14, 12,  1,  3,  9,  5,  0, 15,  4,  8,  6,  2, 10],
+    [ 6, 15, 14,  9, 11,  3,  0,  8, 12,  2, 13,  7,  1,  4, 10,  5],
+    [10,  2,  8,  4,  7,  6,  1,  5, 15, 11,  9, 14,  3, 12, 13,  0],
+    [ 0,  1,  2,  3,  4,  5,  6,  7,  8,  9, 10, 11, 12, 13, 14, 15],
+    [14, 10,  4,  8,  9, 15, 13,  6,  1, 12,  0,  2, 11,  7,  5,  3],
+];
+
+const BLOCK_SIZE: usize = 128;
+pub const MAX_OUT_LEN: usize = 64;
+
+fn compress(h: &mut [u64;8], block: &[u8], count: u128, last: bool) {
+    let m: Vec<u64> = block.chunks_exact(8).map(|c| u64::from_le_bytes(c.try_into().unwrap())).collect();
+
+    let mut v = [0u64;16];
+    v[..8].copy_from_slice(h);
+    v[8..].copy_from_slice(&IV);
+    v[12] ^= count as u64;
+    v[13] ^= (count >> 64) as u64;
+    if last {
+        v[14] = !v[14];
+    }
+
+    macro_rules! g {
+        ($a:expr, $b:expr, $c:expr, $d:expr, $x:expr, $y:expr) => {
+            v[$a] = v[$a].wrapping_add(v[$b]).wrapping_add($x);
+            v[$d] = (v[$d] ^ v[$a]).rotate_right(32);
+            v[$c] = v[$c].wrapping_add(v[$d]);
+            v[$b] = (v[$b] ^ v[$c]).rotate_right(24);
+            v[$a] = v[$a].wrapping_add(v[$b]).wrapping_add($y);
+            v[$d] = (v[$d] ^ v[$a]).rotate_right(16);
+            v[$c] = v[$c].wrapping_add(v[$d]);
+            v[$b] = (v[$b] ^ v[$c]).rotate_right(63);
+        }
+    }
+
+    for s in SIGMA.iter() {
+        g!(0, 4,  8, 12, m[s[ 0]], m[s[ 1]]);
+        g!(1, 5,  9, 13, m[s[ 2]], m[s[ 3]]);
+        g!(2, 6, 10, 14, m[s[ 4]], m[s[ 5]]);
+        g!(3, 7, 11, 15, m[s[ 6]], m[s[ 7]]);
+        g!(0, 5, 10, 15, m[s[ 8]], m[s[ 9]]);
+        g!(1, 6, 11, 12, m[s[10]], m[s[11]]);
+        g!(2, 7,  8, 13, m[s[12]], m[s[13]]);
+        g!(3, 4,  9, 14, m[s[14]], m[s[15]]);
+    }
+
+    for i in 0..8 {
+        h[i] ^= v[i] ^ v[i + 8];
+    }
+}
+
+#[derive(Clone)]
+pub struct Blake2b {
+    h: [u64;8],
+    pending: Vec<u8>,
+    count: u128,
+    out_len: usize,
+}
+
+impl Blake2b {
+    pub fn new(out_len: usize) -> Blake2b {
+        Blake2b::new_keyed(out_len, &[])
+    }
+
+    pub fn new_keyed(out_len: usize, key: &[u8]) -> Blake2b {
+        assert!((1..=MAX_OUT_LEN).contains(&out_len), "BLAKE2b output length must be 1..=64");
+        assert!(key.len() <= 64, "BLAKE2b key must be at most 64 bytes");
+
+        let mut h = IV;
+        h[0] ^= 0x01010000 ^ ((key.len() as u64) << 8) ^ out_len as u64;
+
+        let mut hasher = Blake2b{h, pending: Vec::with_capacity(BLOCK_SIZE), count: 0, out_len};
+        if !key.is_empty() {
+            let mut block = key.to_vec();
+            block.resize(BLOCK_SIZE, 0);
+            hasher.update(&block);
+        }
+        hasher
+    }
+
+    // The last block must be compressed with the final flag set, so a full block is only
+    // compressed once more data is known to follow it.
+    pub fn update(&mut self, data: &[u8]) {
+        let mut data = data;
+        while !data.is_empty() {
+            if self.pending.len() == BLOCK_SIZE {
+                self.count += BLOCK_SIZE as u128;
+                compress(&mut self.h, &self.pending, self.count, false);
+                self.pending.clear();
+            }
+            let take = std::cmp::min(BLOCK_SIZE - self.pending.len(), data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.count += self.pending.len() as u128;
+        let mut block = self.pending.clone();
+        block.resize(BLOCK_SIZE, 0);
+        compress(&mut self.h, &block, self.count, true);
+
+        let mut result: Vec<u8> = self.h.iter().flat_map(|w| w.to_le_bytes().to_vec()).collect();
+        result.truncate(self.out_len);
+        result
+    }
+}
+
+pub fn blake2b(out_len: usize, data: &[u8]) -> Vec<u8> {
+    let mut hasher = Blake2b::new(out_len);
+    hasher.update(data);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate hex;
+
+    // RFC 7693, appendix A
+    #[test]
+    fn test_abc() {
+        assert_eq!(hex::encode(blake2b(64, b"abc")),
+            "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d17d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923");
+    }
+
+    #[test]
+    fn test_empty_256() {
+        assert_eq!(hex::encode(blake2b(32, b"")), "0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8");
+    }
+
+    #[test]
+    fn test_two_blocks_short_output() {
+        let data: Vec<u8> = (0..=255).collect();
+        assert_eq!(hex::encode(blake2b(20, &data)), "2433af65183f411941345962733a8860df650139");
+    }
+
+    #[test]
+    fn test_keyed() {
+        let key: Vec<u8> = (0..64).collect();
+        let data: Vec<u8> = (0..200).collect();
+        let mut hasher = Blake2b::new_keyed(64, &key);
+        hasher.update(&data[..100]);
+        hasher.update(&data[100..]);
+        assert_eq!(hex::encode(hasher.finish()),
+            "3095a349d245708c7cf550118703d7302c27b60af5d4e67fc978f8a4e60953c7a04f92fcf41aee64321ccb707a895851552b1e37b00bc5e6b72fa5bcef9e3fff");
+    }
+}
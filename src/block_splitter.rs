@@ -3,13 +3,13 @@ pub struct BlockStream<I> where I: IntoIterator<Item = u8> {
     blocksize: usize
 }
 
-impl<'a, I> BlockStream<I> where I: IntoIterator<Item = u8> {
+impl<I> BlockStream<I> where I: IntoIterator<Item = u8> {
     pub fn new(blocksize: usize, message_iter: I) -> BlockStream<I> {
         BlockStream{blocksize, message_iter: message_iter.into_iter()}
     }
 }
 
-impl<'a, I> Iterator for BlockStream<I> where I: IntoIterator<Item = u8> {
+impl<I> Iterator for BlockStream<I> where I: IntoIterator<Item = u8> {
     type Item = Vec<u8>;
 
     fn next(&mut self) -> Option<Vec<u8>> {
@@ -21,7 +21,7 @@ impl<'a, I> Iterator for BlockStream<I> where I: IntoIterator<Item = u8> {
                     bytes.push(b);
                     remain -= 1;
                 }
-                None => if bytes.len()==0 {
+                None => if bytes.is_empty() {
                     return None
                 } else {
                     return Some(bytes)
@@ -0,0 +1,373 @@
+// An on-disk cache of file digests, so files unchanged since they were last hashed needn't
+// be read again. A file counts as unchanged while its device, inode, size and modification
+// time (to the nanosecond) all stay the same; on any difference its entries are ignored, and
+// replaced once the file has been hashed again.
+//
+// The cache is a text file with a header line, then a line per digest:
+//   <device> <inode> <size> <mtime_ns> <algorithm> <hex digest> <check>
+// where check is the start of the SHA-256 of the rest of the line. Lines that don't parse or
+// don't match their check are dropped, so a damaged cache costs some rehashing and nothing
+// more. It is saved to a temporary file, which is then renamed over the old one.
+
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::progress::multi_sum_input_progress;
+use crate::sha::{sha, Algorithm, BusyTime, SHA256};
+use crate::{is_stdin, multi_sum_file_timed};
+
+pub const CACHE_HEADER: &str = "# rust-crypto digest cache 1";
+
+// Files modified this recently are not cached: a write within the same tick of a coarse
+// file system clock would leave the modification time as it was.
+const SETTLE_NS: i128 = 2_000_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FileKey {
+    pub device: u64,
+    pub inode: u64,
+    pub size: u64,
+    pub mtime_ns: i128,
+}
+
+impl FileKey {
+    // None where there are no inodes to go by.
+    #[cfg(unix)]
+    pub fn from_metadata(meta: &fs::Metadata) -> Option<FileKey> {
+        use std::os::unix::fs::MetadataExt;
+        let mtime_ns = meta.mtime() as i128 * 1_000_000_000 + meta.mtime_nsec() as i128;
+        Some(FileKey{device: meta.dev(), inode: meta.ino(), size: meta.len(), mtime_ns})
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_metadata(_meta: &fs::Metadata) -> Option<FileKey> {
+        None
+    }
+}
+
+fn line_check(text: &str) -> String {
+    hex::encode(&sha(SHA256, text.bytes())[..4])
+}
+
+fn format_entry(key: &FileKey, algorithm: Algorithm, digest: &[u8]) -> String {
+    let text = format!("{} {} {} {} {} {}", key.device, key.inode, key.size, key.mtime_ns, algorithm.name(), hex::encode(digest));
+    let check = line_check(&text);
+    format!("{} {}", text, check)
+}
+
+fn parse_entry(line: &str) -> Option<(FileKey, Algorithm, Vec<u8>)> {
+    let (text, check) = line.rsplit_once(' ')?;
+    if line_check(text) != check {
+        return None;
+    }
+
+    let fields: Vec<&str> = text.split(' ').collect();
+    if fields.len() != 6 {
+        return None;
+    }
+    let key = FileKey{
+        device: fields[0].parse().ok()?,
+        inode: fields[1].parse().ok()?,
+        size: fields[2].parse().ok()?,
+        mtime_ns: fields[3].parse().ok()?,
+    };
+    let algorithm = Algorithm::from_name(fields[4])?;
+    let digest = hex::decode(fields[5]).ok().filter(|d| d.len() == algorithm.digest_size())?;
+    Some((key, algorithm, digest))
+}
+
+// device, inode and algorithm
+type EntryId = (u64, u64, Algorithm);
+
+// One entry per file and algorithm, keyed by device and inode; the entry is good while the
+// rest of its key matches. Shared by the workers hashing files, hence the lock.
+pub struct DigestCache {
+    path: Option<PathBuf>,
+    entries: Mutex<HashMap<EntryId, (FileKey, Vec<u8>)>>,
+    changed: AtomicBool,
+    rehash: bool,
+    damaged: usize,
+}
+
+impl DigestCache {
+    // A cache kept in memory only.
+    pub fn new() -> DigestCache {
+        DigestCache{path: None, entries: Mutex::new(HashMap::new()), changed: AtomicBool::new(false), rehash: false, damaged: 0}
+    }
+
+    // Never fails: a cache that is missing, unreadable or not a cache at all is taken as empty,
+    // and damaged lines are left out.
+    pub fn open(path: &Path) -> DigestCache {
+        let mut cache = DigestCache{path: Some(path.to_path_buf()), ..DigestCache::new()};
+        let bytes = fs::read(path).unwrap_or_default();
+        let text = String::from_utf8_lossy(&bytes);
+        let mut lines = text.lines();
+        if lines.next() != Some(CACHE_HEADER) {
+            cache.damaged = usize::from(!bytes.is_empty());
+            return cache;
+        }
+
+        let entries = cache.entries.get_mut().unwrap();
+        for line in lines {
+            match parse_entry(line) {
+                Some((key, algorithm, digest)) => {
+                    entries.insert((key.device, key.inode, algorithm), (key, digest));
+                }
+                None => cache.damaged += 1,
+            }
+        }
+        cache
+    }
+
+    // With rehash, nothing is taken from the cache, but what is computed still goes into it.
+    pub fn set_rehash(&mut self, rehash: bool) {
+        self.rehash = rehash;
+    }
+
+    // The number of damaged lines left out when opening, counting a bad header as one.
+    pub fn damaged(&self) -> usize {
+        self.damaged
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, key: &FileKey, algorithm: Algorithm) -> Option<Vec<u8>> {
+        if self.rehash {
+            return None;
+        }
+        match self.entries.lock().unwrap().get(&(key.device, key.inode, algorithm)) {
+            Some((cached_key, digest)) if cached_key == key => Some(digest.clone()),
+            _ => None,
+        }
+    }
+
+    // Replaces the entry for an older version of the same file.
+    pub fn insert(&self, key: FileKey, algorithm: Algorithm, digest: Vec<u8>) {
+        let entry = (key, digest);
+        let mut entries = self.entries.lock().unwrap();
+        if entries.get(&(key.device, key.inode, algorithm)) != Some(&entry) {
+            entries.insert((key.device, key.inode, algorithm), entry);
+            self.changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // Writes the cache back to its file, if anything changed.
+    pub fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) if self.changed.load(Ordering::Relaxed) => path,
+            _ => return Ok(()),
+        };
+
+        let mut lines: Vec<String> = self.entries.lock().unwrap().iter()
+            .map(|((_, _, algorithm), (key, digest))| format_entry(key, *algorithm, digest))
+            .collect();
+        lines.sort();
+
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".tmp-{}", std::process::id()));
+        let temporary = path.with_file_name(name);
+
+        let write = || -> io::Result<()> {
+            let mut out = BufWriter::new(File::create(&temporary)?);
+            writeln!(out, "{}", CACHE_HEADER)?;
+            for line in &lines {
+                writeln!(out, "{}", line)?;
+            }
+            out.into_inner()?.sync_all()?;
+            fs::rename(&temporary, path)
+        };
+        write().inspect_err(|_| {
+            let _ = fs::remove_file(&temporary);
+        })?;
+        self.changed.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl Default for DigestCache {
+    fn default() -> DigestCache {
+        DigestCache::new()
+    }
+}
+
+fn is_settled(key: &FileKey) -> bool {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as i128).unwrap_or(0);
+    now - key.mtime_ns >= SETTLE_NS
+}
+
+// Like multi_sum_input, taking what it can from the cache and adding what it had to compute.
+// A file that changed while it was being read is not cached.
+pub fn cached_sum(cache: &DigestCache, algorithms: &[Algorithm], file: &PathBuf) -> io::Result<(Vec<Vec<u8>>, u64)> {
+    cached_sum_progress(cache, algorithms, file, |_, _| (), None)
+}
+
+// Like cached_sum, telling progress how far it has got with the file, if it has to be read,
+// and adding the time spent in each algorithm to busy.
+pub fn cached_sum_progress<F: FnMut(u64, Option<u64>)>(cache: &DigestCache, algorithms: &[Algorithm], file: &PathBuf, mut progress: F,
+    busy: Option<&BusyTime>) -> io::Result<(Vec<Vec<u8>>, u64)>
+{
+    if is_stdin(file) {
+        return multi_sum_input_progress(algorithms, file, progress, busy);
+    }
+
+    let reader = File::open(file)?;
+    let key = match FileKey::from_metadata(&reader.metadata()?) {
+        Some(key) => key,
+        None => {
+            let size = reader.metadata()?.len();
+            return multi_sum_file_timed(algorithms, &reader, |hashed| progress(hashed, Some(size)), busy);
+        }
+    };
+
+    let mut digests: Vec<Option<Vec<u8>>> = algorithms.iter().map(|&a| cache.get(&key, a)).collect();
+    let missing: Vec<Algorithm> = algorithms.iter().zip(&digests).filter(|(_, d)| d.is_none()).map(|(&a, _)| a).collect();
+    if missing.is_empty() {
+        return Ok((digests.into_iter().flatten().collect(), key.size));
+    }
+
+    let (computed, size) = multi_sum_file_timed(&missing, &reader, |hashed| progress(hashed, Some(key.size)), busy)?;
+    let unchanged = size == key.size && FileKey::from_metadata(&reader.metadata()?) == Some(key);
+    let mut computed = computed.into_iter();
+    for (algorithm, digest) in algorithms.iter().zip(digests.iter_mut()).filter(|(_, d)| d.is_none()) {
+        let value = computed.next().unwrap();
+        if unchanged && is_settled(&key) {
+            cache.insert(key, *algorithm, value.clone());
+        }
+        *digest = Some(value);
+    }
+
+    Ok((digests.into_iter().flatten().collect(), size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::time::Duration;
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    // Writes a file that was last modified a minute ago, so it can be cached.
+    fn settled_file(dir: &TempDir, name: &str, content: &[u8]) -> PathBuf {
+        let path = dir.write(name, content);
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(60)).unwrap();
+        path
+    }
+
+    fn key(inode: u64) -> FileKey {
+        FileKey{device: 1, inode, size: 3, mtime_ns: 1_600_000_000_123_456_789}
+    }
+
+    #[test]
+    fn test_entry_round_trip() {
+        let digest = hex::decode(ABC_SHA256).unwrap();
+        let line = format_entry(&key(7), Algorithm::Sha256, &digest);
+        assert!(line.starts_with(&format!("1 7 3 1600000000123456789 SHA256 {} ", ABC_SHA256)));
+        assert_eq!(parse_entry(&line), Some((key(7), Algorithm::Sha256, digest)));
+
+        // any change to the line is caught by the check
+        assert_eq!(parse_entry(&line.replacen(" 3 ", " 4 ", 1)), None);
+        assert_eq!(parse_entry(&line[..line.len() - 1]), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cached_sum() {
+        let dir = TempDir::new("cache-sum");
+        let file = settled_file(&dir, "abc", b"abc");
+        let cache = DigestCache::new();
+
+        let (digests, size) = cached_sum(&cache, &[Algorithm::Sha256], &file).unwrap();
+        assert_eq!((hex::encode(&digests[0]), size), (ABC_SHA256.to_string(), 3));
+        assert_eq!(cache.len(), 1);
+
+        // a wrong entry for the same key shows the cache is used
+        let key = FileKey::from_metadata(&fs::metadata(&file).unwrap()).unwrap();
+        cache.insert(key, Algorithm::Sha256, vec![0; 32]);
+        assert_eq!(cached_sum(&cache, &[Algorithm::Sha256], &file).unwrap(), (vec![vec![0; 32]], 3));
+        assert_eq!(crate::sha_sum_cached(SHA256, &file, &cache).unwrap(), vec![0; 32]);
+
+        // only the missing algorithm is computed
+        let (digests, _) = cached_sum(&cache, &[Algorithm::Sha512, Algorithm::Sha256], &file).unwrap();
+        assert_eq!(digests[1], vec![0; 32]);
+        assert_eq!(digests[0], crate::sha::sha(crate::sha::SHA512, b"abc".iter().cloned()));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cache_invalidation() {
+        let dir = TempDir::new("cache-invalidation");
+        let file = settled_file(&dir, "abc", b"abc");
+        let mut cache = DigestCache::new();
+        cached_sum(&cache, &[Algorithm::Sha256], &file).unwrap();
+        let key = FileKey::from_metadata(&fs::metadata(&file).unwrap()).unwrap();
+        cache.insert(key, Algorithm::Sha256, vec![0; 32]);
+
+        cache.set_rehash(true);
+        let (digests, _) = cached_sum(&cache, &[Algorithm::Sha256], &file).unwrap();
+        assert_eq!(hex::encode(&digests[0]), ABC_SHA256);
+        cache.set_rehash(false);
+
+        // same size, new content and modification time
+        let file = settled_file(&dir, "abc", b"abd");
+        let (digests, _) = cached_sum(&cache, &[Algorithm::Sha256], &file).unwrap();
+        assert_ne!(hex::encode(&digests[0]), ABC_SHA256);
+        assert_eq!(cache.len(), 1);
+
+        // just written, so not cached
+        let fresh = dir.write("fresh", b"abc");
+        cached_sum(&cache, &[Algorithm::Sha256], &fresh).unwrap();
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_and_open() {
+        let dir = TempDir::new("cache-save");
+        let file = settled_file(&dir, "abc", b"abc");
+        let cache_path = dir.path().join("cache");
+
+        let cache = DigestCache::open(&cache_path);
+        assert!(cache.is_empty());
+        cached_sum(&cache, &[Algorithm::Sha256, Algorithm::Sha512], &file).unwrap();
+        cache.save().unwrap();
+
+        let cache = DigestCache::open(&cache_path);
+        assert_eq!((cache.len(), cache.damaged()), (2, 0));
+        let key = FileKey::from_metadata(&fs::metadata(&file).unwrap()).unwrap();
+        assert_eq!(cache.get(&key, Algorithm::Sha256).map(hex::encode), Some(ABC_SHA256.to_string()));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_open_damaged() {
+        let dir = TempDir::new("cache-damaged");
+        let good = format_entry(&key(1), Algorithm::Sha256, &[1; 32]);
+        let other = format_entry(&key(2), Algorithm::Sha384, &[2; 48]);
+        let text = format!("{}\n{}\n{}\ngarbage\n{}", CACHE_HEADER, good, &good[..20], &other[..other.len() - 3]);
+        let cache = DigestCache::open(&dir.write("cache", text.as_bytes()));
+        assert_eq!((cache.len(), cache.damaged()), (1, 3));
+        assert_eq!(cache.get(&key(1), Algorithm::Sha256), Some(vec![1; 32]));
+
+        let cache = DigestCache::open(&dir.write("binary", b"\xff\x00 not a cache"));
+        assert_eq!((cache.len(), cache.damaged()), (0, 1));
+        let cache = DigestCache::open(&dir.path().join("missing"));
+        assert_eq!((cache.len(), cache.damaged()), (0, 0));
+    }
+}
@@ -0,0 +1,385 @@
+// Verifying checksum files, as written by the checksum binaries or by coreutils:
+//   <hex>  <name>           (text mode)
+//   <hex> *<name>           (binary mode; the same to us)
+//   <hex> <name>            (what our binaries have always printed)
+//   SHA256 (<name>) = <hex> (BSD style, from --tag)
+// The digest may be in any of the encodings from --encoding, and names may be escaped (see escape.rs).
+// Reporting and exit status follow coreutils' sha256sum --check.
+
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use crate::encoding::decode_digest;
+use crate::escape::{escape_name, needs_escape, path_from_bytes, path_to_bytes, unescape_name};
+use crate::sha::ShaParams;
+use crate::sha_sum_input;
+
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct CheckOptions {
+    pub quiet: bool,
+    pub status: bool,
+    pub strict: bool,
+    pub warn: bool,
+    pub ignore_missing: bool,
+    // the list is separated by NULs, not newlines, and names are not escaped
+    pub zero: bool,
+}
+
+#[derive(Default, Debug, PartialEq)]
+pub struct CheckCounts {
+    pub formatted: usize,
+    pub malformed: usize,
+    pub matched: usize,
+    pub mismatched: usize,
+    pub unreadable: usize,
+    pub missing: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ChecksumLine {
+    pub digest: Vec<u8>,
+    pub name: PathBuf,
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+// algo_name is only needed for tagged lines, which name their algorithm. Names in lines
+// starting with a backslash are unescaped, unless the list is NUL separated (zero).
+pub fn parse_checksum_line(line: &[u8], algo_name: &str, digest_size: usize, zero: bool) -> Option<ChecksumLine> {
+    let start = line.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(line.len());
+    let mut line = &line[start..];
+    let escaped = !zero && line.first() == Some(&b'\\');
+    if escaped {
+        line = &line[1..];
+    }
+
+    let (digest, name) = match line.strip_prefix(algo_name.as_bytes()).and_then(|l| l.strip_prefix(b" (")) {
+        Some(tagged) => {
+            let end = rfind(tagged, b") = ")?;
+            (&tagged[end + 4..], &tagged[..end])
+        }
+        None => {
+            let space = line.iter().position(|&b| b == b' ')?;
+            let rest = &line[space + 1..];
+            let name = rest.strip_prefix(b" ").or_else(|| rest.strip_prefix(b"*")).unwrap_or(rest);
+            (&line[..space], name)
+        }
+    };
+    if name.is_empty() {
+        return None;
+    }
+
+    let name = if escaped { unescape_name(name)? } else { name.to_vec() };
+    let digest = decode_digest(std::str::from_utf8(digest).ok()?, digest_size)?;
+    Some(ChecksumLine{digest, name: path_from_bytes(&name)})
+}
+
+// A name in a result line, escaped like in the checksum lines.
+fn write_result(out: &mut dyn Write, name: &Path, result: &str) -> io::Result<()> {
+    let name = path_to_bytes(name);
+    if needs_escape(&name) {
+        out.write_all(b"\\")?;
+        out.write_all(&escape_name(&name))?;
+    } else {
+        out.write_all(&name)?;
+    }
+    writeln!(out, ": {}", result)
+}
+
+// Error messages without Rust's " (os error N)" suffix, the way coreutils prints them.
+pub fn describe_error(e: &io::Error) -> String {
+    let msg = e.to_string();
+    match msg.find(" (os error") {
+        Some(pos) => msg[..pos].to_string(),
+        None => msg,
+    }
+}
+
+fn plural<'a>(n: usize, one: &'a str, many: &'a str) -> &'a str {
+    if n == 1 { one } else { many }
+}
+
+// Checks every line of one checksum list; returns whether the list verified without problems.
+#[allow(clippy::too_many_arguments)]
+pub fn check_list<T: Copy, R: BufRead>(algo: ShaParams<T>, program: &str, list_name: &str, list: R, options: &CheckOptions,
+                                       out: &mut dyn Write, err: &mut dyn Write) -> io::Result<bool> {
+    let mut counts = CheckCounts::default();
+
+    let separator = if options.zero { 0 } else { b'\n' };
+    for (line_number, line) in list.split(separator).enumerate() {
+        let line = line?;
+        let line = line.strip_suffix(b"\r").unwrap_or(&line);
+        if line.starts_with(b"#") {
+            continue;
+        }
+
+        let entry = match parse_checksum_line(line, algo.name, algo.digest_size, options.zero) {
+            Some(entry) => entry,
+            None => {
+                counts.malformed += 1;
+                if options.warn {
+                    writeln!(err, "{}: {}: {}: improperly formatted {} checksum line", program, list_name, line_number + 1, algo.name)?;
+                }
+                continue;
+            }
+        };
+        counts.formatted += 1;
+
+        match sha_sum_input(algo, &entry.name) {
+            Ok(digest) => {
+                if digest == entry.digest {
+                    counts.matched += 1;
+                    if !options.quiet && !options.status {
+                        write_result(out, &entry.name, "OK")?;
+                    }
+                } else {
+                    counts.mismatched += 1;
+                    if !options.status {
+                        write_result(out, &entry.name, "FAILED")?;
+                    }
+                }
+            }
+            Err(e) => {
+                if options.ignore_missing && e.kind() == io::ErrorKind::NotFound {
+                    counts.missing += 1;
+                    continue;
+                }
+                counts.unreadable += 1;
+                if !options.status {
+                    writeln!(err, "{}: {}: {}", program, entry.name.display(), describe_error(&e))?;
+                    write_result(out, &entry.name, "FAILED open or read")?;
+                }
+            }
+        }
+    }
+
+    report(algo.name, program, list_name, &counts, options, err)
+}
+
+fn report(algo_name: &str, program: &str, list_name: &str, counts: &CheckCounts, options: &CheckOptions, err: &mut dyn Write) -> io::Result<bool> {
+    if counts.formatted == 0 {
+        writeln!(err, "{}: {}: no properly formatted {} checksum lines found", program, list_name, algo_name)?;
+        return Ok(false);
+    }
+
+    if !options.status {
+        if counts.malformed > 0 {
+            writeln!(err, "{}: WARNING: {} {} improperly formatted", program, counts.malformed,
+                plural(counts.malformed, "line is", "lines are"))?;
+        }
+        if counts.unreadable > 0 {
+            writeln!(err, "{}: WARNING: {} listed {} could not be read", program, counts.unreadable,
+                plural(counts.unreadable, "file", "files"))?;
+        }
+        if counts.mismatched > 0 {
+            writeln!(err, "{}: WARNING: {} computed {} did NOT match", program, counts.mismatched,
+                plural(counts.mismatched, "checksum", "checksums"))?;
+        }
+    }
+
+    let verified = counts.matched + counts.mismatched + counts.unreadable;
+    if options.ignore_missing && verified == 0 {
+        if !options.status {
+            writeln!(err, "{}: {}: no file was verified", program, list_name)?;
+        }
+        return Ok(false);
+    }
+
+    Ok(counts.mismatched == 0 && counts.unreadable == 0 && !(options.strict && counts.malformed > 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha::SHA256;
+    use crate::test_util::TempDir;
+
+    extern crate hex;
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    fn check(list: &str, options: CheckOptions) -> (bool, String, String) {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let ok = check_list(SHA256, "sha256sum", "list", list.as_bytes(), &options, &mut out, &mut err).unwrap();
+        (ok, String::from_utf8(out).unwrap(), String::from_utf8(err).unwrap())
+    }
+
+    #[test]
+    fn test_parse_text_mode() {
+        let line = format!("{}  some file.txt", ABC_SHA256);
+        let entry = parse_checksum_line(line.as_bytes(), "SHA256", 32, false).unwrap();
+        assert_eq!(hex::encode(entry.digest), ABC_SHA256);
+        assert_eq!(entry.name, Path::new("some file.txt"));
+    }
+
+    #[test]
+    fn test_parse_binary_mode_and_single_space() {
+        assert_eq!(parse_checksum_line(format!("{} *a.bin", ABC_SHA256).as_bytes(), "SHA256", 32, false).unwrap().name, Path::new("a.bin"));
+        assert_eq!(parse_checksum_line(format!("{} a.bin", ABC_SHA256.to_uppercase()).as_bytes(), "SHA256", 32, false).unwrap().name, Path::new("a.bin"));
+    }
+
+    #[test]
+    fn test_parse_tagged() {
+        let entry = parse_checksum_line(format!("SHA256 (a (1).txt) = {}", ABC_SHA256).as_bytes(), "SHA256", 32, false).unwrap();
+        assert_eq!(hex::encode(entry.digest), ABC_SHA256);
+        assert_eq!(entry.name, Path::new("a (1).txt"));
+
+        assert_eq!(parse_checksum_line(format!("SHA512 (a) = {}", ABC_SHA256).as_bytes(), "SHA256", 32, false), None);
+        assert_eq!(parse_checksum_line(format!("SHA256 (a) {}", ABC_SHA256).as_bytes(), "SHA256", 32, false), None);
+    }
+
+    #[rstest(digest,
+        case::base64("ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0="),
+        case::base64url("ungWv48Bz-pBQUDeXa4iI7ADYaOWF3qctBD_YfIAFa0"),
+        case::base32("XJ4BNP4PAHH6UQKBIDPF3LRCEOYAGYNDSYLXVHFUCD7WD4QACWWQ===="),
+    )]
+    fn test_parse_encodings(digest: &str) {
+        let expected = hex::decode(ABC_SHA256).unwrap();
+        assert_eq!(parse_checksum_line(format!("{}  a", digest).as_bytes(), "SHA256", 32, false).unwrap().digest, expected);
+        assert_eq!(parse_checksum_line(format!("SHA256 (a) = {}", digest).as_bytes(), "SHA256", 32, false).unwrap().digest, expected);
+    }
+
+    #[test]
+    fn test_parse_escaped() {
+        let line = format!("\\{}  a\\nb\\\\c", ABC_SHA256);
+        assert_eq!(parse_checksum_line(line.as_bytes(), "SHA256", 32, false).unwrap().name, Path::new("a\nb\\c"));
+        let line = format!("\\SHA256 (a\\nb) = {}", ABC_SHA256);
+        assert_eq!(parse_checksum_line(line.as_bytes(), "SHA256", 32, false).unwrap().name, Path::new("a\nb"));
+        let line = format!("\\{}  a\\tb", ABC_SHA256);
+        assert_eq!(parse_checksum_line(line.as_bytes(), "SHA256", 32, false), None);
+        // without escaping, backslashes are just part of the name
+        let line = format!("{}  a\\nb", ABC_SHA256);
+        assert_eq!(parse_checksum_line(line.as_bytes(), "SHA256", 32, false).unwrap().name, Path::new("a\\nb"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_unusual_names() {
+        let dir = TempDir::new("check-names");
+        let newline = dir.write("new\nline", b"abc");
+        let latin1 = path_from_bytes(&[path_to_bytes(dir.path()), b"/caf\xe9".to_vec()].concat());
+        std::fs::write(&latin1, b"abc").unwrap();
+
+        let mut list = format!("\\{}  {}\n", ABC_SHA256, newline.display().to_string().replace('\n', "\\n")).into_bytes();
+        list.extend_from_slice(format!("{}  ", ABC_SHA256).as_bytes());
+        list.extend_from_slice(&path_to_bytes(&latin1));
+        list.push(b'\n');
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        assert!(check_list(SHA256, "sha256sum", "list", &list[..], &CheckOptions::default(), &mut out, &mut err).unwrap());
+        let mut expected = format!("\\{}/new\\nline: OK\n", dir.path().display()).into_bytes();
+        expected.extend_from_slice(&path_to_bytes(&latin1));
+        expected.extend_from_slice(b": OK\n");
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_check_zero_terminated() {
+        let dir = TempDir::new("check-zero");
+        let good = dir.write("a\\b", b"abc");
+        let list = format!("{}  {}\0", ABC_SHA256, good.display());
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let options = CheckOptions{zero: true, ..Default::default()};
+        assert!(check_list(SHA256, "sha256sum", "list", list.as_bytes(), &options, &mut out, &mut err).unwrap());
+        assert_eq!(String::from_utf8(out).unwrap(), format!("\\{}/a\\\\b: OK\n", dir.path().display()));
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert_eq!(parse_checksum_line(b"", "SHA256", 32, false), None);
+        assert_eq!(parse_checksum_line(format!("{}  ", ABC_SHA256).as_bytes(), "SHA256", 32, false), None);
+        assert_eq!(parse_checksum_line(format!("{}  a", &ABC_SHA256[1..]).as_bytes(), "SHA256", 32, false), None);
+        assert_eq!(parse_checksum_line(format!("{}x  a", ABC_SHA256).as_bytes(), "SHA256", 32, false), None);
+        assert_eq!(parse_checksum_line(format!("{}  a", ABC_SHA256.replace('b', "g")).as_bytes(), "SHA256", 32, false), None);
+    }
+
+    #[test]
+    fn test_check_ok_and_failed() {
+        let dir = TempDir::new("check-ok-failed");
+        let good = dir.write("good", b"abc");
+        let bad = dir.write("bad", b"abd");
+        let list = format!("{}  {}\n{}  {}\n", ABC_SHA256, good.display(), ABC_SHA256, bad.display());
+
+        let (ok, out, err) = check(&list, CheckOptions::default());
+        assert!(!ok);
+        assert_eq!(out, format!("{}: OK\n{}: FAILED\n", good.display(), bad.display()));
+        assert_eq!(err, "sha256sum: WARNING: 1 computed checksum did NOT match\n");
+    }
+
+    #[test]
+    fn test_check_quiet_and_status() {
+        let dir = TempDir::new("check-quiet");
+        let good = dir.write("good", b"abc");
+        let bad = dir.write("bad", b"abd");
+        let list = format!("{}  {}\n{}  {}\n", ABC_SHA256, good.display(), ABC_SHA256, bad.display());
+
+        let (ok, out, _) = check(&list, CheckOptions{quiet: true, ..Default::default()});
+        assert!(!ok);
+        assert_eq!(out, format!("{}: FAILED\n", bad.display()));
+
+        let (ok, out, err) = check(&list, CheckOptions{status: true, ..Default::default()});
+        assert!(!ok);
+        assert_eq!((out.as_str(), err.as_str()), ("", ""));
+    }
+
+    #[test]
+    fn test_check_missing_file() {
+        let dir = TempDir::new("check-missing");
+        let good = dir.write("good", b"abc");
+        let missing = dir.path().join("missing");
+        let list = format!("{}  {}\n{}  {}\n", ABC_SHA256, good.display(), ABC_SHA256, missing.display());
+
+        let (ok, out, err) = check(&list, CheckOptions::default());
+        assert!(!ok);
+        assert_eq!(out, format!("{}: OK\n{}: FAILED open or read\n", good.display(), missing.display()));
+        assert_eq!(err, format!("sha256sum: {}: No such file or directory\nsha256sum: WARNING: 1 listed file could not be read\n", missing.display()));
+
+        let (ok, out, err) = check(&list, CheckOptions{ignore_missing: true, ..Default::default()});
+        assert!(ok);
+        assert_eq!(out, format!("{}: OK\n", good.display()));
+        assert_eq!(err, "");
+    }
+
+    #[test]
+    fn test_check_ignore_missing_nothing_verified() {
+        let list = format!("{}  /nonexistent/file\n", ABC_SHA256);
+        let (ok, _, err) = check(&list, CheckOptions{ignore_missing: true, ..Default::default()});
+        assert!(!ok);
+        assert_eq!(err, "sha256sum: list: no file was verified\n");
+    }
+
+    #[test]
+    fn test_check_malformed_lines() {
+        let dir = TempDir::new("check-malformed");
+        let good = dir.write("good", b"abc");
+        let list = format!("# a comment\n{}  {}\nnot a checksum\n\n", ABC_SHA256, good.display());
+
+        let (ok, _, err) = check(&list, CheckOptions::default());
+        assert!(ok);
+        assert_eq!(err, "sha256sum: WARNING: 2 lines are improperly formatted\n");
+
+        let (ok, _, err) = check(&list, CheckOptions{warn: true, strict: true, ..Default::default()});
+        assert!(!ok);
+        assert_eq!(err, "sha256sum: list: 3: improperly formatted SHA256 checksum line\n\
+                         sha256sum: list: 4: improperly formatted SHA256 checksum line\n\
+                         sha256sum: WARNING: 2 lines are improperly formatted\n");
+    }
+
+    #[test]
+    fn test_check_nothing_formatted() {
+        let (ok, out, err) = check("garbage\n", CheckOptions::default());
+        assert!(!ok);
+        assert_eq!(out, "");
+        assert_eq!(err, "sha256sum: list: no properly formatted SHA256 checksum lines found\n");
+    }
+}
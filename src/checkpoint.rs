@@ -0,0 +1,319 @@
+// Hashing huge files in a way that survives being interrupted: the state of the hashers is
+// saved to a checkpoint file every so often, and a later run carries on from there instead
+// of starting over. The checkpoint is removed once the file has been hashed.
+//
+// A checkpoint is only used for the very file it was made for, unchanged: same device, inode,
+// size and modification time (see cache::FileKey). Anything else is refused rather than
+// started over, since it means either the wrong checkpoint or a file that changed while it
+// was being hashed; either way the user should know.
+//
+// The checkpoint is a text file:
+//   # rust-crypto checkpoint 1
+//   file <device> <inode> <size> <mtime_ns>
+//   offset <bytes hashed>
+//   state <algorithm> <byte count> <hex chaining value> <hex partial block, or ->
+//   check <start of the SHA-256 of the lines before>
+// with a state line per algorithm. It is written to a temporary file, which is then renamed
+// over the old one, so an interruption leaves either the old checkpoint or the new one.
+
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::cache::FileKey;
+use crate::sha::{sha, Algorithm, BusyTime, Midstate, MultiHasher, SHA256};
+use crate::{read_block, READ_BUFFER_SIZE};
+
+pub const CHECKPOINT_HEADER: &str = "# rust-crypto checkpoint 1";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub key: FileKey,
+    // how far into the file the hashers have got
+    pub offset: u64,
+    pub midstates: Vec<Midstate>,
+}
+
+fn check_of(text: &str) -> String {
+    hex::encode(&sha(SHA256, text.bytes())[..4])
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Checkpoint {
+    pub fn format(&self) -> String {
+        let key = &self.key;
+        let mut text = format!("{}\nfile {} {} {} {}\noffset {}\n", CHECKPOINT_HEADER, key.device, key.inode, key.size, key.mtime_ns, self.offset);
+        for m in &self.midstates {
+            let pending = if m.pending.is_empty() { "-".to_string() } else { hex::encode(&m.pending) };
+            text.push_str(&format!("state {} {} {} {}\n", m.algorithm.name(), m.count, hex::encode(&m.chaining), pending));
+        }
+        let check = check_of(&text);
+        text.push_str(&format!("check {}\n", check));
+        text
+    }
+
+    // None if the text is damaged in any way.
+    pub fn parse(text: &str) -> Option<Checkpoint> {
+        let (body, check) = text.strip_suffix('\n')?.rsplit_once('\n')?;
+        let body = format!("{}\n", body);
+        if check.strip_prefix("check ")? != check_of(&body) {
+            return None;
+        }
+
+        let mut lines = body.lines();
+        if lines.next()? != CHECKPOINT_HEADER {
+            return None;
+        }
+        let file: Vec<&str> = lines.next()?.strip_prefix("file ")?.split(' ').collect();
+        let key = match file[..] {
+            [device, inode, size, mtime_ns] => FileKey{
+                device: device.parse().ok()?,
+                inode: inode.parse().ok()?,
+                size: size.parse().ok()?,
+                mtime_ns: mtime_ns.parse().ok()?,
+            },
+            _ => return None,
+        };
+        let offset = lines.next()?.strip_prefix("offset ")?.parse().ok()?;
+        if offset > key.size {
+            return None;
+        }
+
+        let mut midstates = Vec::new();
+        for line in lines {
+            let fields: Vec<&str> = line.strip_prefix("state ")?.split(' ').collect();
+            let midstate = match fields[..] {
+                [algorithm, count, chaining, pending] => Midstate{
+                    algorithm: Algorithm::from_name(algorithm)?,
+                    count: count.parse().ok()?,
+                    chaining: hex::decode(chaining).ok()?,
+                    pending: if pending == "-" { Vec::new() } else { hex::decode(pending).ok()? },
+                },
+                _ => return None,
+            };
+            if midstate.count != offset as u128 {
+                return None;
+            }
+            midstates.push(midstate);
+        }
+        Some(Checkpoint{key, offset, midstates})
+    }
+
+    // None if there is no checkpoint; an error if there is one that can't be read or used.
+    pub fn load(path: &Path) -> io::Result<Option<Checkpoint>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        match Checkpoint::parse(&text) {
+            Some(checkpoint) => Ok(Some(checkpoint)),
+            None => Err(invalid(format!("the checkpoint {} is damaged; remove it to start over", path.display()))),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".tmp-{}", std::process::id()));
+        let temporary = path.with_file_name(name);
+
+        let write = || -> io::Result<()> {
+            let mut out = File::create(&temporary)?;
+            out.write_all(self.format().as_bytes())?;
+            out.sync_all()?;
+            fs::rename(&temporary, path)
+        };
+        write().inspect_err(|_| {
+            let _ = fs::remove_file(&temporary);
+        })
+    }
+}
+
+// The size is where the end is, for block devices, which have no size of their own.
+fn file_key(file: &mut File) -> io::Result<FileKey> {
+    let mut key = FileKey::from_metadata(&file.metadata()?)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "checkpoints need file identities, which this system lacks"))?;
+    key.size = file.seek(SeekFrom::End(0))?;
+    Ok(key)
+}
+
+// Hashes the file, carrying on from the checkpoint if there is one, and saving a checkpoint
+// whenever interval has passed since the last. As for the other hashing functions, progress
+// is told the bytes read so far and the total, which here are what this run reads: after
+// the part hashed before. Once the file is done, the checkpoint is removed. The time this run
+// spends in each algorithm is added to busy.
+pub fn hash_checkpointed<F: FnMut(u64, Option<u64>)>(algorithms: &[Algorithm], file: &Path, checkpoint: &Path,
+    interval: Duration, mut progress: F, busy: Option<&BusyTime>) -> io::Result<(Vec<Vec<u8>>, u64)>
+{
+    let mut reader = File::open(file)?;
+    let key = file_key(&mut reader)?;
+
+    let (mut hasher, mut offset) = match Checkpoint::load(checkpoint)? {
+        None => (MultiHasher::new(algorithms), 0),
+        Some(saved) => {
+            if saved.key != key {
+                return Err(invalid(format!("{} has changed since the checkpoint {} was saved; remove it to start over",
+                    file.display(), checkpoint.display())));
+            }
+            let saved_algorithms: Vec<Algorithm> = saved.midstates.iter().map(|m| m.algorithm).collect();
+            if saved_algorithms != algorithms {
+                let names: Vec<&str> = saved_algorithms.iter().map(|a| a.name()).collect();
+                return Err(invalid(format!("the checkpoint {} is for {}", checkpoint.display(), names.join(","))));
+            }
+            let hasher = MultiHasher::from_midstates(&saved.midstates)
+                .ok_or_else(|| invalid(format!("the checkpoint {} is damaged; remove it to start over", checkpoint.display())))?;
+            (hasher, saved.offset)
+        }
+    };
+
+    if busy.is_some() {
+        hasher.time();
+    }
+    reader.seek(SeekFrom::Start(offset))?;
+    let resumed_at = offset;
+    let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+    let mut saved_at = Instant::now();
+    loop {
+        let len = read_block(&mut reader, &mut buffer)?;
+        hasher.update(&buffer[..len]);
+        offset += len as u64;
+        if len < buffer.len() {
+            break;
+        }
+        if saved_at.elapsed() >= interval {
+            Checkpoint{key, offset, midstates: hasher.midstates()}.save(checkpoint)?;
+            saved_at = Instant::now();
+        }
+        progress(offset - resumed_at, Some(key.size - resumed_at));
+    }
+    progress(offset - resumed_at, Some(key.size - resumed_at));
+    if let Some(busy) = busy {
+        busy.add(&hasher);
+    }
+
+    if offset != key.size || file_key(&mut reader)? != key {
+        return Err(invalid(format!("{} changed while it was being hashed", file.display())));
+    }
+    match fs::remove_file(checkpoint) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => (),
+    }
+    Ok((hasher.finish(), offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn data() -> Vec<u8> {
+        (0..3 * READ_BUFFER_SIZE as u32 + 100).map(|i| ((i * 31) >> 3) as u8).collect()
+    }
+
+    fn digests(data: &[u8]) -> Vec<Vec<u8>> {
+        let mut hasher = MultiHasher::new(&[Algorithm::Sha256, Algorithm::Sha512]);
+        hasher.update(data);
+        hasher.finish()
+    }
+
+    // The checkpoint a run interrupted after hashing the first part bytes would have left.
+    fn interrupted(file: &Path, part: usize) -> Checkpoint {
+        let data = fs::read(file).unwrap();
+        let mut hasher = MultiHasher::new(&[Algorithm::Sha256, Algorithm::Sha512]);
+        hasher.update(&data[..part]);
+        let key = FileKey::from_metadata(&fs::metadata(file).unwrap()).unwrap();
+        Checkpoint{key, offset: part as u64, midstates: hasher.midstates()}
+    }
+
+    #[test]
+    fn test_format_and_parse() {
+        let dir = TempDir::new("checkpoint-format");
+        let file = dir.write("data", &data());
+        let checkpoint = interrupted(&file, 1000);
+        let text = checkpoint.format();
+        assert!(text.starts_with(&format!("{}\nfile ", CHECKPOINT_HEADER)));
+        assert!(text.contains("\noffset 1000\nstate SHA256 1000 "));
+        assert_eq!(Checkpoint::parse(&text), Some(checkpoint));
+
+        assert_eq!(Checkpoint::parse(&text.replace("offset 1000", "offset 1001")), None);
+        assert_eq!(Checkpoint::parse(&text[..text.len() - 2]), None);
+    }
+
+    #[test]
+    fn test_saves_and_removes_checkpoints() {
+        let dir = TempDir::new("checkpoint-saves");
+        let data = data();
+        let file = dir.write("data", &data);
+        let checkpoint = dir.path().join("checkpoint");
+
+        let mut saved = Vec::new();
+        let result = hash_checkpointed(&[Algorithm::Sha256, Algorithm::Sha512], &file, &checkpoint, Duration::ZERO, |_, _| {
+            saved.push(Checkpoint::load(&checkpoint).unwrap().map(|c| c.offset));
+        }, None).unwrap();
+        assert_eq!(result, (digests(&data), data.len() as u64));
+        let expected: Vec<Option<u64>> = (1..=3).map(|i| Some(i * READ_BUFFER_SIZE as u64)).collect();
+        assert_eq!(saved[..3], expected[..]);
+        assert!(!checkpoint.exists());
+    }
+
+    #[test]
+    fn test_resumes() {
+        let dir = TempDir::new("checkpoint-resumes");
+        let data = data();
+        let file = dir.write("data", &data);
+        let checkpoint = dir.path().join("checkpoint");
+        // an odd place to stop, with a partial block
+        interrupted(&file, READ_BUFFER_SIZE + 77).save(&checkpoint).unwrap();
+
+        let (mut first, mut last) = (None, None);
+        let result = hash_checkpointed(&[Algorithm::Sha256, Algorithm::Sha512], &file, &checkpoint, Duration::from_secs(60), |read, total| {
+            first.get_or_insert((read, total));
+            last = Some((read, total));
+        }, None).unwrap();
+        assert_eq!(result, (digests(&data), data.len() as u64));
+        // counted from where it carried on
+        let left = data.len() as u64 - READ_BUFFER_SIZE as u64 - 77;
+        assert_eq!(first, Some((READ_BUFFER_SIZE as u64, Some(left))));
+        assert_eq!(last, Some((left, Some(left))));
+        assert!(!checkpoint.exists());
+    }
+
+    #[test]
+    fn test_refuses() {
+        let dir = TempDir::new("checkpoint-refuses");
+        let file = dir.write("data", &data());
+        let checkpoint = dir.path().join("checkpoint");
+        let algorithms = [Algorithm::Sha256, Algorithm::Sha512];
+        let hash = |algorithms: &[Algorithm]| hash_checkpointed(algorithms, &file, &checkpoint, Duration::from_secs(60), |_, _| (), None)
+            .unwrap_err().to_string();
+
+        let mut changed = interrupted(&file, 1000);
+        changed.key.size += 1;
+        changed.save(&checkpoint).unwrap();
+        assert!(hash(&algorithms).contains("has changed since the checkpoint"));
+
+        interrupted(&file, 1000).save(&checkpoint).unwrap();
+        assert!(hash(&[Algorithm::Sha256]).ends_with("is for SHA256,SHA512"));
+
+        fs::write(&checkpoint, "# rust-crypto checkpoint 1\n").unwrap();
+        assert!(hash(&algorithms).contains("is damaged"));
+
+        // the check line is correct, but the offset is past the end of the file
+        let mut past_end = interrupted(&file, 1000);
+        past_end.offset = past_end.key.size + 1;
+        for midstate in &mut past_end.midstates {
+            midstate.count = past_end.offset as u128;
+        }
+        past_end.save(&checkpoint).unwrap();
+        assert!(hash(&algorithms).contains("is damaged"));
+        // refused checkpoints are left for the user to look at
+        assert!(checkpoint.exists());
+    }
+}
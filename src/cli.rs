@@ -0,0 +1,1048 @@
+// The command line shared by the checksum binaries in src/bin.
+
+use std::env;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, IsTerminal};
+use std::ffi::OsString;
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::bench;
+use crate::cache::{cached_sum_progress, DigestCache};
+use crate::check::{check_list, describe_error, CheckOptions};
+use crate::checkpoint::hash_checkpointed;
+use crate::encoding::Encoding;
+use crate::escape::{escape_name, needs_escape, path_to_bytes};
+use crate::glob::Pattern;
+use crate::pool::Pool;
+use crate::records::{Record, RecordFormat, RecordWriter};
+use crate::sha::{Algorithm, BusyTime, MultiHasher, ShaParams, ALGORITHMS, SHA1, SHA256, SHA384, SHA512};
+use crate::walk::{walk, WalkOptions, WalkWarning};
+use crate::progress::{multi_sum_input_progress, stats, Counter, ProgressBar};
+use crate::range::{multi_sum_input_chunks_progress, multi_sum_input_range_progress, parse_size, Chunk};
+use crate::tar::{hash_archive, TarError};
+use crate::watch::{hash_with, Watcher};
+use crate::{get_file_names, is_stdin, STDIN_NAME};
+
+#[derive(Default, Debug, PartialEq)]
+pub struct Options {
+    // empty for the default of the binary
+    pub algorithms: Vec<Algorithm>,
+    pub check: bool,
+    pub check_options: CheckOptions,
+    pub coreutils: bool,
+    pub tag: bool,
+    pub encoding: Encoding,
+    // records instead of checksum lines
+    pub format: Option<RecordFormat>,
+    pub recursive: bool,
+    pub walk_options: WalkOptions,
+    pub zero: bool,
+    // 0 for one worker per CPU
+    pub jobs: usize,
+    // the digest cache; without it, the one named by CACHE_VARIABLE, if any
+    pub cache: Option<PathBuf>,
+    pub no_cache: bool,
+    pub rehash: bool,
+    // what is hashed of each file: length bytes from offset on, or all of it from offset
+    pub offset: u64,
+    pub length: Option<u64>,
+    // the size of the chunks to cut that into, with a digest each
+    pub split: Option<u64>,
+    // the FILEs are tar archives, and the files inside them are hashed
+    pub tar: bool,
+    // hash the FILEs again whenever they are written
+    pub watch: bool,
+    // a progress bar on standard error, if it is a terminal
+    pub progress: bool,
+    // a summary of what was hashed, how fast, on standard error at the end
+    pub stats: bool,
+    // where to save the state of the hashers now and then, to carry on from after an interruption
+    pub checkpoint: Option<PathBuf>,
+    pub files: Vec<OsString>,
+}
+
+// The environment variable naming a digest cache to use when --cache isn't given.
+pub const CACHE_VARIABLE: &str = "HASHSUM_CACHE";
+
+// With --watch, how long the files must have been left alone before they are hashed again.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+// With --checkpoint, how often the state of the hashers is saved.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
+
+pub fn usage(program: &str) -> String {
+    let mut text = format!("Usage: {} [OPTION]... [FILE]...\n", program);
+    text.push_str("Print or check checksums.\n");
+    text.push_str("With no FILE, hash the files in the current directory; FILE may be - for standard input.\n");
+    if is_hashsum(program) {
+        text.push_str(&format!("'{} bench [OPTION]...' instead measures how fast the algorithms are.\n", program));
+    }
+    text.push('\n');
+    text.push_str("  -a, --algorithm=LIST  the hash to use: sha256, sha384 or sha512; several, e.g.\n");
+    text.push_str("                        sha256,sha512, give a line per hash for each file\n");
+    text.push_str("  -c, --check           read checksums from the FILEs and check them\n");
+    text.push_str("      --coreutils       with no FILE, read standard input, as coreutils does\n");
+    text.push_str("  -z, --zero            end each output line with NUL, not newline, and don't escape names;\n");
+    text.push_str("                        with --check, the lines read are NUL terminated as well\n");
+    text.push_str("      --tag             print BSD style lines: SHA256 (FILE) = DIGEST\n");
+    text.push_str("      --encoding=ENC    print digests as hex (the default), HEX, base64, base64url or base32\n");
+    text.push_str("      --format=FORMAT   print a json, jsonl or csv record per file instead of checksum lines\n");
+    text.push_str("  -j, --jobs=N          hash N files at a time; the default is one per CPU\n");
+    text.push_str("  -r, --recursive       hash all files below the directories given, or below the current one\n");
+    text.push_str("      --offset=SIZE     hash each file from byte SIZE on\n");
+    text.push_str("      --length=SIZE     hash only SIZE bytes of each file; it is an error if there are fewer\n");
+    text.push_str("      --split=SIZE      cut each file into chunks of SIZE bytes, and print a digest per chunk,\n");
+    text.push_str("                        named FILE@OFFSET+LENGTH; SIZE may be given as 64K, 1M, 1G, or 1MB\n");
+    text.push_str("      --tar             read each FILE as a tar archive, and hash the files in it; the lines\n");
+    text.push_str("                        can be checked with -c against the extracted files\n");
+    text.push_str("      --watch           hash the FILEs, then again each time one is written, until interrupted\n");
+    text.push_str("      --progress        show a progress bar on standard error, if it is a terminal\n");
+    text.push_str("      --stats           print the bytes hashed, the time taken and the rate per algorithm\n");
+    text.push_str("                        on standard error at the end\n");
+    text.push_str("      --checkpoint=FILE save how far the hashing has got to FILE every 30 seconds, and carry on\n");
+    text.push_str("                        from there if FILE exists; it is removed once done, and refused if\n");
+    text.push_str("                        the file hashed has changed. Only one FILE can be hashed this way\n");
+    text.push_str("      --cache=FILE      keep digests in FILE, and take them from there for unchanged files;\n");
+    text.push_str(&format!("                        the default is the file named by {}, if set\n", CACHE_VARIABLE));
+    text.push_str("      --no-cache        don't use a digest cache\n");
+    text.push_str("      --rehash          hash every file, and update the digest cache with the results\n");
+    text.push_str("\nThe following options are useful only with --recursive:\n");
+    text.push_str("  -L, --follow-symlinks follow symbolic links found in the directories\n");
+    text.push_str("      --include=PATTERN only hash files matching PATTERN; may be repeated\n");
+    text.push_str("      --exclude=PATTERN skip files and directories matching PATTERN; may be repeated\n");
+    text.push_str("\nThe following options are useful only when verifying checksums:\n");
+    text.push_str("      --ignore-missing  don't fail or report status for missing files\n");
+    text.push_str("      --quiet           don't print OK for each successfully verified file\n");
+    text.push_str("      --status          don't output anything, status code shows success\n");
+    text.push_str("      --strict          exit non-zero for improperly formatted checksum lines\n");
+    text.push_str("  -w, --warn            warn about improperly formatted checksum lines\n");
+    text
+}
+
+// --quiet, --status and --warn override each other; the last one given wins.
+fn set_verbosity(options: &mut CheckOptions, quiet: bool, status: bool, warn: bool) {
+    options.quiet = quiet;
+    options.status = status;
+    options.warn = warn;
+}
+
+fn algorithm_names() -> String {
+    ALGORITHMS.iter().map(|a| a.name().to_lowercase()).collect::<Vec<_>>().join(", ")
+}
+
+// A comma separated list; an algorithm given twice is used once.
+pub fn algorithms(list: &str) -> Result<Vec<Algorithm>, String> {
+    let mut result = Vec::new();
+    for name in list.split(',') {
+        let algorithm = Algorithm::from_name(name)
+            .ok_or_else(|| format!("unknown algorithm '{}'; choose one of: {}", name, algorithm_names()))?;
+        if !result.contains(&algorithm) {
+            result.push(algorithm);
+        }
+    }
+    Ok(result)
+}
+
+fn encoding(name: &str) -> Result<Encoding, String> {
+    Encoding::from_name(name).ok_or_else(|| format!("unknown encoding '{}'; choose one of: hex, HEX, base64, base64url, base32", name))
+}
+
+pub fn jobs(text: &str) -> Result<usize, String> {
+    match text.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("invalid number of jobs '{}'", text)),
+    }
+}
+
+fn format(name: &str) -> Result<RecordFormat, String> {
+    RecordFormat::from_name(name).ok_or_else(|| format!("unknown format '{}'; choose one of: json, jsonl, csv", name))
+}
+
+fn size(text: &str) -> Result<u64, String> {
+    parse_size(text).ok_or_else(|| format!("invalid size '{}'", text))
+}
+
+pub fn pattern(text: &str) -> Result<Pattern, String> {
+    Pattern::new(text).map_err(|_| format!("invalid pattern '{}'", text))
+}
+
+// The options a command knows: long names, the short letters standing for some of
+// them, and which of them take a value.
+pub struct OptionSpec<'a> {
+    pub long: &'a [&'a str],
+    pub short: &'a [(char, &'a str)],
+    pub with_value: &'a [&'a str],
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Arg {
+    // the long name, and the value if it takes one
+    Option(String, Option<String>),
+    Operand(OsString),
+}
+
+// Splits a command line (args[0] being the program) into options and operands, in order.
+// Long options take values as --name=value or --name value, short ones as -xvalue or -x value;
+// short options may be grouped, and everything after -- is an operand.
+pub fn split_args(args: &[OsString], spec: &OptionSpec) -> Result<Vec<Arg>, String> {
+    let mut result = Vec::new();
+    let mut only_operands = false;
+    let mut rest = args.iter().skip(1);
+
+    while let Some(os_arg) = rest.next() {
+        // anything not valid UTF-8 can only be an operand
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg != "-" && arg.starts_with('-') => arg,
+            _ => {
+                result.push(Arg::Operand(os_arg.clone()));
+                continue;
+            }
+        };
+        if arg == "--" {
+            only_operands = true;
+            continue;
+        }
+
+        let mut next_value = |name: &str| rest.next().map(|a| a.to_string_lossy().into_owned())
+            .ok_or_else(|| format!("option '--{}' requires an argument", name));
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            if !spec.long.contains(&name) {
+                return Err(format!("unrecognized option '{}'", arg));
+            }
+            let value = match (spec.with_value.contains(&name), value) {
+                (true, Some(value)) => Some(value),
+                (true, None) => Some(next_value(name)?),
+                (false, Some(_)) => return Err(format!("option '{}' doesn't allow an argument", arg)),
+                (false, None) => None,
+            };
+            result.push(Arg::Option(name.to_string(), value));
+            continue;
+        }
+
+        for (i, c) in arg[1..].char_indices() {
+            let name = spec.short.iter().find(|(s, _)| *s == c).map(|(_, name)| *name)
+                .ok_or_else(|| format!("invalid option -- '{}'", c))?;
+            if !spec.with_value.contains(&name) {
+                result.push(Arg::Option(name.to_string(), None));
+                continue;
+            }
+            // the rest of the group is the value, if there is any
+            let value = if i + 1 + c.len_utf8() < arg.len() {
+                arg[i + 1 + c.len_utf8()..].to_string()
+            } else {
+                next_value(name)?
+            };
+            result.push(Arg::Option(name.to_string(), Some(value)));
+            break;
+        }
+    }
+
+    Ok(result)
+}
+
+const OPTIONS: OptionSpec = OptionSpec{
+    long: &["algorithm", "check", "jobs", "coreutils", "tag", "zero", "encoding", "format", "recursive",
+            "follow-symlinks", "include", "exclude", "offset", "length", "split", "tar", "watch", "progress", "stats", "checkpoint", "cache", "no-cache", "rehash", "quiet", "status", "warn", "strict",
+            "ignore-missing"],
+    short: &[('a', "algorithm"), ('c', "check"), ('j', "jobs"), ('z', "zero"), ('r', "recursive"),
+             ('L', "follow-symlinks"), ('w', "warn")],
+    with_value: &["algorithm", "jobs", "encoding", "format", "include", "exclude", "offset", "length", "split", "checkpoint", "cache"],
+};
+
+// args[0] is the name of the program.
+pub fn parse_args(args: &[OsString]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut check_only = None;
+    let mut recursive_only = None;
+
+    for arg in split_args(args, &OPTIONS)? {
+        let (name, value) = match arg {
+            Arg::Operand(file) => {
+                options.files.push(file);
+                continue;
+            }
+            Arg::Option(name, value) => (name, value.unwrap_or_default()),
+        };
+
+        match name.as_str() {
+            "algorithm" => options.algorithms = algorithms(&value)?,
+            "check" => options.check = true,
+            "jobs" => options.jobs = jobs(&value)?,
+            "coreutils" => options.coreutils = true,
+            "tag" => options.tag = true,
+            "zero" => options.zero = true,
+            "encoding" => options.encoding = encoding(&value)?,
+            "format" => options.format = Some(format(&value)?),
+            "recursive" => options.recursive = true,
+            "follow-symlinks" => options.walk_options.follow_symlinks = true,
+            "include" => options.walk_options.include.push(pattern(&value)?),
+            "exclude" => options.walk_options.exclude.push(pattern(&value)?),
+            "offset" => options.offset = size(&value)?,
+            "length" => options.length = Some(size(&value)?),
+            "split" => options.split = Some(size(&value).ok().filter(|&s| s > 0).ok_or_else(|| format!("invalid chunk size '{}'", value))?),
+            "tar" => options.tar = true,
+            "watch" => options.watch = true,
+            "progress" => options.progress = true,
+            "stats" => options.stats = true,
+            "checkpoint" => options.checkpoint = Some(PathBuf::from(value)),
+            "cache" => options.cache = Some(PathBuf::from(value)),
+            "no-cache" => options.no_cache = true,
+            "rehash" => options.rehash = true,
+            "quiet" => set_verbosity(&mut options.check_options, true, false, false),
+            "status" => set_verbosity(&mut options.check_options, false, true, false),
+            "warn" => set_verbosity(&mut options.check_options, false, false, true),
+            "strict" => options.check_options.strict = true,
+            "ignore-missing" => options.check_options.ignore_missing = true,
+            _ => unreachable!("option --{} is in OPTIONS but not handled", name),
+        }
+
+        match name.as_str() {
+            "quiet" | "status" | "warn" | "strict" | "ignore-missing" => check_only = Some(name),
+            "follow-symlinks" | "include" | "exclude" => recursive_only = Some(name),
+            _ => (),
+        }
+    }
+
+    if let (false, Some(flag)) = (options.check, check_only) {
+        return Err(format!("the --{} option is meaningful only when verifying checksums", flag));
+    }
+    if let (false, Some(flag)) = (options.recursive, recursive_only) {
+        return Err(format!("the --{} option is meaningful only with --recursive", flag));
+    }
+    options.check_options.zero = options.zero;
+    if options.check && options.tag {
+        return Err("the --tag option is not meaningful when verifying checksums".to_string());
+    }
+    if options.check && options.encoding != Encoding::default() {
+        return Err("the --encoding option is not meaningful when verifying checksums; any encoding is accepted".to_string());
+    }
+    if options.check && options.format.is_some() {
+        return Err("the --format option is not meaningful when verifying checksums".to_string());
+    }
+    if options.format.is_some() && (options.tag || options.zero) {
+        return Err("the --format option can't be combined with --tag or --zero".to_string());
+    }
+    if options.check && options.recursive {
+        return Err("the --recursive option is not meaningful when verifying checksums".to_string());
+    }
+    if options.check && (options.offset > 0 || options.length.is_some() || options.split.is_some()) {
+        return Err("the --offset, --length and --split options are not meaningful when verifying checksums".to_string());
+    }
+    if options.check && (options.cache.is_some() || options.rehash) {
+        return Err("the --cache and --rehash options are not meaningful when verifying checksums".to_string());
+    }
+    if options.tar && (options.check || options.recursive) {
+        return Err("the --tar option can't be combined with --check or --recursive".to_string());
+    }
+    if options.tar && (options.offset > 0 || options.length.is_some() || options.split.is_some() || options.cache.is_some() || options.rehash) {
+        return Err("the --offset, --length, --split, --cache and --rehash options are not meaningful with --tar".to_string());
+    }
+    if options.watch && (options.check || options.recursive || options.tar || options.format.is_some()) {
+        return Err("the --watch option can't be combined with --check, --recursive, --tar or --format".to_string());
+    }
+    if options.watch && (options.offset > 0 || options.length.is_some() || options.split.is_some() || options.cache.is_some() || options.rehash) {
+        return Err("the --offset, --length, --split, --cache and --rehash options are not meaningful with --watch".to_string());
+    }
+    if (options.progress || options.stats) && (options.check || options.tar || options.watch) {
+        return Err("the --progress and --stats options can't be combined with --check, --tar or --watch".to_string());
+    }
+    if options.checkpoint.is_some() && (options.check || options.recursive || options.tar || options.watch) {
+        return Err("the --checkpoint option can't be combined with --check, --recursive, --tar or --watch".to_string());
+    }
+    if options.checkpoint.is_some() && (options.offset > 0 || options.length.is_some() || options.split.is_some() || options.cache.is_some() || options.rehash) {
+        return Err("the --offset, --length, --split, --cache and --rehash options are not meaningful with --checkpoint".to_string());
+    }
+    if options.checkpoint.is_some() && (options.files.len() != 1 || options.files[0] == STDIN_NAME) {
+        return Err("the --checkpoint option needs a single FILE, not standard input".to_string());
+    }
+    if options.watch && options.files.is_empty() {
+        return Err("the --watch option needs the FILEs to watch".to_string());
+    }
+    if options.check && options.algorithms.len() > 1 {
+        return Err("only one algorithm can be given when verifying checksums".to_string());
+    }
+
+    Ok(options)
+}
+
+fn check_files<T: Copy>(algo: ShaParams<T>, program: &str, options: &Options) -> bool {
+    let stdout = io::stdout();
+    let stderr = io::stderr();
+    let mut out = stdout.lock();
+    let mut err = stderr.lock();
+
+    let lists = if options.files.is_empty() { vec![OsString::from(STDIN_NAME)] } else { options.files.clone() };
+    let mut ok = true;
+
+    for list in lists {
+        let list_name = list.to_string_lossy();
+        let result = if list == STDIN_NAME {
+            let stdin = io::stdin();
+            let reader = stdin.lock();
+            check_list(algo, program, &list_name, reader, &options.check_options, &mut out, &mut err)
+        } else {
+            File::open(&list).and_then(|f|
+                check_list(algo, program, &list_name, BufReader::new(f), &options.check_options, &mut out, &mut err))
+        };
+
+        match result {
+            Ok(list_ok) => ok &= list_ok,
+            Err(e) => {
+                let _ = writeln!(err, "{}: {}: {}", program, list_name, describe_error(&e));
+                ok = false;
+            }
+        }
+    }
+
+    ok
+}
+
+// Prints the warnings of a walk; false if some file or directory couldn't be read.
+pub fn report_walk_warnings(program: &str, warnings: Vec<WalkWarning>) -> bool {
+    let mut ok = true;
+    for warning in warnings {
+        match warning {
+            WalkWarning::SpecialFile(p) => eprintln!("{}: {}: skipping special file", program, p.display()),
+            WalkWarning::SymlinkLoop(p) => eprintln!("{}: {}: skipping symbolic link loop", program, p.display()),
+            WalkWarning::Unreadable(p, e) => {
+                eprintln!("{}: {}: {}", program, p.display(), describe_error(&e));
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+fn walk_files(program: &str, options: &Options) -> (Vec<PathBuf>, bool) {
+    let roots: Vec<PathBuf> = if options.files.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        options.files.iter().map(PathBuf::from).collect()
+    };
+
+    let result = walk(&roots, &options.walk_options);
+    let ok = report_walk_warnings(program, result.warnings);
+    (result.files, ok)
+}
+
+fn hash_files(algorithms: &[Algorithm], program: &str, options: &Options, cache: Option<&DigestCache>) -> bool {
+    if options.tar {
+        let archives = if options.files.is_empty() {
+            vec![PathBuf::from(STDIN_NAME)]
+        } else {
+            options.files.iter().map(PathBuf::from).collect()
+        };
+        return hash_archives(algorithms, program, archives, options);
+    }
+    if options.recursive {
+        let (files, walk_ok) = walk_files(program, options);
+        return hash_list(algorithms, program, files, options, cache) && walk_ok;
+    }
+
+    // files that can't be hashed are reported along with the rest
+    let files = if !options.files.is_empty() {
+        options.files.iter().map(PathBuf::from).collect()
+    } else if options.coreutils {
+        vec![PathBuf::from(STDIN_NAME)]
+    } else {
+        match get_file_names(vec![program]) {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("{}: {}", program, describe_error(&e));
+                return false;
+            }
+        }
+    };
+
+    hash_list(algorithms, program, files, options, cache)
+}
+
+// The one place deciding how a digest is printed; the line includes its terminator.
+pub fn format_line(algo_name: &str, digest: &[u8], name: &Path, options: &Options) -> Vec<u8> {
+    let digest = options.encoding.encode(digest);
+    let name = path_to_bytes(name);
+    let escape = !options.zero && needs_escape(&name);
+
+    let mut line = Vec::with_capacity(digest.len() + name.len() + 16);
+    if escape {
+        line.push(b'\\');
+    }
+    let name = if escape { escape_name(&name) } else { name };
+    if options.tag {
+        line.extend_from_slice(format!("{} (", algo_name).as_bytes());
+        line.extend_from_slice(&name);
+        line.extend_from_slice(b") = ");
+        line.extend_from_slice(digest.as_bytes());
+    } else {
+        line.extend_from_slice(digest.as_bytes());
+        // two spaces, as coreutils has it: the checker takes a single space or a '*' after the
+        // digest as the separator, so a name starting with either would lose it otherwise
+        line.extend_from_slice(b"  ");
+        line.extend_from_slice(&name);
+    }
+    line.push(if options.zero { 0 } else { b'\n' });
+    line
+}
+
+fn hidden<T, F: FnOnce() -> T>(bar: Option<&ProgressBar>, f: F) -> T {
+    match bar {
+        Some(bar) => bar.hidden(f),
+        None => f(),
+    }
+}
+
+// Writes one checksum line or record per file, in the order of files; a write failing
+// stops the output, but not the hashing.
+struct Output<'a> {
+    program: &'a str,
+    options: &'a Options,
+    lines: Option<io::StdoutLock<'static>>,
+    records: Option<RecordWriter<io::StdoutLock<'static>>>,
+    // taken off the terminal for each line
+    bar: Option<&'a ProgressBar>,
+    failed: bool,
+}
+
+impl<'a> Output<'a> {
+    fn new(program: &'a str, options: &'a Options) -> Output<'a> {
+        let mut output = Output{program, options, lines: None, records: None, bar: None, failed: false};
+        match options.format {
+            Some(format) => {
+                let result = RecordWriter::new(io::stdout().lock(), format);
+                output.records = output.check(result);
+            }
+            None => output.lines = Some(io::stdout().lock()),
+        }
+        output
+    }
+
+    fn check<T>(&mut self, result: io::Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                // the reader went away, e.g. head; nothing worth telling
+                if !self.failed && e.kind() != io::ErrorKind::BrokenPipe {
+                    eprintln!("{}: {}", self.program, describe_error(&e));
+                }
+                self.failed = true;
+                None
+            }
+        }
+    }
+
+    fn write(&mut self, algo_name: &str, path: &Path, result: Result<(&[u8], u64), &io::Error>) {
+        if self.failed {
+            return;
+        }
+        let options = self.options;
+        let bar = self.bar;
+        let written = if let Some(records) = &mut self.records {
+            let digest = result.ok().map(|(digest, _)| options.encoding.encode(digest));
+            let error = result.err().map(describe_error);
+            let record = Record{
+                path,
+                algorithm: algo_name,
+                digest: digest.as_deref(),
+                size: result.ok().map(|(_, size)| size),
+                error: error.as_deref(),
+            };
+            hidden(bar, || records.write(&record))
+        } else if let (Some(lines), Ok((digest, _))) = (&mut self.lines, result) {
+            hidden(bar, || lines.write_all(&format_line(algo_name, digest, path, options)))
+        } else {
+            Ok(())
+        };
+        self.check(written);
+    }
+
+    fn flush(&mut self) {
+        if let Some(lines) = &mut self.lines {
+            let result = lines.flush();
+            self.check(result);
+        }
+    }
+
+    fn finish(mut self) -> bool {
+        let result = match (self.records.take(), self.lines.take()) {
+            (Some(records), _) => records.finish().map(|_| ()),
+            (None, Some(mut lines)) => lines.flush(),
+            (None, None) => Ok(()),
+        };
+        self.check(result);
+        !self.failed
+    }
+}
+
+// What is hashed of a file: all of it, or the range given, possibly cut into chunks. The
+// cache only holds digests of whole files.
+// The bytes read are added to read as they are, and the time spent in each algorithm to busy.
+fn hash_chunks(algorithms: &[Algorithm], file: &PathBuf, options: &Options, cache: Option<&DigestCache>, read: &Counter,
+    busy: Option<&BusyTime>) -> io::Result<Vec<Chunk>>
+{
+    let whole = |(digests, length)| vec![Chunk{offset: options.offset, length, digests}];
+    let mut last = 0;
+    let progress = |so_far: u64, _| {
+        read.fetch_add(so_far - last, Ordering::Relaxed);
+        last = so_far;
+    };
+    if let Some(checkpoint) = &options.checkpoint {
+        return hash_checkpointed(algorithms, file, checkpoint, CHECKPOINT_INTERVAL, progress, busy).map(whole);
+    }
+    match (options.split, cache) {
+        (Some(chunk_size), _) =>
+            multi_sum_input_chunks_progress(algorithms, file, options.offset, options.length, chunk_size, progress, busy),
+        _ if options.offset > 0 || options.length.is_some() =>
+            multi_sum_input_range_progress(algorithms, file, options.offset, options.length, progress, busy).map(whole),
+        (None, Some(cache)) => cached_sum_progress(cache, algorithms, file, progress, busy).map(whole),
+        (None, None) => multi_sum_input_progress(algorithms, file, progress, busy).map(whole),
+    }
+}
+
+// What will be read of the files, for the progress bar; unknown if one is standard input.
+fn expected_size(files: &[PathBuf], options: &Options) -> Option<u64> {
+    let mut total = 0;
+    for file in files {
+        if is_stdin(file) {
+            return None;
+        }
+        let size = fs::metadata(file).map_or(0, |m| m.len()).saturating_sub(options.offset);
+        total += options.length.map_or(size, |length| length.min(size));
+    }
+    Some(total)
+}
+
+// With several algorithms, each file (or chunk) gets a line or record per algorithm, in the order given.
+fn hash_list(algorithms: &[Algorithm], program: &str, files: Vec<PathBuf>, options: &Options, cache: Option<&DigestCache>) -> bool {
+    let busy = BusyTime::default();
+    let start = Instant::now();
+    let read = Counter::default();
+    let bar = match options.progress && io::stderr().is_terminal() {
+        true => Some(ProgressBar::start(read.clone(), expected_size(&files, options))),
+        false => None,
+    };
+    let mut output = Output::new(program, options);
+    output.bar = bar.as_ref();
+    let mut ok = true;
+    let mut hashed = 0;
+
+    let hash = |p: &PathBuf| hash_chunks(algorithms, p, options, cache, &read, options.stats.then_some(&busy));
+    Pool::new(options.jobs).map_ordered(&files, hash, |_, p, result| {
+        let chunks = match result {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                hidden(bar.as_ref(), || eprintln!("{}: {}: {}", program, p.display(), describe_error(&e)));
+                ok = false;
+                for algorithm in algorithms {
+                    output.write(algorithm.name(), p, Err(&e));
+                }
+                return;
+            }
+        };
+
+        hashed += 1;
+        for chunk in chunks {
+            let name = match options.split {
+                Some(_) => {
+                    let mut name = p.clone().into_os_string();
+                    name.push(format!("@{}+{}", chunk.offset, chunk.length));
+                    PathBuf::from(name)
+                }
+                None => p.clone(),
+            };
+            for (algorithm, digest) in algorithms.iter().zip(&chunk.digests) {
+                output.write(algorithm.name(), &name, Ok((digest, chunk.length)));
+            }
+        }
+    });
+
+    let ok = output.finish() && ok;
+    if let Some(bar) = bar {
+        bar.finish();
+    }
+    if options.stats {
+        let spent: Vec<(Algorithm, Duration)> = algorithms.iter().map(|&a| (a, busy.spent(a))).collect();
+        eprint!("{}", stats(hashed, read.load(Ordering::Relaxed), start.elapsed(), &spent));
+    }
+    ok
+}
+
+fn describe_tar_error(e: &TarError) -> String {
+    match e {
+        TarError::Io(e) => describe_error(e),
+        TarError::Truncated => "the archive is truncated".to_string(),
+        TarError::BadChecksum(offset) => format!("bad header checksum at byte {}", offset),
+        TarError::BadHeader(offset) => format!("bad header at byte {}", offset),
+        TarError::BadPax(offset) => format!("bad pax header at byte {}", offset),
+    }
+}
+
+// The files in each archive, in archive order, named by their paths inside it. The archives
+// are read one at a time, as a stream; an archive that turns out to be damaged is reported
+// after the lines for the files before the damage.
+fn hash_archives(algorithms: &[Algorithm], program: &str, archives: Vec<PathBuf>, options: &Options) -> bool {
+    let mut output = Output::new(program, options);
+    let mut ok = true;
+
+    for archive in archives {
+        let reader: Box<dyn Read> = if is_stdin(&archive) {
+            Box::new(io::stdin().lock())
+        } else {
+            match File::open(&archive) {
+                Ok(f) => Box::new(BufReader::new(f)),
+                Err(e) => {
+                    eprintln!("{}: {}: {}", program, archive.display(), describe_error(&e));
+                    ok = false;
+                    continue;
+                }
+            }
+        };
+
+        let result = hash_archive(algorithms, reader, |path, digests, size| {
+            for (algorithm, digest) in algorithms.iter().zip(digests) {
+                output.write(algorithm.name(), path, Ok((digest, size)));
+            }
+        });
+        if let Err(e) = result {
+            eprintln!("{}: {}: {}", program, archive.display(), describe_tar_error(&e));
+            ok = false;
+        }
+    }
+
+    output.finish() && ok
+}
+
+// Prints a line for each file, then another whenever one has been written, for as long as
+// the output can be written.
+fn watch_files(algorithms: &[Algorithm], program: &str, options: &Options) -> bool {
+    let files: Vec<PathBuf> = options.files.iter().map(PathBuf::from).collect();
+    if files.iter().any(|f| is_stdin(f)) {
+        eprintln!("{}: standard input can't be watched", program);
+        return false;
+    }
+    let mut watcher = match Watcher::new() {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            eprintln!("{}: {}", program, describe_error(&e));
+            return false;
+        }
+    };
+    for file in &files {
+        if let Err(e) = watcher.add(file) {
+            eprintln!("{}: {}: {}", program, file.display(), describe_error(&e));
+            return false;
+        }
+    }
+
+    let mut output = Output::new(program, options);
+    let mut hasher = MultiHasher::new(algorithms);
+    let mut written = files;
+    loop {
+        for file in written {
+            match hash_with(&mut hasher, &file) {
+                Ok((digests, size)) => {
+                    for (algorithm, digest) in algorithms.iter().zip(&digests) {
+                        output.write(algorithm.name(), &file, Ok((digest, size)));
+                    }
+                }
+                // it may be back; it is still watched
+                Err(e) => eprintln!("{}: {}: {}", program, file.display(), describe_error(&e)),
+            }
+        }
+        output.flush();
+        if output.failed {
+            return false;
+        }
+
+        written = match watcher.wait(WATCH_DEBOUNCE) {
+            Ok(written) => written,
+            Err(e) => {
+                eprintln!("{}: {}", program, describe_error(&e));
+                return false;
+            }
+        };
+    }
+}
+
+// The cache from --cache or the environment, unless --no-cache.
+fn open_cache(options: &Options) -> Option<DigestCache> {
+    if options.no_cache {
+        return None;
+    }
+    let path = options.cache.clone().or_else(|| env::var_os(CACHE_VARIABLE).filter(|v| !v.is_empty()).map(PathBuf::from))?;
+    let mut cache = DigestCache::open(&path);
+    cache.set_rehash(options.rehash);
+    Some(cache)
+}
+
+// Whether the program is hashsum itself, by name, rather than a link named for an algorithm.
+fn is_hashsum(program: &str) -> bool {
+    let base = program.rsplit(['/', '\\']).next().unwrap_or(program);
+    base.strip_suffix(".exe").unwrap_or(base) == "hashsum"
+}
+
+// Runs a checksum binary; the algorithms are the ones given with -a, or else the default,
+// which the binaries take from their own name. Returns the exit code.
+pub fn run(default: Option<Algorithm>, args: Vec<OsString>) -> i32 {
+    let program = args.first().map(|p| p.to_string_lossy().into_owned()).unwrap_or_else(|| "hashsum".to_string());
+
+    // `hashsum bench` measures the algorithms instead; for the tools named for an algorithm,
+    // bench is a file like any other
+    if is_hashsum(&program) && args.get(1).is_some_and(|arg| arg == "bench") {
+        let mut args = args;
+        args.splice(..2, [OsString::from(format!("{} bench", program))]);
+        return bench::run(args);
+    }
+
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{}: {}", program, msg);
+            eprint!("{}", usage(&program));
+            return 1;
+        }
+    };
+
+    let algorithms = if options.algorithms.is_empty() { default.into_iter().collect() } else { options.algorithms.clone() };
+
+    let ok = match (options.check, algorithms.first()) {
+        (_, None) => {
+            eprintln!("{}: no algorithm given; use -a with one of: {}", program, algorithm_names());
+            false
+        }
+        (true, Some(Algorithm::Sha256)) => check_files(SHA256, &program, &options),
+        (true, Some(Algorithm::Sha384)) => check_files(SHA384, &program, &options),
+        (true, Some(Algorithm::Sha512)) => check_files(SHA512, &program, &options),
+        (true, Some(Algorithm::Sha1)) => check_files(SHA1, &program, &options),
+        (false, Some(_)) if options.watch => watch_files(&algorithms, &program, &options),
+        (false, Some(_)) => {
+            let cache = open_cache(&options);
+            let ok = hash_files(&algorithms, &program, &options, cache.as_ref());
+            // a cache that can't be saved costs time next run, not correctness
+            if let Some(Err(e)) = cache.as_ref().map(DigestCache::save) {
+                eprintln!("{}: warning: can't save the digest cache: {}", program, describe_error(&e));
+            }
+            ok
+        }
+    };
+
+    if ok { 0 } else { 1 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    fn args(line: &str) -> Vec<OsString> {
+        line.split_whitespace().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_parse_plain_files() {
+        let options = parse_args(&args("sha256sum a b")).unwrap();
+        assert!(!options.check);
+        assert_eq!(options.files, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_parse_check() {
+        let options = parse_args(&args("sha256sum -c --strict --ignore-missing sums.txt")).unwrap();
+        assert!(options.check);
+        assert!(options.check_options.strict);
+        assert!(options.check_options.ignore_missing);
+        assert_eq!(options.files, vec!["sums.txt"]);
+    }
+
+    #[rstest(line, quiet, status, warn,
+        case::quiet("sha256sum -c --quiet", true, false, false),
+        case::status("sha256sum --check --status", false, true, false),
+        case::warn_last("sha256sum -c --status -w", false, false, true),
+        case::quiet_last("sha256sum -cw --quiet", true, false, false),
+    )]
+    fn test_parse_verbosity(line: &str, quiet: bool, status: bool, warn: bool) {
+        let options = parse_args(&args(line)).unwrap().check_options;
+        assert_eq!((options.quiet, options.status, options.warn), (quiet, status, warn));
+    }
+
+    #[test]
+    fn test_parse_stdin() {
+        let options = parse_args(&args("sha256sum --coreutils - a")).unwrap();
+        assert!(options.coreutils);
+        assert_eq!(options.files, vec!["-", "a"]);
+    }
+
+    #[test]
+    fn test_parse_recursive() {
+        let options = parse_args(&args("sha256sum -rL --include *.rs --exclude=target dir")).unwrap();
+        assert!(options.recursive);
+        assert!(options.walk_options.follow_symlinks);
+        assert_eq!(options.walk_options.include, vec![Pattern::new("*.rs").unwrap()]);
+        assert_eq!(options.walk_options.exclude, vec![Pattern::new("target").unwrap()]);
+        assert_eq!(options.files, vec!["dir"]);
+    }
+
+    #[rstest(line, expected,
+        case::none("sha256sum a", vec![]),
+        case::short("hashsum -a sha512 a", vec![Algorithm::Sha512]),
+        case::long("hashsum --algorithm=SHA-384 a", vec![Algorithm::Sha384]),
+        case::attached("hashsum -casha512 a", vec![Algorithm::Sha512]),
+        case::list("hashsum -a sha256,sha512,sha256 a", vec![Algorithm::Sha256, Algorithm::Sha512]),
+        case::sha1("hashsum -a sha1,sha256 a", vec![Algorithm::Sha1, Algorithm::Sha256]),
+        case::last_wins("hashsum -a sha256,sha512 -a sha384 a", vec![Algorithm::Sha384]),
+    )]
+    fn test_parse_algorithm(line: &str, expected: Vec<Algorithm>) {
+        assert_eq!(parse_args(&args(line)).unwrap().algorithms, expected);
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let options = parse_args(&args("sha256sum --offset=1K --length 100 --split 64K a")).unwrap();
+        assert_eq!((options.offset, options.length, options.split), (1024, Some(100), Some(65536)));
+    }
+
+    #[test]
+    fn test_parse_cache() {
+        let options = parse_args(&args("sha256sum --cache .sums --rehash a")).unwrap();
+        assert_eq!(options.cache, Some(PathBuf::from(".sums")));
+        assert!(options.rehash && !options.no_cache);
+        assert!(parse_args(&args("sha256sum --no-cache -c SUMS")).unwrap().no_cache);
+    }
+
+    #[rstest(line, tag, encoding,
+        case::default("sha256sum a", false, Encoding::Hex),
+        case::tag("sha256sum --tag a", true, Encoding::Hex),
+        case::upper("sha256sum --encoding HEX a", false, Encoding::HexUpper),
+        case::base64("sha256sum --tag --encoding=base64 a", true, Encoding::Base64),
+    )]
+    fn test_parse_output_format(line: &str, tag: bool, encoding: Encoding) {
+        let options = parse_args(&args(line)).unwrap();
+        assert_eq!((options.tag, options.encoding), (tag, encoding));
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(parse_args(&args("sha256sum a")).unwrap().format, None);
+        assert_eq!(parse_args(&args("sha256sum --format jsonl a")).unwrap().format, Some(RecordFormat::Jsonl));
+    }
+
+    #[test]
+    fn test_format_line() {
+        let digest = [0xfb, 0xff];
+        let name = Path::new("dir/a b");
+        let mut options = Options::default();
+        assert_eq!(format_line("SHA256", &digest, name, &options), b"fbff  dir/a b\n");
+        assert_eq!(format_line("SHA256", &digest, Path::new(" *a"), &options), b"fbff   *a\n");
+        options.tag = true;
+        options.encoding = Encoding::Base64Url;
+        assert_eq!(format_line("SHA256", &digest, name, &options), b"SHA256 (dir/a b) = -_8\n");
+    }
+
+    #[rstest(name, tag, zero, expected,
+        case::newline("a\nb", false, false, &b"\\fbff  a\\nb\n"[..]),
+        case::backslash("a\\b", true, false, &b"\\SHA256 (a\\\\b) = fbff\n"[..]),
+        case::zero("a\nb", false, true, &b"fbff  a\nb\0"[..]),
+    )]
+    fn test_format_line_escaping(name: &str, tag: bool, zero: bool, expected: &[u8]) {
+        let options = Options{tag, zero, ..Default::default()};
+        assert_eq!(format_line("SHA256", &[0xfb, 0xff], Path::new(name), &options), expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_non_utf8() {
+        use std::os::unix::ffi::OsStringExt;
+        let file = OsString::from_vec(b"caf\xe9".to_vec());
+        let options = parse_args(&[OsString::from("sha256sum"), OsString::from("-z"), file.clone()]).unwrap();
+        assert!(options.zero && options.check_options.zero);
+        assert_eq!(options.files, vec![file]);
+    }
+
+    #[rstest(program, expected,
+        case::plain("hashsum", true),
+        case::path("/usr/local/bin/hashsum", true),
+        case::windows("C:\\bin\\hashsum.exe", true),
+        case::algorithm("sha256sum", false),
+        case::prefixed("myhashsum", false),
+    )]
+    fn test_is_hashsum(program: &str, expected: bool) {
+        assert_eq!(is_hashsum(program), expected);
+    }
+
+    #[rstest(line, expected,
+        case::default("sha256sum a", 0),
+        case::short("sha256sum -j 4 a", 4),
+        case::attached("sha256sum -rj3 a", 3),
+        case::long("sha256sum --jobs=12 a", 12),
+    )]
+    fn test_parse_jobs(line: &str, expected: usize) {
+        assert_eq!(parse_args(&args(line)).unwrap().jobs, expected);
+    }
+
+    #[test]
+    fn test_split_args() {
+        let spec = OptionSpec{long: &["all", "size"], short: &[('a', "all"), ('s', "size")], with_value: &["size"]};
+        assert_eq!(split_args(&args("prog -as10 x --size=3 -- -a"), &spec), Ok(vec![
+            Arg::Option("all".to_string(), None),
+            Arg::Option("size".to_string(), Some("10".to_string())),
+            Arg::Operand(OsString::from("x")),
+            Arg::Option("size".to_string(), Some("3".to_string())),
+            Arg::Operand(OsString::from("-a")),
+        ]));
+        assert_eq!(split_args(&args("prog --size"), &spec), Err("option '--size' requires an argument".to_string()));
+    }
+
+    #[test]
+    fn test_parse_double_dash() {
+        let options = parse_args(&args("sha256sum -- -c --weird")).unwrap();
+        assert!(!options.check);
+        assert_eq!(options.files, vec!["-c", "--weird"]);
+    }
+
+    #[rstest(line, msg,
+        case::long("sha256sum --bogus", "unrecognized option '--bogus'"),
+        case::short("sha256sum -x", "invalid option -- 'x'"),
+        case::status("sha256sum --status a", "the --status option is meaningful only when verifying checksums"),
+        case::warn("sha256sum -w a", "the --warn option is meaningful only when verifying checksums"),
+        case::include("sha256sum --include=*.rs", "the --include option is meaningful only with --recursive"),
+        case::follow("sha256sum -L", "the --follow-symlinks option is meaningful only with --recursive"),
+        case::check_recursive("sha256sum -c -r", "the --recursive option is not meaningful when verifying checksums"),
+        case::missing_value("sha256sum -r --exclude", "option '--exclude' requires an argument"),
+        case::bad_algorithm("hashsum -a md5", "unknown algorithm 'md5'; choose one of: sha256, sha384, sha512, sha1"),
+        case::bad_encoding("sha256sum --encoding=b64", "unknown encoding 'b64'; choose one of: hex, HEX, base64, base64url, base32"),
+        case::check_tag("sha256sum -c --tag", "the --tag option is not meaningful when verifying checksums"),
+        case::check_split("sha256sum -c --split=1M", "the --offset, --length and --split options are not meaningful when verifying checksums"),
+        case::bad_size("sha256sum --length=1X", "invalid size '1X'"),
+        case::zero_split("sha256sum --split=0", "invalid chunk size '0'"),
+        case::check_cache("sha256sum -c --cache=c", "the --cache and --rehash options are not meaningful when verifying checksums"),
+        case::check_two_algorithms("hashsum -c -a sha256,sha512", "only one algorithm can be given when verifying checksums"),
+        case::tar_check("sha256sum --tar -c a.tar", "the --tar option can't be combined with --check or --recursive"),
+        case::tar_split("sha256sum --tar --split=1M a.tar", "the --offset, --length, --split, --cache and --rehash options are not meaningful with --tar"),
+        case::watch_recursive("sha256sum --watch -r", "the --watch option can't be combined with --check, --recursive, --tar or --format"),
+        case::progress_check("sha256sum -c --progress", "the --progress and --stats options can't be combined with --check, --tar or --watch"),
+        case::checkpoint_two_files("sha256sum --checkpoint=cp a b", "the --checkpoint option needs a single FILE, not standard input"),
+        case::checkpoint_split("sha256sum --checkpoint=cp --split=1M a", "the --offset, --length, --split, --cache and --rehash options are not meaningful with --checkpoint"),
+        case::watch_nothing("sha256sum --watch", "the --watch option needs the FILEs to watch"),
+        case::zero_jobs("sha256sum -j 0", "invalid number of jobs '0'"),
+        case::bad_jobs("sha256sum --jobs=many", "invalid number of jobs 'many'"),
+        case::bad_format("sha256sum --format=xml", "unknown format 'xml'; choose one of: json, jsonl, csv"),
+        case::format_tag("sha256sum --format=csv --tag", "the --format option can't be combined with --tag or --zero"),
+        case::check_format("sha256sum -c --format=json", "the --format option is not meaningful when verifying checksums"),
+        case::bad_pattern("sha256sum -r --exclude [a", "invalid pattern '[a'"),
+        case::unexpected_value("sha256sum -c --strict=yes", "option '--strict=yes' doesn't allow an argument"),
+    )]
+    fn test_parse_errors(line: &str, msg: &str) {
+        assert_eq!(parse_args(&args(line)), Err(msg.to_string()));
+    }
+}
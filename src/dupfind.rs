@@ -0,0 +1,421 @@
+// Finding duplicate files. Files are grouped by size, then by a hash of their first and
+// last blocks, and only the files still sharing a group are hashed in full, so most files
+// are never read to the end. Duplicates may be replaced by hard links to one copy.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+
+use crate::cache::FileKey;
+use crate::check::describe_error;
+use crate::cli::{jobs, pattern, report_walk_warnings, split_args, Arg, OptionSpec};
+use crate::pool::Pool;
+use crate::sha::{Hasher, SHA256};
+use crate::walk::{walk, WalkOptions};
+use crate::{sha_sum, sha_sum_reader};
+
+// The size of the blocks read from either end of a file for the partial hash.
+pub const PARTIAL_SIZE: u64 = 4096;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuplicateSet {
+    pub size: u64,
+    // the SHA-256 of the content
+    pub digest: Vec<u8>,
+    // in the order they were given; at least two
+    pub files: Vec<PathBuf>,
+    // the files as they were before being hashed, in the same order; None where there are
+    // no inodes to go by
+    pub keys: Vec<Option<FileKey>>,
+}
+
+impl DuplicateSet {
+    // The bytes that linking all files to one copy would free.
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.files.len() as u64 - 1)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Duplicates {
+    // the sets wasting the most first
+    pub sets: Vec<DuplicateSet>,
+    pub errors: Vec<(PathBuf, io::Error)>,
+}
+
+impl Duplicates {
+    pub fn wasted(&self) -> u64 {
+        self.sets.iter().map(DuplicateSet::wasted).sum()
+    }
+}
+
+// The SHA-256 of the first and last PARTIAL_SIZE bytes of a file of the given size. A file
+// no longer than both blocks is hashed whole, so its partial hash is its full digest.
+pub fn partial_hash(path: &Path, size: u64) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    if size <= 2 * PARTIAL_SIZE {
+        return sha_sum_reader(SHA256, file);
+    }
+
+    let mut block = vec![0u8; PARTIAL_SIZE as usize];
+    let mut hasher = Hasher::new(SHA256);
+    file.read_exact(&mut block)?;
+    hasher.update(&block);
+    file.seek(SeekFrom::Start(size - PARTIAL_SIZE))?;
+    file.read_exact(&mut block)?;
+    hasher.update(&block);
+    Ok(hasher.finish())
+}
+
+#[cfg(unix)]
+fn file_id(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+// Splits every group of files further by key, dropping the files that are left alone.
+// Errors take the file out of the running.
+fn split_groups<K, F>(groups: Vec<Vec<(PathBuf, u64)>>, pool: &Pool, key: F, errors: &mut Vec<(PathBuf, io::Error)>)
+    -> Vec<(K, Vec<(PathBuf, u64)>)>
+    where K: Ord + Send, F: Fn(&Path, u64) -> io::Result<K> + Sync
+{
+    let files: Vec<(usize, PathBuf, u64)> = groups.into_iter().enumerate()
+        .flat_map(|(group, files)| files.into_iter().map(move |(path, size)| (group, path, size)))
+        .collect();
+
+    let mut split: BTreeMap<(usize, K), Vec<(PathBuf, u64)>> = BTreeMap::new();
+    pool.map_ordered(&files, |(_, path, size)| key(path, *size), |_, (group, path, size), result| match result {
+        Ok(k) => split.entry((*group, k)).or_default().push((path.clone(), *size)),
+        Err(e) => errors.push((path.clone(), e)),
+    });
+
+    split.into_iter().filter(|(_, files)| files.len() > 1).map(|((_, k), files)| (k, files)).collect()
+}
+
+// Empty files are left out, and so are further names of a file already hard linked to an
+// earlier one, as linking them again frees nothing.
+pub fn find_duplicates(files: &[PathBuf], pool: &Pool) -> Duplicates {
+    let mut result = Duplicates::default();
+    let mut seen = HashSet::new();
+    let mut keys = HashMap::new();
+    let mut by_size: BTreeMap<u64, Vec<(PathBuf, u64)>> = BTreeMap::new();
+
+    for path in files {
+        match fs::metadata(path) {
+            Ok(meta) if meta.len() == 0 => (),
+            Ok(meta) => {
+                if file_id(&meta).is_none_or(|id| seen.insert(id)) {
+                    keys.insert(path.clone(), FileKey::from_metadata(&meta));
+                    by_size.entry(meta.len()).or_default().push((path.clone(), meta.len()));
+                }
+            }
+            Err(e) => result.errors.push((path.clone(), e)),
+        }
+    }
+
+    let groups = by_size.into_values().filter(|files| files.len() > 1).collect();
+    let partial = split_groups(groups, pool, partial_hash, &mut result.errors);
+
+    // below both blocks, the partial hash is all there is to know
+    let (whole, rest): (Vec<_>, Vec<_>) = partial.into_iter().partition(|(_, files)| files[0].1 <= 2 * PARTIAL_SIZE);
+    let rest = rest.into_iter().map(|(_, files)| files).collect();
+    let full = split_groups(rest, pool, |path, _| sha_sum(SHA256, &path.to_path_buf()), &mut result.errors);
+
+    result.sets = whole.into_iter().chain(full)
+        .map(|(digest, files)| {
+            let size = files[0].1;
+            let files: Vec<PathBuf> = files.into_iter().map(|(path, _)| path).collect();
+            let keys = files.iter().map(|path| keys[path]).collect();
+            DuplicateSet{size, digest, files, keys}
+        })
+        .collect();
+    result.sets.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.files.cmp(&b.files)));
+    result
+}
+
+// Fails if the file isn't the one the key was taken from, or has been written since.
+fn check_unchanged(path: &Path, key: Option<FileKey>) -> io::Result<()> {
+    match key {
+        Some(key) if FileKey::from_metadata(&fs::metadata(path)?) != Some(key) =>
+            Err(io::Error::new(io::ErrorKind::InvalidData, "changed since it was hashed; not linked")),
+        _ => Ok(()),
+    }
+}
+
+// Replaces duplicate by a hard link to original. The link is made under a temporary name
+// and renamed over the duplicate, so the duplicate is never missing. The keys are those of
+// the two files when they were hashed (see DuplicateSet); if either file has changed since,
+// the duplicate is left alone, as it may no longer be one.
+pub fn replace_with_link(original: &Path, duplicate: &Path, original_key: Option<FileKey>, duplicate_key: Option<FileKey>) -> io::Result<()> {
+    let mut name = OsString::from(".");
+    name.push(duplicate.file_name().unwrap_or_default());
+    name.push(format!(".dupfind-{}", std::process::id()));
+    let temporary = duplicate.with_file_name(name);
+
+    fs::hard_link(original, &temporary)?;
+    // the temporary name is the original by now, so a change to it can't slip in between
+    check_unchanged(&temporary, original_key)
+        .and_then(|()| check_unchanged(duplicate, duplicate_key))
+        .and_then(|()| fs::rename(&temporary, duplicate))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&temporary);
+        })
+}
+
+// The dupfind command line.
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    pub walk_options: WalkOptions,
+    // 0 for one worker per CPU
+    pub jobs: usize,
+    pub link: bool,
+    // with link, only tell what would be linked
+    pub dry_run: bool,
+    pub files: Vec<OsString>,
+}
+
+pub fn usage(program: &str) -> String {
+    let mut text = format!("Usage: {} [OPTION]... [FILE]...\n", program);
+    text.push_str("Find files with the same content below the directories given, or the current one,\n");
+    text.push_str("and print them in sets with the bytes they waste.\n\n");
+    text.push_str("  -l, --link            replace duplicates by hard links to the first file of their\n");
+    text.push_str("                        set; only tells what it would do, unless --no-dry-run is given\n");
+    text.push_str("      --no-dry-run      with --link, really replace the files\n");
+    text.push_str("  -j, --jobs=N          hash N files at a time; the default is one per CPU\n");
+    text.push_str("  -L, --follow-symlinks follow symbolic links found in the directories\n");
+    text.push_str("      --include=PATTERN only look at files matching PATTERN; may be repeated\n");
+    text.push_str("      --exclude=PATTERN skip files and directories matching PATTERN; may be repeated\n");
+    text
+}
+
+const OPTIONS: OptionSpec = OptionSpec{
+    long: &["link", "no-dry-run", "jobs", "follow-symlinks", "include", "exclude"],
+    short: &[('l', "link"), ('j', "jobs"), ('L', "follow-symlinks")],
+    with_value: &["jobs", "include", "exclude"],
+};
+
+// args[0] is the name of the program.
+pub fn parse_args(args: &[OsString]) -> Result<Options, String> {
+    let mut options = Options{dry_run: true, ..Default::default()};
+
+    for arg in split_args(args, &OPTIONS)? {
+        match arg {
+            Arg::Operand(file) => options.files.push(file),
+            Arg::Option(name, value) => {
+                let value = value.unwrap_or_default();
+                match name.as_str() {
+                    "link" => options.link = true,
+                    "no-dry-run" => options.dry_run = false,
+                    "jobs" => options.jobs = jobs(&value)?,
+                    "follow-symlinks" => options.walk_options.follow_symlinks = true,
+                    "include" => options.walk_options.include.push(pattern(&value)?),
+                    "exclude" => options.walk_options.exclude.push(pattern(&value)?),
+                    _ => unreachable!("option --{} is in OPTIONS but not handled", name),
+                }
+            }
+        }
+    }
+
+    if !options.link && !options.dry_run {
+        return Err("the --no-dry-run option is meaningful only with --link".to_string());
+    }
+    Ok(options)
+}
+
+fn link_set(program: &str, set: &DuplicateSet, dry_run: bool) -> bool {
+    let mut ok = true;
+    for (duplicate, &key) in set.files[1..].iter().zip(&set.keys[1..]) {
+        if dry_run {
+            println!("would link {} => {}", duplicate.display(), set.files[0].display());
+            continue;
+        }
+        match replace_with_link(&set.files[0], duplicate, set.keys[0], key) {
+            Ok(()) => println!("linked {} => {}", duplicate.display(), set.files[0].display()),
+            Err(e) => {
+                eprintln!("{}: {}: {}", program, duplicate.display(), describe_error(&e));
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let program = args.first().map(|p| p.to_string_lossy().into_owned()).unwrap_or_else(|| "dupfind".to_string());
+
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{}: {}", program, msg);
+            eprint!("{}", usage(&program));
+            return 1;
+        }
+    };
+
+    let roots: Vec<PathBuf> = if options.files.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        options.files.iter().map(PathBuf::from).collect()
+    };
+    let walk = walk(&roots, &options.walk_options);
+    let mut ok = report_walk_warnings(&program, walk.warnings);
+
+    let duplicates = find_duplicates(&walk.files, &Pool::new(options.jobs));
+    for (path, e) in &duplicates.errors {
+        eprintln!("{}: {}: {}", program, path.display(), describe_error(e));
+        ok = false;
+    }
+
+    let extra_files: usize = duplicates.sets.iter().map(|s| s.files.len() - 1).sum();
+    for set in &duplicates.sets {
+        println!("# {} files of {} bytes, {} bytes wasted, SHA256 {}", set.files.len(), set.size, set.wasted(), hex::encode(&set.digest));
+        for file in &set.files {
+            println!("{}", file.display());
+        }
+        if options.link {
+            ok &= link_set(&program, set, options.dry_run);
+        }
+        println!();
+    }
+    println!("{} duplicate sets, {} duplicate files, {} bytes wasted", duplicates.sets.len(), extra_files, duplicates.wasted());
+
+    if ok { 0 } else { 1 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    fn args(line: &str) -> Vec<OsString> {
+        line.split_whitespace().map(OsString::from).collect()
+    }
+
+    fn names(dir: &TempDir, files: &[PathBuf]) -> Vec<String> {
+        files.iter().map(|f| f.strip_prefix(dir.path()).unwrap().to_string_lossy().into_owned()).collect()
+    }
+
+    // Large files that only differ in the middle, so only the full hash tells them apart.
+    fn large(middle: u8) -> Vec<u8> {
+        let mut content = vec![7u8; 3 * PARTIAL_SIZE as usize];
+        content[PARTIAL_SIZE as usize + 10] = middle;
+        content
+    }
+
+    #[test]
+    fn test_partial_hash() {
+        let dir = TempDir::new("dupfind-partial");
+        let small = dir.write("small", b"abc");
+        assert_eq!(hex::encode(partial_hash(&small, 3).unwrap()), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+
+        let a = dir.write("a", &large(1));
+        let b = dir.write("b", &large(2));
+        let size = 3 * PARTIAL_SIZE;
+        assert_eq!(partial_hash(&a, size).unwrap(), partial_hash(&b, size).unwrap());
+        assert_ne!(sha_sum(SHA256, &a).unwrap(), sha_sum(SHA256, &b).unwrap());
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let dir = TempDir::new("dupfind-find");
+        let files = vec![
+            dir.write("a/one", b"same"),
+            dir.write("a/two", b"different"),
+            dir.write("b/one", b"same"),
+            dir.write("b/empty", b""),
+            dir.write("c/empty", b""),
+            dir.write("large1", &large(1)),
+            dir.write("large2", &large(2)),
+            dir.write("large3", &large(1)),
+            dir.write("c/one", b"same"),
+            dir.path().join("missing"),
+        ];
+
+        let duplicates = find_duplicates(&files, &Pool::new(2));
+        assert_eq!(duplicates.sets.len(), 2);
+        assert_eq!(names(&dir, &duplicates.sets[0].files), vec!["large1", "large3"]);
+        assert_eq!(duplicates.sets[0].digest, sha_sum(SHA256, &files[5]).unwrap());
+        assert_eq!(names(&dir, &duplicates.sets[1].files), vec!["a/one", "b/one", "c/one"]);
+        assert_eq!(duplicates.sets[1].wasted(), 8);
+        assert_eq!(duplicates.wasted(), 3 * PARTIAL_SIZE + 8);
+        assert!(matches!(&duplicates.errors[..], [(p, _)] if p.ends_with("missing")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replace_with_link() {
+        use std::os::unix::fs::MetadataExt;
+        let dir = TempDir::new("dupfind-link");
+        let files = vec![dir.write("a", b"same"), dir.write("sub/b", b"same"), dir.write("c", b"same")];
+
+        let duplicates = find_duplicates(&files, &Pool::new(1));
+        let set = &duplicates.sets[0];
+        assert_eq!(set.wasted(), 8);
+        for (duplicate, &key) in files[1..].iter().zip(&set.keys[1..]) {
+            replace_with_link(&files[0], duplicate, set.keys[0], key).unwrap();
+        }
+
+        let inode = fs::metadata(&files[0]).unwrap().ino();
+        assert!(files.iter().all(|f| fs::metadata(f).unwrap().ino() == inode));
+        assert_eq!(fs::read(&files[1]).unwrap(), b"same");
+        assert_eq!(fs::read_dir(dir.path().join("sub")).unwrap().count(), 1);
+        // linked files are no longer duplicates
+        assert!(find_duplicates(&files, &Pool::new(1)).sets.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replace_with_link_changed() {
+        use std::os::unix::fs::MetadataExt;
+        let dir = TempDir::new("dupfind-changed");
+        let files = vec![dir.write("a", b"same"), dir.write("b", b"same"), dir.write("c", b"same")];
+        let duplicates = find_duplicates(&files, &Pool::new(1));
+        let set = &duplicates.sets[0];
+
+        // rewritten after hashing, to the same size; only the time tells
+        let mtime = fs::metadata(&files[1]).unwrap().modified().unwrap();
+        fs::write(&files[1], b"diff").unwrap();
+        File::options().write(true).open(&files[1]).unwrap().set_modified(mtime + std::time::Duration::from_secs(1)).unwrap();
+        let e = replace_with_link(&files[0], &files[1], set.keys[0], set.keys[1]).unwrap_err();
+        assert_eq!(describe_error(&e), "changed since it was hashed; not linked");
+        assert_eq!(fs::read(&files[1]).unwrap(), b"diff");
+
+        // nor anything linked to an original that has changed
+        fs::write(&files[0], b"longer").unwrap();
+        assert!(replace_with_link(&files[0], &files[2], set.keys[0], set.keys[2]).is_err());
+        assert_eq!(fs::read(&files[2]).unwrap(), b"same");
+        assert_ne!(fs::metadata(&files[2]).unwrap().ino(), fs::metadata(&files[0]).unwrap().ino());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse_args(&args("dupfind -l -j 2 --exclude *.o a b")).unwrap();
+        assert!(options.link && options.dry_run);
+        assert_eq!(options.jobs, 2);
+        assert_eq!(options.files, vec!["a", "b"]);
+        assert!(!parse_args(&args("dupfind --link --no-dry-run")).unwrap().dry_run);
+    }
+
+    #[rstest(line, expected,
+        case::no_link("dupfind --no-dry-run", "the --no-dry-run option is meaningful only with --link"),
+        case::unknown("dupfind --delete", "unrecognized option '--delete'"),
+    )]
+    fn test_parse_args_errors(line: &str, expected: &str) {
+        assert_eq!(parse_args(&args(line)), Err(expected.to_string()));
+    }
+}
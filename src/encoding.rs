@@ -0,0 +1,230 @@
+// Text encodings of binary data, beyond the hex that the hex crate gives us.
+
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    InvalidCharacter,
+    InvalidLength,
+}
+
+const BASE64_ALPHABET: &[u8;64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64URL_ALPHABET: &[u8;64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const BASE32_ALPHABET: &[u8;32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+// Base64 and base32 work the same way: groups of `group` bytes become `chars` characters
+// of `bits` bits each, and a partial group gives as many characters as needed to hold it.
+struct Alphabet {
+    chars: &'static [u8],
+    bits: usize,
+    group: usize,
+}
+
+const BASE64: Alphabet = Alphabet{chars: BASE64_ALPHABET, bits: 6, group: 3};
+const BASE64URL: Alphabet = Alphabet{chars: BASE64URL_ALPHABET, bits: 6, group: 3};
+const BASE32: Alphabet = Alphabet{chars: BASE32_ALPHABET, bits: 5, group: 5};
+
+impl Alphabet {
+    fn group_chars(&self) -> usize {
+        self.group * 8 / self.bits
+    }
+
+    fn encode(&self, data: &[u8], pad: bool) -> String {
+        let mut result = String::with_capacity(data.len().div_ceil(self.group) * self.group_chars());
+
+        for chunk in data.chunks(self.group) {
+            let mut group = [0u8;8];
+            group[8 - self.group..8 - self.group + chunk.len()].copy_from_slice(chunk);
+            let bits = u64::from_be_bytes(group);
+
+            let chars = (chunk.len() * 8).div_ceil(self.bits);
+            for i in 0..chars {
+                let shift = self.group * 8 - self.bits * (i + 1);
+                let index = (bits >> shift) & ((1 << self.bits) - 1);
+                result.push(self.chars[index as usize] as char);
+            }
+            if pad {
+                for _ in chars..self.group_chars() {
+                    result.push('=');
+                }
+            }
+        }
+
+        result
+    }
+
+    // Accepts input with or without padding.
+    fn decode(&self, s: &str) -> Result<Vec<u8>, DecodeError> {
+        let s = s.trim_end_matches('=');
+        let mut result = Vec::with_capacity(s.len() * self.bits / 8);
+
+        for chunk in s.as_bytes().chunks(self.group_chars()) {
+            let len = chunk.len() * self.bits / 8;
+            // a partial group must hold whole bytes, with less than a character to spare
+            if chunk.len() != (len * 8).div_ceil(self.bits) {
+                return Err(DecodeError::InvalidLength);
+            }
+
+            let mut bits = 0u64;
+            for (i, c) in chunk.iter().enumerate() {
+                let value = self.chars.iter().position(|a| a == c).ok_or(DecodeError::InvalidCharacter)?;
+                bits |= (value as u64) << (self.group * 8 - self.bits * (i + 1));
+            }
+            let bytes = bits.to_be_bytes();
+            result.extend_from_slice(&bytes[8 - self.group..8 - self.group + len]);
+        }
+
+        Ok(result)
+    }
+}
+
+pub fn base64_encode(data: &[u8], pad: bool) -> String {
+    BASE64.encode(data, pad)
+}
+
+// Accepts input with or without padding.
+pub fn base64_decode(s: &str) -> Result<Vec<u8>, DecodeError> {
+    BASE64.decode(s)
+}
+
+// The URL and filename safe variant, with - and _ instead of + and /.
+pub fn base64url_encode(data: &[u8], pad: bool) -> String {
+    BASE64URL.encode(data, pad)
+}
+
+pub fn base64url_decode(s: &str) -> Result<Vec<u8>, DecodeError> {
+    BASE64URL.decode(s)
+}
+
+pub fn base32_encode(data: &[u8], pad: bool) -> String {
+    BASE32.encode(data, pad)
+}
+
+pub fn base32_decode(s: &str) -> Result<Vec<u8>, DecodeError> {
+    BASE32.decode(s)
+}
+
+// How the checksum binaries print digests.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Hex,
+    HexUpper,
+    Base64,
+    Base64Url,
+    Base32,
+}
+
+impl Encoding {
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        match name {
+            "hex" => Some(Encoding::Hex),
+            "HEX" => Some(Encoding::HexUpper),
+            "base64" => Some(Encoding::Base64),
+            "base64url" => Some(Encoding::Base64Url),
+            "base32" => Some(Encoding::Base32),
+            _ => None,
+        }
+    }
+
+    // base64url is left unpadded, as it mostly ends up in URLs and file names.
+    pub fn encode(&self, data: &[u8]) -> String {
+        match self {
+            Encoding::Hex => hex::encode(data),
+            Encoding::HexUpper => hex::encode_upper(data),
+            Encoding::Base64 => base64_encode(data, true),
+            Encoding::Base64Url => base64url_encode(data, false),
+            Encoding::Base32 => base32_encode(data, true),
+        }
+    }
+}
+
+// Reads a digest of the given size in any of the encodings; their lengths differ,
+// so there is no ambiguity.
+pub fn decode_digest(s: &str, digest_size: usize) -> Option<Vec<u8>> {
+    let candidates = [
+        hex::decode(s).ok(),
+        base64_decode(s).ok(),
+        base64url_decode(s).ok(),
+        base32_decode(s).ok(),
+    ];
+    candidates.iter().flatten().find(|d| d.len() == digest_size).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate hex;
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    // RFC 4648, section 10
+    #[rstest(input, padded, unpadded,
+        case::empty("", "", ""),
+        case::f("f", "Zg==", "Zg"),
+        case::fo("fo", "Zm8=", "Zm8"),
+        case::foo("foo", "Zm9v", "Zm9v"),
+        case::foob("foob", "Zm9vYg==", "Zm9vYg"),
+        case::fooba("fooba", "Zm9vYmE=", "Zm9vYmE"),
+        case::foobar("foobar", "Zm9vYmFy", "Zm9vYmFy"),
+    )]
+    fn test_base64(input: &str, padded: &str, unpadded: &str) {
+        assert_eq!(base64_encode(input.as_bytes(), true), padded);
+        assert_eq!(base64_encode(input.as_bytes(), false), unpadded);
+        assert_eq!(base64_decode(padded), Ok(input.as_bytes().to_vec()));
+        assert_eq!(base64_decode(unpadded), Ok(input.as_bytes().to_vec()));
+    }
+
+    #[test]
+    fn test_base64_invalid() {
+        assert_eq!(base64_decode("Zm9v!"), Err(DecodeError::InvalidLength));
+        assert_eq!(base64_decode("Zm9-"), Err(DecodeError::InvalidCharacter));
+    }
+
+    #[test]
+    fn test_base64url() {
+        let data = [0xfb, 0xff, 0xbf, 0x01];
+        assert_eq!(base64_encode(&data, true), "+/+/AQ==");
+        assert_eq!(base64url_encode(&data, false), "-_-_AQ");
+        assert_eq!(base64url_decode("-_-_AQ=="), Ok(data.to_vec()));
+        assert_eq!(base64url_decode("+/+/AQ"), Err(DecodeError::InvalidCharacter));
+    }
+
+    // RFC 4648, section 10
+    #[rstest(input, padded,
+        case::empty("", ""),
+        case::f("f", "MY======"),
+        case::fo("fo", "MZXQ===="),
+        case::foo("foo", "MZXW6==="),
+        case::foob("foob", "MZXW6YQ="),
+        case::fooba("fooba", "MZXW6YTB"),
+        case::foobar("foobar", "MZXW6YTBOI======"),
+    )]
+    fn test_base32(input: &str, padded: &str) {
+        assert_eq!(base32_encode(input.as_bytes(), true), padded);
+        assert_eq!(base32_encode(input.as_bytes(), false), padded.trim_end_matches('='));
+        assert_eq!(base32_decode(padded), Ok(input.as_bytes().to_vec()));
+        assert_eq!(base32_decode(padded.trim_end_matches('=')), Ok(input.as_bytes().to_vec()));
+    }
+
+    #[test]
+    fn test_base32_invalid() {
+        assert_eq!(base32_decode("MZX"), Err(DecodeError::InvalidLength));
+        assert_eq!(base32_decode("MZ1W6==="), Err(DecodeError::InvalidCharacter));
+    }
+
+    #[rstest(encoding, expected,
+        case::hex(Encoding::Hex, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+        case::hex_upper(Encoding::HexUpper, "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD"),
+        case::base64(Encoding::Base64, "ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0="),
+        case::base64url(Encoding::Base64Url, "ungWv48Bz-pBQUDeXa4iI7ADYaOWF3qctBD_YfIAFa0"),
+        case::base32(Encoding::Base32, "XJ4BNP4PAHH6UQKBIDPF3LRCEOYAGYNDSYLXVHFUCD7WD4QACWWQ===="),
+    )]
+    fn test_digest_encodings(encoding: Encoding, expected: &str) {
+        // SHA-256 of "abc"
+        let digest = hex::decode("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad").unwrap();
+        assert_eq!(encoding.encode(&digest), expected);
+        assert_eq!(decode_digest(expected, 32), Some(digest));
+        assert_eq!(decode_digest(expected, 64), None);
+    }
+}
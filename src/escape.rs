@@ -0,0 +1,98 @@
+// File names in checksum lines, written the way coreutils does: a name containing a
+// newline, carriage return or backslash is escaped (\n, \r and \\) and the whole line is
+// prefixed with a backslash, so that every line stays a line. Names are handled as raw
+// bytes, so names that aren't valid UTF-8 survive the round trip.
+
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+pub fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+pub fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(unix)]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+pub fn needs_escape(name: &[u8]) -> bool {
+    name.iter().any(|&b| b == b'\n' || b == b'\r' || b == b'\\')
+}
+
+pub fn escape_name(name: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(name.len());
+    for &b in name {
+        match b {
+            b'\n' => result.extend_from_slice(b"\\n"),
+            b'\r' => result.extend_from_slice(b"\\r"),
+            b'\\' => result.extend_from_slice(b"\\\\"),
+            _ => result.push(b),
+        }
+    }
+    result
+}
+
+// None for a backslash followed by anything else, or by nothing at all.
+pub fn unescape_name(name: &[u8]) -> Option<Vec<u8>> {
+    let mut result = Vec::with_capacity(name.len());
+    let mut bytes = name.iter();
+    while let Some(&b) = bytes.next() {
+        if b != b'\\' {
+            result.push(b);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'n') => result.push(b'\n'),
+            Some(b'r') => result.push(b'\r'),
+            Some(b'\\') => result.push(b'\\'),
+            _ => return None,
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    #[rstest(name, escaped,
+        case::plain(b"a b.txt", b"a b.txt"),
+        case::newline(b"a\nb", b"a\\nb"),
+        case::carriage_return(b"a\rb", b"a\\rb"),
+        case::backslash(b"a\\b", b"a\\\\b"),
+        case::mixed(b"\\\n\\", b"\\\\\\n\\\\"),
+    )]
+    fn test_escape(name: &[u8], escaped: &[u8]) {
+        assert_eq!(needs_escape(name), name != escaped);
+        assert_eq!(escape_name(name), escaped);
+        assert_eq!(unescape_name(escaped), Some(name.to_vec()));
+    }
+
+    #[test]
+    fn test_unescape_invalid() {
+        assert_eq!(unescape_name(b"a\\tb"), None);
+        assert_eq!(unescape_name(b"a\\"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path() {
+        let bytes = b"caf\xe9.txt";
+        assert_eq!(path_to_bytes(&path_from_bytes(bytes)), bytes.to_vec());
+    }
+}
@@ -0,0 +1,183 @@
+// Shell-style glob patterns for selecting paths:
+//   *      any run of characters, except /
+//   **     any run of characters, including /
+//   ?      any single character, except /
+//   [abc]  one of the listed characters; ranges like [a-z], negation with [!...] or [^...]
+//   \x     the character x itself
+// A pattern without a / is matched against the last component of a path only.
+
+#[derive(Debug, PartialEq)]
+pub enum PatternError {
+    UnclosedBracket,
+    TrailingBackslash,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Char(char),
+    AnyChar,
+    AnySequence,
+    AnyPath,
+    Class{negated: bool, ranges: Vec<(char, char)>},
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    text: String,
+    tokens: Vec<Token>,
+    basename_only: bool,
+}
+
+impl Pattern {
+    pub fn new(text: &str) -> Result<Pattern, PatternError> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            match chars[i] {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    tokens.push(Token::AnyPath);
+                    i += 2;
+                    // "**/" also matches no directories at all
+                    if chars.get(i) == Some(&'/') {
+                        i += 1;
+                    }
+                }
+                '*' => {
+                    tokens.push(Token::AnySequence);
+                    i += 1;
+                }
+                '?' => {
+                    tokens.push(Token::AnyChar);
+                    i += 1;
+                }
+                '\\' => {
+                    let c = *chars.get(i + 1).ok_or(PatternError::TrailingBackslash)?;
+                    tokens.push(Token::Char(c));
+                    i += 2;
+                }
+                '[' => {
+                    let (token, next) = parse_class(&chars, i + 1)?;
+                    tokens.push(token);
+                    i = next;
+                }
+                c => {
+                    tokens.push(Token::Char(c));
+                    i += 1;
+                }
+            }
+        }
+
+        Ok(Pattern{text: text.to_string(), tokens, basename_only: !text.contains('/')})
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    // path uses / as separator, and is relative to wherever the walk started.
+    pub fn matches(&self, path: &str) -> bool {
+        let path = if self.basename_only {
+            path.rsplit('/').next().unwrap_or(path)
+        } else {
+            path
+        };
+        let chars: Vec<char> = path.chars().collect();
+        matches_from(&self.tokens, &chars)
+    }
+}
+
+// Parses the inside of [...], starting just after the [; returns the token and the index after the ].
+fn parse_class(chars: &[char], start: usize) -> Result<(Token, usize), PatternError> {
+    let mut i = start;
+    let negated = matches!(chars.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let c = *chars.get(i).ok_or(PatternError::UnclosedBracket)?;
+        // a ] right after the opening bracket is a literal
+        if c == ']' && !first {
+            return Ok((Token::Class{negated, ranges}, i + 1));
+        }
+        first = false;
+
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|&e| e != ']') {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+}
+
+fn matches_from(tokens: &[Token], path: &[char]) -> bool {
+    match tokens.first() {
+        None => path.is_empty(),
+        Some(Token::AnyPath) => (0..=path.len()).any(|skip| matches_from(&tokens[1..], &path[skip..])),
+        Some(Token::AnySequence) => {
+            let limit = path.iter().position(|&c| c == '/').unwrap_or(path.len());
+            (0..=limit).any(|skip| matches_from(&tokens[1..], &path[skip..]))
+        }
+        Some(token) => match path.first() {
+            None => false,
+            Some(&c) => matches_char(token, c) && matches_from(&tokens[1..], &path[1..]),
+        },
+    }
+}
+
+fn matches_char(token: &Token, c: char) -> bool {
+    match token {
+        Token::Char(expected) => *expected == c,
+        Token::AnyChar => c != '/',
+        Token::Class{negated, ranges} => c != '/' && ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated,
+        Token::AnySequence | Token::AnyPath => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    #[rstest(pattern, path, expected,
+        case::literal("a.txt", "a.txt", true),
+        case::literal_other("a.txt", "b.txt", false),
+        case::star("*.o", "main.o", true),
+        case::star_basename("*.o", "src/lib/main.o", true),
+        case::star_no_match("*.o", "main.c", false),
+        case::question("?.rs", "a.rs", true),
+        case::question_too_long("?.rs", "ab.rs", false),
+        case::class("[abc].rs", "b.rs", true),
+        case::class_range("file[0-9]", "file7", true),
+        case::class_negated("file[!0-9]", "file7", false),
+        case::class_negated_caret("file[^0-9]", "filex", true),
+        case::class_bracket("[]x]", "]", true),
+        case::escape("\\*.rs", "*.rs", true),
+        case::escape_literal("\\*.rs", "a.rs", false),
+        case::path("target/*.o", "target/a.o", true),
+        case::path_star_stops_at_slash("target/*.o", "target/debug/a.o", false),
+        case::path_anchored("target/*.o", "x/target/a.o", false),
+        case::double_star("target/**", "target/debug/deps/a.o", true),
+        case::double_star_slash("**/*.o", "a.o", true),
+        case::double_star_deep("**/*.o", "x/y/a.o", true),
+        case::double_star_middle("src/**/mod.rs", "src/mod.rs", true),
+        case::double_star_middle_deep("src/**/mod.rs", "src/a/b/mod.rs", true),
+    )]
+    fn test_matches(pattern: &str, path: &str, expected: bool) {
+        assert_eq!(Pattern::new(pattern).unwrap().matches(path), expected);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(Pattern::new("[abc"), Err(PatternError::UnclosedBracket));
+        assert_eq!(Pattern::new("abc\\"), Err(PatternError::TrailingBackslash));
+    }
+}
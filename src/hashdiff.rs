@@ -0,0 +1,379 @@
+// Comparing two directory trees by the content of their files rather than by timestamps:
+// which files of the second tree were added, removed, modified, or renamed (the same content
+// under another path) compared to the first.
+//
+// Files at the same path are compared by size first, and only hashed if the sizes agree.
+// A removed file can only have been renamed to an added file of the same size, so of the
+// files at only one side, just those with a size found at the other side are hashed.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::check::describe_error;
+use crate::cli::{jobs, pattern, report_walk_warnings, split_args, Arg, OptionSpec};
+use crate::pool::Pool;
+use crate::records::json_string;
+use crate::sha::SHA256;
+use crate::sha_sum;
+use crate::walk::{walk, WalkOptions, WalkWarning};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Added(PathBuf),
+    Removed(PathBuf),
+    // same path, different content
+    Modified(PathBuf),
+    // from, to
+    Renamed(PathBuf, PathBuf),
+}
+
+impl Change {
+    // Where the change is, in the second tree if it is there.
+    pub fn path(&self) -> &Path {
+        match self {
+            Change::Added(path) | Change::Removed(path) | Change::Modified(path) | Change::Renamed(_, path) => path,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Diff {
+    // paths relative to the roots, sorted by Change::path
+    pub changes: Vec<Change>,
+    // the files at the same path with the same content
+    pub identical: usize,
+    // how many files had to be read
+    pub hashed: usize,
+    pub errors: Vec<(PathBuf, io::Error)>,
+    pub warnings: Vec<WalkWarning>,
+}
+
+// The files below root, by path relative to it, with their sizes.
+fn listing(root: &Path, options: &WalkOptions, diff: &mut Diff) -> BTreeMap<PathBuf, u64> {
+    let walk = walk(&[root.to_path_buf()], options);
+    diff.warnings.extend(walk.warnings);
+    let mut files = BTreeMap::new();
+    for file in walk.files {
+        match fs::metadata(&file) {
+            Ok(meta) => {
+                files.insert(file.strip_prefix(root).unwrap_or(&file).to_path_buf(), meta.len());
+            }
+            Err(e) => diff.errors.push((file, e)),
+        }
+    }
+    files
+}
+
+pub fn diff_trees(a: &Path, b: &Path, options: &WalkOptions, pool: &Pool) -> Diff {
+    let mut diff = Diff::default();
+    let files_a = listing(a, options, &mut diff);
+    let files_b = listing(b, options, &mut diff);
+
+    let sizes_a: HashSet<u64> = files_a.iter().filter(|(p, _)| !files_b.contains_key(*p)).map(|(_, &s)| s).collect();
+    let sizes_b: HashSet<u64> = files_b.iter().filter(|(p, _)| !files_a.contains_key(*p)).map(|(_, &s)| s).collect();
+
+    // what has to be hashed: files at both sides with the same size, and rename candidates
+    let mut to_hash = Vec::new();
+    for (path, size) in &files_a {
+        match files_b.get(path) {
+            Some(size_b) if size_b == size => to_hash.extend([a.join(path), b.join(path)]),
+            Some(_) => diff.changes.push(Change::Modified(path.clone())),
+            None if sizes_b.contains(size) => to_hash.push(a.join(path)),
+            None => (),
+        }
+    }
+    to_hash.extend(files_b.iter()
+        .filter(|(path, size)| !files_a.contains_key(*path) && sizes_a.contains(size))
+        .map(|(path, _)| b.join(path)));
+
+    diff.hashed = to_hash.len();
+    let mut digests = HashMap::new();
+    pool.map_ordered(&to_hash, |file| sha_sum(SHA256, file), |_, file, result| match result {
+        Ok(digest) => {
+            digests.insert(file.clone(), digest);
+        }
+        Err(e) => diff.errors.push((file.clone(), e)),
+    });
+
+    // candidates for having been renamed, by size and digest, in path order
+    let mut removed: HashMap<(u64, &Vec<u8>), VecDeque<&PathBuf>> = HashMap::new();
+    let mut unpaired: Vec<&PathBuf> = Vec::new();
+    for (path, size) in &files_a {
+        if files_b.contains_key(path) {
+            let (digest_a, digest_b) = (digests.get(&a.join(path)), digests.get(&b.join(path)));
+            match (digest_a, digest_b) {
+                (Some(digest_a), Some(digest_b)) if digest_a == digest_b => diff.identical += 1,
+                (Some(_), Some(_)) => diff.changes.push(Change::Modified(path.clone())),
+                // not hashed because the sizes differ, or an error
+                _ => (),
+            }
+            continue;
+        }
+        match digests.get(&a.join(path)) {
+            Some(digest) => removed.entry((*size, digest)).or_default().push_back(path),
+            None => unpaired.push(path),
+        }
+    }
+
+    for (path, size) in files_b.iter().filter(|(path, _)| !files_a.contains_key(*path)) {
+        let from = digests.get(&b.join(path)).and_then(|digest| removed.get_mut(&(*size, digest))?.pop_front());
+        diff.changes.push(match from {
+            Some(from) => Change::Renamed(from.clone(), path.clone()),
+            None => Change::Added(path.clone()),
+        });
+    }
+    unpaired.extend(removed.into_values().flatten());
+    diff.changes.extend(unpaired.into_iter().map(|path| Change::Removed(path.clone())));
+
+    diff.changes.sort_by(|x, y| x.path().cmp(y.path()));
+    diff
+}
+
+#[derive(Default, Debug, PartialEq)]
+pub struct Options {
+    pub json: bool,
+    pub jobs: usize,
+    pub walk_options: WalkOptions,
+    pub dirs: Vec<OsString>,
+}
+
+pub fn usage(program: &str) -> String {
+    let mut text = format!("Usage: {} [OPTION]... DIR_A DIR_B\n", program);
+    text.push_str("Compare two directory trees by the content of their files, and print the files of\n");
+    text.push_str("DIR_B that were added, removed, modified or renamed compared to DIR_A.\n\n");
+    text.push_str("      --json            print the differences as a JSON object\n");
+    text.push_str("  -j, --jobs=N          hash N files at a time; the default is one per CPU\n");
+    text.push_str("  -L, --follow-symlinks follow symbolic links found in the directories\n");
+    text.push_str("      --include=PATTERN only compare files matching PATTERN; may be repeated\n");
+    text.push_str("  -x, --exclude=PATTERN ignore files and directories matching PATTERN; may be repeated\n");
+    text.push_str("\nThe exit status is 0 if the trees have the same content, 1 if they differ, and 2\n");
+    text.push_str("if there was trouble.\n");
+    text
+}
+
+const OPTIONS: OptionSpec = OptionSpec{
+    long: &["json", "jobs", "follow-symlinks", "include", "exclude"],
+    short: &[('j', "jobs"), ('L', "follow-symlinks"), ('x', "exclude")],
+    with_value: &["jobs", "include", "exclude"],
+};
+
+// args[0] is the name of the program.
+pub fn parse_args(args: &[OsString]) -> Result<Options, String> {
+    let mut options = Options::default();
+
+    for arg in split_args(args, &OPTIONS)? {
+        match arg {
+            Arg::Operand(dir) => options.dirs.push(dir),
+            Arg::Option(name, value) => {
+                let value = value.unwrap_or_default();
+                match name.as_str() {
+                    "json" => options.json = true,
+                    "jobs" => options.jobs = jobs(&value)?,
+                    "follow-symlinks" => options.walk_options.follow_symlinks = true,
+                    "include" => options.walk_options.include.push(pattern(&value)?),
+                    "exclude" => options.walk_options.exclude.push(pattern(&value)?),
+                    _ => unreachable!("option --{} is in OPTIONS but not handled", name),
+                }
+            }
+        }
+    }
+
+    match options.dirs.len() {
+        0 | 1 => Err("two directories are needed".to_string()),
+        2 => Ok(options),
+        _ => Err(format!("extra operand '{}'", options.dirs[2].to_string_lossy())),
+    }
+}
+
+fn json_path(path: &Path) -> String {
+    json_string(&path.to_string_lossy())
+}
+
+pub fn format_json(diff: &Diff) -> String {
+    let paths = |pick: fn(&Change) -> Option<&PathBuf>| -> String {
+        let paths: Vec<String> = diff.changes.iter().filter_map(pick).map(|p| json_path(p)).collect();
+        format!("[{}]", paths.join(","))
+    };
+    let renamed: Vec<String> = diff.changes.iter().filter_map(|change| match change {
+        Change::Renamed(from, to) => Some(format!("{{\"from\":{},\"to\":{}}}", json_path(from), json_path(to))),
+        _ => None,
+    }).collect();
+
+    format!("{{\"added\":{},\"removed\":{},\"modified\":{},\"renamed\":[{}],\"identical\":{}}}\n",
+        paths(|c| if let Change::Added(p) = c { Some(p) } else { None }),
+        paths(|c| if let Change::Removed(p) = c { Some(p) } else { None }),
+        paths(|c| if let Change::Modified(p) = c { Some(p) } else { None }),
+        renamed.join(","),
+        diff.identical)
+}
+
+pub fn format_text(diff: &Diff) -> String {
+    let mut text = String::new();
+    let mut counts = [0; 4];
+    for change in &diff.changes {
+        let (line, i) = match change {
+            Change::Added(path) => (format!("added: {}", path.display()), 0),
+            Change::Removed(path) => (format!("removed: {}", path.display()), 1),
+            Change::Modified(path) => (format!("modified: {}", path.display()), 2),
+            Change::Renamed(from, to) => (format!("renamed: {} => {}", from.display(), to.display()), 3),
+        };
+        text.push_str(&line);
+        text.push('\n');
+        counts[i] += 1;
+    }
+    text.push_str(&format!("{} added, {} removed, {} modified, {} renamed, {} identical\n",
+        counts[0], counts[1], counts[2], counts[3], diff.identical));
+    text
+}
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let program = args.first().map(|p| p.to_string_lossy().into_owned()).unwrap_or_else(|| "hashdiff".to_string());
+
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{}: {}", program, msg);
+            eprint!("{}", usage(&program));
+            return 2;
+        }
+    };
+
+    let (a, b) = (Path::new(&options.dirs[0]), Path::new(&options.dirs[1]));
+    for dir in [a, b] {
+        if let Err(e) = fs::read_dir(dir) {
+            eprintln!("{}: {}: {}", program, dir.display(), describe_error(&e));
+            return 2;
+        }
+    }
+
+    let mut diff = diff_trees(a, b, &options.walk_options, &Pool::new(options.jobs));
+    let mut ok = report_walk_warnings(&program, std::mem::take(&mut diff.warnings));
+    for (path, e) in &diff.errors {
+        eprintln!("{}: {}: {}", program, path.display(), describe_error(e));
+        ok = false;
+    }
+
+    if options.json {
+        print!("{}", format_json(&diff));
+    } else {
+        print!("{}", format_text(&diff));
+    }
+
+    match (ok, diff.changes.is_empty()) {
+        (false, _) => 2,
+        (true, true) => 0,
+        (true, false) => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    fn args(line: &str) -> Vec<OsString> {
+        line.split_whitespace().map(OsString::from).collect()
+    }
+
+    // a and b below one temp dir, with the files given.
+    fn trees(name: &str, a: &[(&str, &str)], b: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new(name);
+        for (side, files) in [("a", a), ("b", b)] {
+            std::fs::create_dir_all(dir.path().join(side)).unwrap();
+            for (path, content) in files {
+                dir.write(&format!("{}/{}", side, path), content.as_bytes());
+            }
+        }
+        dir
+    }
+
+    fn diff(dir: &TempDir, options: &WalkOptions) -> Diff {
+        diff_trees(&dir.path().join("a"), &dir.path().join("b"), options, &Pool::new(2))
+    }
+
+    fn path(p: &str) -> PathBuf {
+        PathBuf::from(p)
+    }
+
+    #[test]
+    fn test_diff_trees() {
+        let dir = trees("hashdiff", &[
+            ("same.txt", "same"),
+            ("grown.txt", "abc"),
+            ("edited.txt", "abc"),
+            ("old/name.txt", "renamed"),
+            ("gone.txt", "gone"),
+            ("copied.txt", "copy"),
+        ], &[
+            ("same.txt", "same"),
+            ("grown.txt", "abcd"),
+            ("edited.txt", "abd"),
+            ("new/name.txt", "renamed"),
+            ("copied.txt", "copy"),
+            ("copy.txt", "copy"),
+            ("fresh.txt", "fresh"),
+        ]);
+
+        let diff = diff(&dir, &WalkOptions::default());
+        assert_eq!(diff.changes, vec![
+            Change::Added(path("copy.txt")),
+            Change::Modified(path("edited.txt")),
+            Change::Added(path("fresh.txt")),
+            Change::Removed(path("gone.txt")),
+            Change::Modified(path("grown.txt")),
+            Change::Renamed(path("old/name.txt"), path("new/name.txt")),
+        ]);
+        assert_eq!(diff.identical, 2);
+        assert!(diff.errors.is_empty());
+        // not grown.txt, whose sizes differ, nor fresh.txt, with no file of its size removed
+        assert_eq!(diff.hashed, 10);
+
+        assert_eq!(format_text(&diff), "added: copy.txt\nmodified: edited.txt\nadded: fresh.txt\nremoved: gone.txt\n\
+            modified: grown.txt\nrenamed: old/name.txt => new/name.txt\n2 added, 1 removed, 2 modified, 1 renamed, 2 identical\n");
+        assert_eq!(format_json(&diff), "{\"added\":[\"copy.txt\",\"fresh.txt\"],\"removed\":[\"gone.txt\"],\
+            \"modified\":[\"edited.txt\",\"grown.txt\"],\"renamed\":[{\"from\":\"old/name.txt\",\"to\":\"new/name.txt\"}],\"identical\":2}\n");
+    }
+
+    #[test]
+    fn test_renames_pair_in_order() {
+        let dir = trees("hashdiff-pairs", &[("x1", "dup"), ("x2", "dup")], &[("y1", "dup"), ("y2", "dup"), ("y3", "dup")]);
+        assert_eq!(diff(&dir, &WalkOptions::default()).changes, vec![
+            Change::Renamed(path("x1"), path("y1")),
+            Change::Renamed(path("x2"), path("y2")),
+            Change::Added(path("y3")),
+        ]);
+    }
+
+    #[test]
+    fn test_ignore_patterns() {
+        let dir = trees("hashdiff-ignore", &[("a.txt", "a"), ("build/out.o", "1")], &[("a.txt", "a"), ("build/out.o", "2"), ("x.log", "")]);
+        let options = WalkOptions{exclude: vec![pattern("build").unwrap(), pattern("*.log").unwrap()], ..Default::default()};
+        let diff = diff(&dir, &options);
+        assert!(diff.changes.is_empty());
+        assert_eq!(diff.identical, 1);
+    }
+
+    #[rstest(line, msg,
+        case::one("hashdiff a", "two directories are needed"),
+        case::three("hashdiff a b c", "extra operand 'c'"),
+        case::bad_option("hashdiff --csv a b", "unrecognized option '--csv'"),
+    )]
+    fn test_parse_errors(line: &str, msg: &str) {
+        assert_eq!(parse_args(&args(line)), Err(msg.to_string()));
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse_args(&args("hashdiff --json -x target -j2 a b")).unwrap();
+        assert!(options.json);
+        assert_eq!(options.jobs, 2);
+        assert_eq!(options.walk_options.exclude.len(), 1);
+        assert_eq!(options.dirs, vec!["a", "b"]);
+    }
+}
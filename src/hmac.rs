@@ -0,0 +1,102 @@
+// HMAC (RFC 2104) over any of the SHA variants.
+//
+// The inner and outer hashers are keyed once, so a keyed Hmac can be cloned and reused for
+// many messages under the same key - which is what the KDFs built on top of it do.
+
+use crate::sha::{Hasher, ShaParams};
+
+const IPAD: u8 = 0x36;
+const OPAD: u8 = 0x5c;
+
+#[derive(Clone)]
+pub struct Hmac<T> {
+    inner: Hasher<T>,
+    outer: Hasher<T>,
+}
+
+impl<T: Copy> Hmac<T> {
+    pub fn new(params: ShaParams<T>, key: &[u8]) -> Hmac<T> {
+        let mut block_key = if key.len() > params.block_size {
+            let mut hasher = Hasher::new(params);
+            hasher.update(key);
+            hasher.finish()
+        } else {
+            key.to_vec()
+        };
+        block_key.resize(params.block_size, 0);
+
+        let mut inner = Hasher::new(params);
+        inner.update(&block_key.iter().map(|b| b ^ IPAD).collect::<Vec<_>>());
+        let mut outer = Hasher::new(params);
+        outer.update(&block_key.iter().map(|b| b ^ OPAD).collect::<Vec<_>>());
+
+        Hmac{inner, outer}
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        let mut outer = self.outer;
+        outer.update(&self.inner.finish());
+        outer.finish()
+    }
+}
+
+pub fn hmac<T: Copy>(params: ShaParams<T>, key: &[u8], msg: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::new(params, key);
+    mac.update(msg);
+    mac.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha::{SHA256, SHA512};
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    extern crate hex;
+
+    // test cases from RFC 4231 (case 5, truncated output, is left out)
+    #[rstest(key, data, expected256, expected512,
+        case::case1("0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b", "4869205468657265",
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            "87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cdedaa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854"),
+        case::case2("4a656665", "7768617420646f2079612077616e7420666f72206e6f7468696e673f",
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"),
+        case::case3("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", "dddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddd",
+            "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            "fa73b0089d56a284efb0f0756c890be9b1b5dbdd8ee81a3655f83e33b2279d39bf3e848279a722c806b485a47e67c807b946a337bee8942674278859e13292fb"),
+        case::case4("0102030405060708090a0b0c0d0e0f10111213141516171819", "cdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcd",
+            "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            "b0ba465637458c6990e5a8c5f61d4af7e576d97ff94b872de76f8050361ee3dba91ca5c11aa25eb4d679275cc5788063a5f19741120c4f2de2adebeb10a298dd"),
+        case::case6(
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            "54657374205573696e67204c6172676572205468616e20426c6f636b2d53697a65204b6579202d2048617368204b6579204669727374",
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f3526b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598"),
+        case::case7(
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            "5468697320697320612074657374207573696e672061206c6172676572207468616e20626c6f636b2d73697a65206b657920616e642061206c6172676572207468616e20626c6f636b2d73697a6520646174612e20546865206b6579206e6565647320746f20626520686173686564206265666f7265206265696e6720757365642062792074686520484d414320616c676f726974686d2e",
+            "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            "e37b6a775dc87dbaa4dfa9f96e5e3ffddebd71f8867289865df5a32d20cdc944b6022cac3c4982b10d5eeb55c3e4de15134676fb6de0446065c97440fa8c6a58"),
+    )]
+    fn test_rfc4231(key: &str, data: &str, expected256: &str, expected512: &str) {
+        let key = hex::decode(key).unwrap();
+        let data = hex::decode(data).unwrap();
+        assert_eq!(hex::encode(hmac(SHA256, &key, &data)), expected256);
+        assert_eq!(hex::encode(hmac(SHA512, &key, &data)), expected512);
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let mut mac = Hmac::new(SHA256, b"Jefe");
+        mac.update(b"what do ya want ");
+        mac.update(b"for nothing?");
+        assert_eq!(hex::encode(mac.finish()), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+}
@@ -0,0 +1,454 @@
+// Key derivation functions:
+// * HKDF (RFC 5869)
+// * PBKDF2 (RFC 8018)
+// * KBKDF in counter and feedback mode, with HMAC as the PRF (NIST SP 800-108)
+// * the one-step KDF of NIST SP 800-56C (hash variant), and its close relative from ANSI X9.63
+
+use crate::hmac::{hmac, Hmac};
+use crate::sha::{Hasher, ShaParams};
+
+#[derive(Debug, PartialEq)]
+pub enum KdfError {
+    InvalidCounterWidth,
+    InvalidCounterLocation,
+    InvalidLengthWidth,
+    InvalidIterationCount,
+    OutputTooLong,
+    LabelTooLong,
+    ContextTooLong,
+}
+
+// An empty salt is the same as a salt of digest_size zero bytes.
+pub fn hkdf_extract<T: Copy>(params: ShaParams<T>, salt: &[u8], ikm: &[u8]) -> Vec<u8> {
+    if salt.is_empty() {
+        hmac(params, &vec![0u8; params.digest_size], ikm)
+    } else {
+        hmac(params, salt, ikm)
+    }
+}
+
+pub fn hkdf_expand<T: Copy>(params: ShaParams<T>, prk: &[u8], info: &[u8], out_len: usize) -> Result<Vec<u8>, KdfError> {
+    let iterations = out_len.div_ceil(params.digest_size);
+    if iterations > 255 {
+        return Err(KdfError::OutputTooLong);
+    }
+
+    let keyed = Hmac::new(params, prk);
+    let mut result = Vec::with_capacity(iterations * params.digest_size);
+    let mut t: Vec<u8> = Vec::new();
+    for i in 1..=iterations {
+        let mut mac = keyed.clone();
+        mac.update(&t);
+        mac.update(info);
+        mac.update(&[i as u8]);
+        t = mac.finish();
+        result.extend_from_slice(&t);
+    }
+    result.truncate(out_len);
+
+    Ok(result)
+}
+
+pub fn pbkdf2<T: Copy>(prf: ShaParams<T>, password: &[u8], salt: &[u8], iterations: u32, out_len: usize) -> Result<Vec<u8>, KdfError> {
+    if iterations == 0 {
+        return Err(KdfError::InvalidIterationCount);
+    }
+    let blocks = out_len.div_ceil(prf.digest_size);
+    if blocks as u64 > u32::MAX as u64 {
+        return Err(KdfError::OutputTooLong);
+    }
+
+    let keyed = Hmac::new(prf, password);
+    let mut result = Vec::with_capacity(blocks * prf.digest_size);
+    for i in 1..=blocks {
+        // U_1 = PRF(P, S || INT(i)), U_j = PRF(P, U_{j-1}), T_i = U_1 ^ ... ^ U_c
+        let mut mac = keyed.clone();
+        mac.update(salt);
+        mac.update(&(i as u32).to_be_bytes());
+        let mut u = mac.finish();
+        let mut t = u.clone();
+        for _ in 1..iterations {
+            let mut mac = keyed.clone();
+            mac.update(&u);
+            u = mac.finish();
+            t.iter_mut().zip(&u).for_each(|(t, u)| *t ^= u);
+        }
+        result.append(&mut t);
+    }
+    result.truncate(out_len);
+
+    Ok(result)
+}
+
+// Where the counter goes relative to the other PRF input. For counter mode, BeforeIter and
+// BeforeFixed are the same thing, as there is no iteration variable. MiddleFixed splits the
+// fixed input data at the given byte offset.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CounterLocation {
+    BeforeIter,
+    BeforeFixed,
+    MiddleFixed(usize),
+    AfterFixed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CounterSpec {
+    pub bits: usize,
+    pub location: CounterLocation,
+}
+
+impl CounterSpec {
+    pub fn new(bits: usize, location: CounterLocation) -> CounterSpec {
+        CounterSpec{bits, location}
+    }
+
+    fn validate(&self, fixed: &[u8], iterations: usize) -> Result<(), KdfError> {
+        if ![8, 16, 24, 32].contains(&self.bits) {
+            return Err(KdfError::InvalidCounterWidth);
+        }
+        if let CounterLocation::MiddleFixed(offset) = self.location {
+            if offset > fixed.len() {
+                return Err(KdfError::InvalidCounterLocation);
+            }
+        }
+        if iterations as u64 > (1u64 << self.bits) - 1 {
+            return Err(KdfError::OutputTooLong);
+        }
+        Ok(())
+    }
+
+    fn encode(&self, i: u32) -> Vec<u8> {
+        i.to_be_bytes()[4 - self.bits / 8..].to_vec()
+    }
+}
+
+// The conventional fixed input data: Label || 0x00 || Context || [L], with L (the output length
+// in bits) encoded big endian in l_bytes bytes, which must be from 1 to 8 and hold L.
+pub fn fixed_input(label: &[u8], context: &[u8], out_len: usize, l_bytes: usize) -> Result<Vec<u8>, KdfError> {
+    let l_bits = (out_len as u64).checked_mul(8).ok_or(KdfError::OutputTooLong)?.to_be_bytes();
+    if !(1..=8).contains(&l_bytes) || l_bits[..8 - l_bytes].iter().any(|&b| b != 0) {
+        return Err(KdfError::InvalidLengthWidth);
+    }
+
+    let mut fixed = label.to_vec();
+    fixed.push(0x00);
+    fixed.extend_from_slice(context);
+    fixed.extend_from_slice(&l_bits[8 - l_bytes..]);
+
+    Ok(fixed)
+}
+
+fn prf_input<T: Copy>(mac: &mut Hmac<T>, iter: &[u8], fixed: &[u8], counter: Option<(CounterSpec, u32)>) {
+    match counter {
+        None => {
+            mac.update(iter);
+            mac.update(fixed);
+        }
+        Some((spec, i)) => {
+            let c = spec.encode(i);
+            match spec.location {
+                CounterLocation::BeforeIter => {
+                    mac.update(&c);
+                    mac.update(iter);
+                    mac.update(fixed);
+                }
+                CounterLocation::BeforeFixed => {
+                    mac.update(iter);
+                    mac.update(&c);
+                    mac.update(fixed);
+                }
+                CounterLocation::MiddleFixed(offset) => {
+                    mac.update(iter);
+                    mac.update(&fixed[..offset]);
+                    mac.update(&c);
+                    mac.update(&fixed[offset..]);
+                }
+                CounterLocation::AfterFixed => {
+                    mac.update(iter);
+                    mac.update(fixed);
+                    mac.update(&c);
+                }
+            }
+        }
+    }
+}
+
+pub fn kbkdf_counter<T: Copy>(prf: ShaParams<T>, key: &[u8], fixed: &[u8], counter: CounterSpec, out_len: usize) -> Result<Vec<u8>, KdfError> {
+    let iterations = out_len.div_ceil(prf.digest_size);
+    counter.validate(fixed, iterations)?;
+
+    let keyed = Hmac::new(prf, key);
+    let mut result = Vec::with_capacity(iterations * prf.digest_size);
+    for i in 1..=iterations {
+        let mut mac = keyed.clone();
+        prf_input(&mut mac, &[], fixed, Some((counter, i as u32)));
+        result.extend(mac.finish());
+    }
+    result.truncate(out_len);
+
+    Ok(result)
+}
+
+// K(0) = iv, K(i) = PRF(key, K(i-1) {|| [i]} || fixed); the counter is optional in feedback mode.
+pub fn kbkdf_feedback<T: Copy>(prf: ShaParams<T>, key: &[u8], iv: &[u8], fixed: &[u8], counter: Option<CounterSpec>, out_len: usize) -> Result<Vec<u8>, KdfError> {
+    let iterations = out_len.div_ceil(prf.digest_size);
+    match counter {
+        Some(spec) => spec.validate(fixed, iterations)?,
+        None => if iterations as u64 > u32::MAX as u64 {
+            return Err(KdfError::OutputTooLong);
+        }
+    }
+
+    let keyed = Hmac::new(prf, key);
+    let mut result = Vec::with_capacity(iterations * prf.digest_size);
+    let mut k = iv.to_vec();
+    for i in 1..=iterations {
+        let mut mac = keyed.clone();
+        prf_input(&mut mac, &k, fixed, counter.map(|spec| (spec, i as u32)));
+        k = mac.finish();
+        result.extend_from_slice(&k);
+    }
+    result.truncate(out_len);
+
+    Ok(result)
+}
+
+fn concatenation_kdf<T: Copy>(hash: ShaParams<T>, z: &[u8], other_info: &[u8], out_len: usize, counter_first: bool) -> Result<Vec<u8>, KdfError> {
+    let iterations = out_len.div_ceil(hash.digest_size);
+    if iterations as u64 > u32::MAX as u64 {
+        return Err(KdfError::OutputTooLong);
+    }
+
+    let mut result = Vec::with_capacity(iterations * hash.digest_size);
+    for i in 1..=iterations {
+        let counter = (i as u32).to_be_bytes();
+        let mut hasher = Hasher::new(hash);
+        if counter_first {
+            hasher.update(&counter);
+            hasher.update(z);
+        } else {
+            hasher.update(z);
+            hasher.update(&counter);
+        }
+        hasher.update(other_info);
+        result.extend(hasher.finish());
+    }
+    result.truncate(out_len);
+
+    Ok(result)
+}
+
+// SP 800-56C one-step KDF with a plain hash as the auxiliary function: H(counter || Z || OtherInfo)
+pub fn one_step_kdf<T: Copy>(hash: ShaParams<T>, z: &[u8], other_info: &[u8], out_len: usize) -> Result<Vec<u8>, KdfError> {
+    concatenation_kdf(hash, z, other_info, out_len, true)
+}
+
+// ANSI X9.63 KDF: H(Z || counter || SharedInfo)
+pub fn x963_kdf<T: Copy>(hash: ShaParams<T>, z: &[u8], shared_info: &[u8], out_len: usize) -> Result<Vec<u8>, KdfError> {
+    concatenation_kdf(hash, z, shared_info, out_len, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha::{Algorithm, SHA1, SHA256, SHA384, SHA512};
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    extern crate hex;
+
+    // RFC 5869, test cases 1 and 3
+    #[test]
+    fn test_hkdf_sha256() {
+        let prk = hkdf_extract(SHA256, &unhex("000102030405060708090a0b0c"), &[0x0b; 22]);
+        assert_eq!(hex::encode(&prk), "077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5");
+        let okm = hkdf_expand(SHA256, &prk, &unhex("f0f1f2f3f4f5f6f7f8f9"), 42).unwrap();
+        assert_eq!(hex::encode(okm), "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865");
+    }
+
+    #[test]
+    fn test_hkdf_sha256_no_salt_no_info() {
+        let prk = hkdf_extract(SHA256, &[], &[0x0b; 22]);
+        assert_eq!(hex::encode(&prk), "19ef24a32c717b167f33a91d6f648bdf96596776afdb6377ac434c1c293ccb04");
+        let okm = hkdf_expand(SHA256, &prk, &[], 42).unwrap();
+        assert_eq!(hex::encode(okm), "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8");
+    }
+
+    #[test]
+    fn test_hkdf_expand_too_long() {
+        assert!(hkdf_expand(SHA256, &[0; 32], &[], 255 * 32).is_ok());
+        assert_eq!(hkdf_expand(SHA256, &[0; 32], &[], 255 * 32 + 1), Err(KdfError::OutputTooLong));
+    }
+
+    #[rstest(password, salt, iterations, expected,
+        case::rfc7914("passwd", "salt", 1, "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783"),
+        case::rfc7914_80000("Password", "NaCl", 80000, "4ddcd8f60b98be21830cee5ef22701f9641a4418d04c0414aeff08876b34ab56a1d425a1225833549adb841b51c9b3176a272bdebba1d078478f62b397f33c8d"),
+    )]
+    fn test_pbkdf2_hmac_sha256(password: &str, salt: &str, iterations: u32, expected: &str) {
+        let result = pbkdf2(SHA256, password.as_bytes(), salt.as_bytes(), iterations, 64).unwrap();
+        assert_eq!(hex::encode(result), expected);
+    }
+
+    #[test]
+    fn test_pbkdf2_hmac_sha512() {
+        let result = pbkdf2(SHA512, b"password", b"salt", 2, 64).unwrap();
+        assert_eq!(hex::encode(result), "e1d9c16aa681708a45f5c7c4e215ceb66e011a2e9f0040713f18aefdb866d53cf76cab2868a39b9f7840edce4fef5a82be67335c77a6068e04112754f27ccf4e");
+    }
+
+    #[test]
+    fn test_pbkdf2_zero_iterations() {
+        assert_eq!(pbkdf2(SHA256, b"passwd", b"salt", 0, 32), Err(KdfError::InvalidIterationCount));
+    }
+
+    // Key and fixed input data from the first HMAC_SHA256 counter mode vector in the NIST KBKDF
+    // CAVP set (KDFCTR_gen.rsp). That set isn't at hand for the other PRFs, counter widths and
+    // locations, so those reuse the key and fixed data, with the output checked against
+    // pyca/cryptography's KBKDFHMAC (an independent implementation, on OpenSSL).
+    const KI: &str = "dd1d91b7d90b2bd3138533ce92b272fbf8a369316aefe242e659cc0ae238afe0";
+    const FIXED: &str = "01322b96b30acd197979444e468e1c5c6859bf1b1cf951b7e725303e237e46b864a145fab25e517b08f8683d0315bb2911d80a0e8aba17f3b413faac";
+    const IV: &str = "93f698e842eed75394d629d957e2e89c6e741f810b623c8b901e38376d068e7b";
+
+    fn unhex(s: &str) -> Vec<u8> {
+        hex::decode(s).unwrap()
+    }
+
+    #[rstest(prf, bits, location, out_len, expected,
+        case::cavp_sha256_r8_before_fixed(Algorithm::Sha256, 8, CounterLocation::BeforeFixed, 16, "d0fe7e094ed44de101cbed70b1a32e99"),
+        case::sha1_r8_before_fixed(Algorithm::Sha1, 8, CounterLocation::BeforeFixed, 16, "1ce60ae4efe9a3104ea478b41408687e"),
+        case::sha256_r32_after_fixed(Algorithm::Sha256, 32, CounterLocation::AfterFixed, 40, "5744905fe42bf28854782ca85903da7d7c338523324cb7dca643cd404c35c5f4168110f242d2601a"),
+        case::sha256_r24_middle_fixed(Algorithm::Sha256, 24, CounterLocation::MiddleFixed(20), 48, "029d8438e206fae036a386dc42f0d7cbf92ed4e3679901a5c069bf8641879fb7575e8737b3ba2cbd5e43524b5eda111f"),
+        case::sha384_r16_after_fixed(Algorithm::Sha384, 16, CounterLocation::AfterFixed, 60, "a295409190c11771b2ea2651cb12c7397c00ed99397d2915cb0c1dd13a8f784c6a88afc27213e44b7b02b3cfaa623b23bf9e83b136881ba99a462c39"),
+        case::sha512_r16_before_fixed(Algorithm::Sha512, 16, CounterLocation::BeforeFixed, 100, "33843ffa42b4d2eae5513c43238d5faac64deb9aaa9f04380cc8fde34d5fca230d716c611cb92c6bc8feafdef7a6fe46da0c973e4b76f14fd89c57fbac8b8ed134a7cafa3f3f3502664b6e9b027c4902249d5b7e7c62022281b73d4a9c7b48cb524da059"),
+    )]
+    fn test_counter(prf: Algorithm, bits: usize, location: CounterLocation, out_len: usize, expected: &str) {
+        let spec = CounterSpec::new(bits, location);
+        let result = match prf {
+            Algorithm::Sha256 => kbkdf_counter(SHA256, &unhex(KI), &unhex(FIXED), spec, out_len),
+            Algorithm::Sha384 => kbkdf_counter(SHA384, &unhex(KI), &unhex(FIXED), spec, out_len),
+            Algorithm::Sha512 => kbkdf_counter(SHA512, &unhex(KI), &unhex(FIXED), spec, out_len),
+            Algorithm::Sha1 => kbkdf_counter(SHA1, &unhex(KI), &unhex(FIXED), spec, out_len),
+        };
+        assert_eq!(hex::encode(result.unwrap()), expected);
+    }
+
+    // pyca/cryptography builds the same fixed input from a label and context
+    #[test]
+    fn test_counter_with_label_and_context() {
+        let fixed = fixed_input(b"label", b"context", 42, 4).unwrap();
+        assert_eq!(hex::encode(&fixed), "6c6162656c00636f6e7465787400000150");
+        let result = kbkdf_counter(SHA256, &unhex(KI), &fixed, CounterSpec::new(32, CounterLocation::BeforeFixed), 42).unwrap();
+        assert_eq!(hex::encode(result), "108823e7244030923f68ddabcfb72e2d07313e565c7832cd38fa8a996dccdd736d5814653405e2ea9b1a");
+    }
+
+    #[test]
+    fn test_fixed_input_length_width() {
+        assert_eq!(hex::encode(fixed_input(b"", b"", 31, 1).unwrap()), "00f8");
+        assert_eq!(hex::encode(fixed_input(b"", b"", 1, 8).unwrap()), "000000000000000008");
+        assert_eq!(fixed_input(b"", b"", 16, 0), Err(KdfError::InvalidLengthWidth));
+        assert_eq!(fixed_input(b"", b"", 16, 9), Err(KdfError::InvalidLengthWidth));
+        // 256 bits don't fit in a byte
+        assert_eq!(fixed_input(b"", b"", 32, 1), Err(KdfError::InvalidLengthWidth));
+    }
+
+    #[test]
+    fn test_counter_invalid_width() {
+        let result = kbkdf_counter(SHA256, &unhex(KI), &unhex(FIXED), CounterSpec::new(12, CounterLocation::BeforeFixed), 16);
+        assert_eq!(result, Err(KdfError::InvalidCounterWidth));
+    }
+
+    #[test]
+    fn test_counter_overflow() {
+        let spec = CounterSpec::new(8, CounterLocation::BeforeFixed);
+        assert!(kbkdf_counter(SHA256, &unhex(KI), &[], spec, 255 * 32).is_ok());
+        assert_eq!(kbkdf_counter(SHA256, &unhex(KI), &[], spec, 255 * 32 + 1), Err(KdfError::OutputTooLong));
+    }
+
+    #[test]
+    fn test_counter_middle_outside_fixed() {
+        let result = kbkdf_counter(SHA256, &unhex(KI), &unhex(FIXED), CounterSpec::new(8, CounterLocation::MiddleFixed(61)), 16);
+        assert_eq!(result, Err(KdfError::InvalidCounterLocation));
+    }
+
+    // The NIST ACVP KAS-KDF two-step validation vectors (SP 800-56C) as kept in BoringSSL's
+    // acvptool tests: the key is HMAC(salt, Z), then feedback mode with an empty IV and an 8 bit
+    // counter after the fixed info, which is the party infos (id, then ephemeral data, if any)
+    // of U and then V. The last one is given as a wrong key, to be found so.
+    #[rstest(salt, z, fixed, dkm, valid,
+        case::tc55("9b969fa89ef8274afdeb5889cabdb26dfc98bf79339b8aaf00c4eecab5accbe6",
+            "f8b8c0d2f4663a26873fc15e113b00f4105826f6a26aafe08dc18d8cef75faea9c8652442a869563828be6bcd66167f8",
+            "3d8be87b0df5d3a56a0ea729adb35ba1f620bbcc2651c8edbb283e7416443675",
+            "b17e160c9011c4e067ba759958a2621f72e8614ef5c89d4079f8aad2e5db76d1", true),
+        case::tc60("abe2e0b8ab9fef53a110da4478c57aeb67e585bf8af3a4dc5c723322adb0e34a",
+            "fba3c69432590fd370a53d2106e20e17f339b44c79130c977b05dd229603e5ba",
+            "9f5496c737e04a750e454f81dacf48c1ba3ca86d5dd791d4c8c7725fe403b81769d0e7d28312c393407c89dcfdaa94ab\
+             62a28a328bed9a53bfe4fef932e97cbaee49963c84c49144d3196f9efe55c85787ead21fb24b29e1cc3856ba50deb0ce",
+            "0d99d3a7d3fd7375e17f4e7a2049f3073ad01e51edb7a97b65600d3ce42742e7", true),
+        case::tc80_default_salt("0000000000000000000000000000000000000000000000000000000000000000",
+            "f168d5234dfe770807333801313b3010d37b9268a85faae8c0655b2aef0a8d457df6bb61259632d0198425f4c019bc61",
+            "3db1dcc64ad7585605e64fb5ccd6129c41139d175db2e603590d7cc5803115c3\
+             bac170add3ea4ae754a607815bcf5808c4ba3ea73468b272c896d67137bf5f96c0c62c41e960a8da3649c01dafbc550c",
+            "3bec38b73cfd4cd812b7ca1c89799bac9db0dc645d93d48092e026c9c020af0c", true),
+        case::tc85_wrong("0000000000000000000000000000000000000000000000000000000000000000",
+            "0423ea4c7c4c458b85b9b7cf451b1b5050bb14aff560f88fef9ee333de6f6541",
+            "8ae1895cb3074552ea8e91caf0e6081771938c356eaab8e87dd49081975e15fd\
+             74b91d7be3d9e1721277953dbac34719535a682d69259b219fce255ebe57031a",
+            "af2a51ed5e94f4e80e51a054acef4034297ffa9d33ce7fc5edbe36f4e7279625", false),
+    )]
+    fn test_feedback_acvp_two_step(salt: &str, z: &str, fixed: &str, dkm: &str, valid: bool) {
+        let key = hkdf_extract(SHA256, &unhex(salt), &unhex(z));
+        let spec = CounterSpec::new(8, CounterLocation::AfterFixed);
+        let result = kbkdf_feedback(SHA256, &key, &[], &unhex(&fixed.replace(' ', "")), Some(spec), 32).unwrap();
+        assert_eq!(hex::encode(result) == dkm, valid);
+    }
+
+    // The feedback cases below have no published vector at hand; they were checked against a
+    // straightforward HMAC loop in Python, written from SP 800-108 rather than from this code.
+    #[test]
+    fn test_feedback_counter_after_iter() {
+        let spec = CounterSpec::new(32, CounterLocation::BeforeFixed);
+        let result = kbkdf_feedback(SHA256, &unhex(KI), &unhex(IV), &unhex(FIXED), Some(spec), 80).unwrap();
+        assert_eq!(hex::encode(result), "6a4aaa1b724ae8a714a0b4c244f1da679b40912c3f10d3cfeec9c46afe96db72d73dbfdddda12d9a465d2c3695d74436b5b44e566028cc35ad1858fee15285e9d674e017a1bb081d56e569c4f27d57d9");
+    }
+
+    #[test]
+    fn test_feedback_sha1_counter_after_fixed() {
+        let spec = CounterSpec::new(8, CounterLocation::AfterFixed);
+        let result = kbkdf_feedback(SHA1, &unhex(KI), &unhex(IV), &unhex(FIXED), Some(spec), 50).unwrap();
+        assert_eq!(hex::encode(result), "d2f9e118a72d560638f2e1983bfba96988136b1673d0e740152100756c5c3296b78ade2e6ac8c74647efeffda0c77063b57e");
+    }
+
+    #[test]
+    fn test_feedback_without_counter() {
+        let result = kbkdf_feedback(SHA512, &unhex(KI), &unhex(IV), &unhex(FIXED), None, 70).unwrap();
+        assert_eq!(hex::encode(result), "84cb14fcfe895886dac90b328950a2ccd992ebb69b32a786317a7efbf93e3de56daa3722f4d231d1172c42dc0dd7734e327589067d52ccbbea8fc8f91cedb5d82770522751e2");
+    }
+
+    #[test]
+    fn test_feedback_empty_iv_matches_counter_mode_first_block() {
+        let spec = CounterSpec::new(8, CounterLocation::BeforeIter);
+        let result = kbkdf_feedback(SHA256, &unhex(KI), &[], &unhex(FIXED), Some(spec), 40).unwrap();
+        assert_eq!(hex::encode(result), "d0fe7e094ed44de101cbed70b1a32e99c165a76fb4377089acaff62a1295ca60ab8591c7dbd1655e");
+    }
+
+    // A NIST CAVP KAS vector (SP 800-56A, with the one-step KDF), as used in pyca/cryptography's tests
+    #[test]
+    fn test_one_step_sha256() {
+        let z = "52169af5c485dcc2321eb8d26d5efa21fb9b93c98e38412ee2484cf14f0d0d23";
+        let other_info = "a1b2c3d4e53728157e634612c12d6d5223e204aeea4341565369647bd184bcd246f72971f292badaa2fe4124612cba";
+        let result = one_step_kdf(SHA256, &unhex(z), &unhex(other_info), 16).unwrap();
+        assert_eq!(hex::encode(result), "1c3bc9e7c4547c5191c0d478cccaed55");
+    }
+
+    // NIST CAVP ANSI X9.63 vectors (SHA-256), as used in pyca/cryptography's tests
+    #[rstest(z, shared_info, expected,
+        case::no_shared_info("96c05619d56c328ab95fe84b18264b08725b85e33fd34f08", "", "443024c3dae66b95e6f5670601558f71"),
+        case::shared_info("22518b10e70f2a3f243810ae3254139efbee04aa57c7af7d", "75eef81aa3041e33b80971203d2c0c52",
+            "c498af77161cc59f2962b9a713e2b215152d139766ce34a776df11866a69bf2e52a13d9c7c6fc878c50c5ea0bc7b00e0da2447cfd874f6cf92f30d0097111485\
+             500c90c3af8b487872d04685d14c8d1dc8d7fa08beb0ce0ababc11f0bd496269142d43525a78e5bc79a17f59676a5706dc54d54d4d1f0bd7e386128ec26afc21"),
+    )]
+    fn test_x963_sha256(z: &str, shared_info: &str, expected: &str) {
+        let result = x963_kdf(SHA256, &unhex(z), &unhex(shared_info), expected.len() / 2).unwrap();
+        assert_eq!(hex::encode(result), expected);
+    }
+}
@@ -3,10 +3,40 @@ mod padder;
 mod primitives;
 mod sha256;
 mod sha512;
+mod sha1;
 pub mod sha;
+pub mod pkcs1;
+pub mod hmac;
+pub mod kdf;
+pub mod tls;
+pub mod scrypt;
+pub mod blake2b;
+pub mod argon2;
+pub mod encoding;
+pub mod escape;
+pub mod check;
+pub mod cli;
+pub mod glob;
+pub mod walk;
+pub mod pool;
+pub mod cache;
+pub mod range;
+pub mod records;
+pub mod manifest;
+pub mod dupfind;
+pub mod hashdiff;
+pub mod tar;
+pub mod watch;
+pub mod progress;
+pub mod checkpoint;
+pub mod bench;
+mod mmap;
 mod sha_tests;
+#[cfg(test)]
+mod test_util;
 
 use std::env;
+use std::ffi::OsStr;
 use std::path::{PathBuf, Path};
 use std::fs::{File,read_dir};
 use std::convert::TryFrom;
@@ -15,13 +45,20 @@ use std::io::BufReader;
 use std::io::prelude::*;
 
 use padder::Padder;
-use sha::ShaParams;
+use sha::{Algorithm, BusyTime, Hasher, MultiHasher, ShaParams};
 
 
-fn is_file_or_complain(path: &PathBuf) -> bool {
+// The name standing for standard input, on the command line and in checksum lists.
+pub const STDIN_NAME: &str = "-";
+
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == STDIN_NAME
+}
+
+fn is_file_or_complain(path: &Path) -> bool {
     let name_of_executable = env::args().next().unwrap();
 
-    let ok = path.is_file();
+    let ok = is_stdin(path) || path.is_file();
     if !ok {
         eprintln!("{}: {} is not a file", name_of_executable, path.display());
     }
@@ -29,36 +66,182 @@ fn is_file_or_complain(path: &PathBuf) -> bool {
     ok
 }
 
-pub fn get_file_names(args: Vec<String>) -> io::Result<Vec<PathBuf>> {
-    let files: Vec<PathBuf>;
-    if args.len()>1 {
+pub fn get_file_names<S: AsRef<OsStr>>(args: Vec<S>) -> io::Result<Vec<PathBuf>> {
+    let files = if args.len()>1 {
         //explicit list of files; it's OK to say if something isn't a file
-        files = args[1..].iter()
+        args[1..].iter()
             .map(|s| Path::new(s).to_path_buf())
             .filter(|p| is_file_or_complain(p))
-            .collect::<Vec<_>>();
+            .collect::<Vec<_>>()
     } else {
         // no files, so read current dir, and silently ignore all that isn't a file
-        files = read_dir(".")?
+        let mut files = read_dir(".")?
             .filter_map(|e| e.ok())
             .map(|e| e.path())
             .filter(|p| p.is_file())
             .collect::<Vec<_>>();
-    }
+        files.sort();
+        files
+    };
 
     Ok(files)
 }
 
-pub fn sha_sum<T: std::clone::Clone>(algo: ShaParams<T>, file: &PathBuf) -> io::Result<Vec<u8>> {
-    let f = File::open(file)?;
-    let mut reader = BufReader::new(f);
+// Files are read this much at a time. It is a multiple of every block size, so all but the
+// last read hand whole runs of blocks straight to the compression function.
+pub const READ_BUFFER_SIZE: usize = 1 << 17;
+
+pub fn sha_sum<T: Copy>(algo: ShaParams<T>, file: &PathBuf) -> io::Result<Vec<u8>> {
+    hash_file(algo, &File::open(file)?, |_| ()).map(|(digest, _)| digest)
+}
+
+// Like sha_sum, but "-" means standard input.
+pub fn sha_sum_input<T: Copy>(algo: ShaParams<T>, file: &PathBuf) -> io::Result<Vec<u8>> {
+    if is_stdin(file) {
+        let stdin = io::stdin();
+        let reader = stdin.lock();
+        sha_sum_reader(algo, reader)
+    } else {
+        sha_sum(algo, file)
+    }
+}
+
+// Reads until buf is full or the input ends; pipes and terminals hand out data in
+// arbitrary portions, and only the last block may be short.
+pub(crate) fn read_block<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+pub fn sha_sum_reader<T: Copy, R: Read>(algo: ShaParams<T>, reader: R) -> io::Result<Vec<u8>> {
+    hash_reader(algo, reader).map(|(digest, _)| digest)
+}
+
+// Like sha_sum_input, also giving the number of bytes hashed.
+pub fn sha_sum_sized<T: Copy>(algo: ShaParams<T>, file: &PathBuf) -> io::Result<(Vec<u8>, u64)> {
+    if is_stdin(file) {
+        let stdin = io::stdin();
+        let reader = stdin.lock();
+        hash_reader(algo, reader)
+    } else {
+        hash_file(algo, &File::open(file)?, |_| ())
+    }
+}
+
+pub(crate) fn hash_reader<T: Copy, R: Read>(algo: ShaParams<T>, reader: R) -> io::Result<(Vec<u8>, u64)> {
+    let mut hasher = Hasher::new(algo);
+    let size = read_all(reader, |data| hasher.update(data))?;
+    Ok((hasher.finish(), size))
+}
+
+// Like hash_reader, for a file, which may be mapped rather than read; progress is told the
+// number of bytes hashed so far as it goes.
+pub(crate) fn hash_file<T: Copy, P: FnMut(u64)>(algo: ShaParams<T>, file: &File, mut progress: P) -> io::Result<(Vec<u8>, u64)> {
+    let mut hasher = Hasher::new(algo);
+    let mut hashed = 0;
+    let size = mmap::read_file(file, |data| {
+        hasher.update(data);
+        hashed += data.len() as u64;
+        progress(hashed);
+    })?;
+    Ok((hasher.finish(), size))
+}
+
+// Like sha_sum, taking the digest from the cache if the file is unchanged; see cache.rs.
+pub fn sha_sum_cached<T: Copy>(algo: ShaParams<T>, file: &PathBuf, cache: &cache::DigestCache) -> io::Result<Vec<u8>> {
+    match Algorithm::from_name(algo.name) {
+        Some(algorithm) => cache::cached_sum(cache, &[algorithm], file).map(|(mut digests, _)| digests.remove(0)),
+        None => sha_sum(algo, file),
+    }
+}
+
+// Several digests of a file ("-" being standard input), reading it once; the digests are in
+// the order of the algorithms, followed by the number of bytes hashed.
+pub fn multi_sum_input(algorithms: &[Algorithm], file: &PathBuf) -> io::Result<(Vec<Vec<u8>>, u64)> {
+    if is_stdin(file) {
+        let stdin = io::stdin();
+        let reader = stdin.lock();
+        multi_sum_reader(algorithms, reader)
+    } else {
+        multi_sum_file_timed(algorithms, &File::open(file)?, |_| (), None)
+    }
+}
+
+pub fn multi_sum_reader<R: Read>(algorithms: &[Algorithm], reader: R) -> io::Result<(Vec<Vec<u8>>, u64)> {
+    multi_sum_reader_timed(algorithms, reader, None)
+}
+
+// Like multi_sum_reader, adding the time spent in each algorithm to busy.
+pub fn multi_sum_reader_timed<R: Read>(algorithms: &[Algorithm], reader: R, busy: Option<&BusyTime>)
+    -> io::Result<(Vec<Vec<u8>>, u64)>
+{
+    let mut hasher = MultiHasher::new(algorithms);
+    if busy.is_some() {
+        hasher.time();
+    }
+    let size = read_all(reader, |data| hasher.update(data))?;
+    if let Some(busy) = busy {
+        busy.add(&hasher);
+    }
+    Ok((hasher.finish(), size))
+}
+
+// Like multi_sum_reader_timed, for a file, which may be mapped rather than read; progress is
+// told the number of bytes hashed so far as it goes.
+pub(crate) fn multi_sum_file_timed<P: FnMut(u64)>(algorithms: &[Algorithm], file: &File, mut progress: P, busy: Option<&BusyTime>)
+    -> io::Result<(Vec<Vec<u8>>, u64)>
+{
+    let mut hasher = MultiHasher::new(algorithms);
+    if busy.is_some() {
+        hasher.time();
+    }
+    let mut hashed = 0;
+    let size = mmap::read_file(file, |data| {
+        hasher.update(data);
+        hashed += data.len() as u64;
+        progress(hashed);
+    })?;
+    if let Some(busy) = busy {
+        busy.add(&hasher);
+    }
+    Ok((hasher.finish(), size))
+}
+
+// Hands everything the reader has to update, READ_BUFFER_SIZE bytes at a time, and returns
+// the number of bytes read.
+pub(crate) fn read_all<R: Read, F: FnMut(&[u8])>(mut reader: R, mut update: F) -> io::Result<u64> {
+    let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+    let mut size = 0u64;
+
+    loop {
+        let len = read_block(&mut reader, &mut buffer)?;
+        update(&buffer[..len]);
+        size += len as u64;
+        if len < buffer.len() {
+            return Ok(size);
+        }
+    }
+}
+
+// The original way of hashing a file: one block per read, through a BufReader. Kept for
+// comparison - see benches/file_hash.rs.
+pub fn sha_sum_blockwise<T: Copy>(algo: ShaParams<T>, file: &PathBuf) -> io::Result<Vec<u8>> {
+    let mut reader = BufReader::new(File::open(file)?);
     let mut count: u128 = 0;
     let mut msg = [0u8;128];
     let mut hash = algo.h0;
     let padder = Padder::new(algo.block_size, algo.length_size);
 
     loop {
-        let len = reader.read(&mut msg[..algo.block_size])?;
+        let len = read_block(&mut reader, &mut msg[..algo.block_size])?;
         count += u128::try_from(len).unwrap();
 
         if padder.is_full_block(len) {
@@ -0,0 +1,686 @@
+// Manifests in the format of hashdeep: the size and several digests of every file below a
+// directory, so the directory can later be audited against it.
+//
+//   %%%% HASHDEEP-1.0
+//   %%%% size,sha256,sha512,filename
+//   ## comment lines
+//   3,ba7816bf...,ddaf35a1...,sub/abc.txt
+//
+// File names are relative to the directory and run to the end of the line, so they may
+// contain commas. hashdeep writes them as given on its command line, e.g. ./sub/abc.txt or
+// /home/user/dir/sub/abc.txt; a leading ./ is dropped when parsing, and the directory
+// audited is taken off the front of the names if all of them start with it. A name with a
+// newline, carriage return or backslash is escaped, and its line starts with a backslash, as
+// in checksum lists (see escape.rs).
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::OsString;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::path::{Component, Path, PathBuf};
+
+use crate::check::describe_error;
+use crate::cli::{algorithms, jobs, pattern, report_walk_warnings, split_args, Arg, OptionSpec};
+use crate::escape::{escape_name, needs_escape, path_from_bytes, path_to_bytes, unescape_name};
+use crate::pool::Pool;
+use crate::sha::Algorithm;
+use crate::walk::{walk, WalkOptions, WalkWarning};
+use crate::multi_sum_input;
+
+pub const HEADER: &str = "%%%% HASHDEEP-1.0";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub size: u64,
+    // one per algorithm of the manifest, in the same order
+    pub digests: Vec<Vec<u8>>,
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Manifest {
+    pub algorithms: Vec<Algorithm>,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    Io(io::Error),
+    MissingHeader,
+    // the column line, if it isn't size, digests and filename
+    BadColumns(String),
+    UnknownAlgorithm(String),
+    // the number of the line, counting from 1
+    BadLine(usize),
+}
+
+impl From<io::Error> for ManifestError {
+    fn from(e: io::Error) -> ManifestError {
+        ManifestError::Io(e)
+    }
+}
+
+fn column_name(algorithm: Algorithm) -> String {
+    algorithm.name().to_lowercase()
+}
+
+fn parse_entry(line: &[u8], algorithms: &[Algorithm]) -> Option<Entry> {
+    let (escaped, line) = match line.strip_prefix(b"\\") {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+
+    let mut fields = line.splitn(algorithms.len() + 2, |&b| b == b',');
+    let size = std::str::from_utf8(fields.next()?).ok()?.parse::<u64>().ok()?;
+    let mut digests = Vec::with_capacity(algorithms.len());
+    for algorithm in algorithms {
+        let digest = hex::decode(fields.next()?).ok()?;
+        if digest.len() != algorithm.digest_size() {
+            return None;
+        }
+        digests.push(digest);
+    }
+
+    let name = fields.next().filter(|name| !name.is_empty())?;
+    let name = if escaped { unescape_name(name)? } else { name.to_vec() };
+    Some(Entry{size, digests, path: without_cur_dir(&path_from_bytes(&name))})
+}
+
+// "./sub/abc.txt" as "sub/abc.txt", which is how scan gives it.
+fn without_cur_dir(path: &Path) -> PathBuf {
+    path.components().filter(|c| *c != Component::CurDir).collect()
+}
+
+impl Manifest {
+    pub fn new(algorithms: Vec<Algorithm>) -> Manifest {
+        Manifest{algorithms, entries: Vec::new()}
+    }
+
+    pub fn parse<R: BufRead>(reader: R) -> Result<Manifest, ManifestError> {
+        let mut lines = reader.split(b'\n');
+        let mut next_line = || -> Result<Option<Vec<u8>>, ManifestError> {
+            match lines.next().transpose()? {
+                Some(mut line) => {
+                    if line.last() == Some(&b'\r') {
+                        line.pop();
+                    }
+                    Ok(Some(line))
+                }
+                None => Ok(None),
+            }
+        };
+
+        if next_line()?.as_deref() != Some(HEADER.as_bytes()) {
+            return Err(ManifestError::MissingHeader);
+        }
+
+        let columns = next_line()?.unwrap_or_default();
+        let columns = String::from_utf8_lossy(&columns).into_owned();
+        let names: Vec<&str> = match columns.strip_prefix("%%%% ") {
+            Some(names) => names.split(',').collect(),
+            None => return Err(ManifestError::BadColumns(columns)),
+        };
+        if names.len() < 3 || names[0] != "size" || names[names.len() - 1] != "filename" {
+            return Err(ManifestError::BadColumns(columns));
+        }
+        let algorithms = names[1..names.len() - 1].iter()
+            .map(|name| Algorithm::from_name(name).ok_or_else(|| ManifestError::UnknownAlgorithm(name.to_string())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut manifest = Manifest::new(algorithms);
+        let mut number = 2;
+        while let Some(line) = next_line()? {
+            number += 1;
+            // manifests appended to each other repeat the header
+            if line.is_empty() || line.starts_with(b"##") || line.starts_with(b"%%%%") {
+                continue;
+            }
+            let entry = parse_entry(&line, &manifest.algorithms).ok_or(ManifestError::BadLine(number))?;
+            manifest.entries.push(entry);
+        }
+
+        Ok(manifest)
+    }
+
+    // Makes the names relative to root if every one of them starts with it, as they do in a
+    // manifest written by hashdeep -r root.
+    pub fn strip_root(&mut self, root: &Path) {
+        let root = without_cur_dir(root);
+        if root.as_os_str().is_empty() || !self.entries.iter().all(|e| e.path.starts_with(&root)) {
+            return;
+        }
+        for entry in &mut self.entries {
+            entry.path = entry.path.strip_prefix(&root).unwrap().to_path_buf();
+        }
+    }
+
+    // comments are written as ## lines after the header.
+    pub fn write<W: Write>(&self, mut out: W, comments: &[String]) -> io::Result<()> {
+        let columns: Vec<String> = self.algorithms.iter().map(|&a| column_name(a)).collect();
+        writeln!(out, "{}", HEADER)?;
+        writeln!(out, "%%%% size,{},filename", columns.join(","))?;
+        for comment in comments {
+            writeln!(out, "## {}", comment)?;
+        }
+        writeln!(out, "##")?;
+
+        for entry in &self.entries {
+            let name = path_to_bytes(&entry.path);
+            let mut line = Vec::new();
+            if needs_escape(&name) {
+                line.push(b'\\');
+            }
+            line.extend_from_slice(entry.size.to_string().as_bytes());
+            for digest in &entry.digests {
+                line.push(b',');
+                line.extend_from_slice(hex::encode(digest).as_bytes());
+            }
+            line.push(b',');
+            line.extend_from_slice(&escape_name(&name));
+            line.push(b'\n');
+            out.write_all(&line)?;
+        }
+
+        out.flush()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Scan {
+    // paths relative to the root
+    pub entries: Vec<Entry>,
+    pub errors: Vec<(PathBuf, io::Error)>,
+    pub warnings: Vec<WalkWarning>,
+}
+
+fn is_same_file(path: &Path, canonical: &Path) -> bool {
+    path.file_name() == canonical.file_name() && fs::canonicalize(path).is_ok_and(|p| p == canonical)
+}
+
+// Hashes every file below root on the pool. skip is the canonical path of a file to leave
+// out, e.g. the manifest itself.
+pub fn scan(root: &Path, algorithms: &[Algorithm], options: &WalkOptions, pool: &Pool, skip: Option<&Path>) -> Scan {
+    let walk = walk(&[root.to_path_buf()], options);
+    let files: Vec<PathBuf> = walk.files.into_iter()
+        .filter(|file| !skip.is_some_and(|skip| is_same_file(file, skip)))
+        .collect();
+
+    let mut result = Scan{warnings: walk.warnings, ..Default::default()};
+    pool.map_ordered(&files, |file| multi_sum_input(algorithms, file), |_, file, hashed| match hashed {
+        Ok((digests, size)) => {
+            let path = file.strip_prefix(root).unwrap_or(file).to_path_buf();
+            result.entries.push(Entry{size, digests, path});
+        }
+        Err(e) => result.errors.push((file.clone(), e)),
+    });
+    result
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Matched,
+    // same path, different content
+    Changed,
+    // the content of a file that is missing from its old path
+    Moved(PathBuf),
+    New,
+    Missing,
+}
+
+// Exit codes of the audit command, or'ed together.
+pub const EXIT_ERROR: i32 = 1;
+pub const EXIT_CHANGED: i32 = 2;
+pub const EXIT_MOVED: i32 = 4;
+pub const EXIT_NEW: i32 = 8;
+pub const EXIT_MISSING: i32 = 16;
+
+impl Status {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Status::Matched => 0,
+            Status::Changed => EXIT_CHANGED,
+            Status::Moved(_) => EXIT_MOVED,
+            Status::New => EXIT_NEW,
+            Status::Missing => EXIT_MISSING,
+        }
+    }
+}
+
+// Classifies the files found against the manifest, which must have the same algorithms.
+// unreadable are the files that are there but couldn't be hashed: they are neither missing
+// nor anything else, having been reported already. The files found come first, in their
+// order, then the missing ones in manifest order.
+pub fn audit(manifest: &Manifest, found: &[Entry], unreadable: &[&Path]) -> Vec<(PathBuf, Status)> {
+    let known: HashMap<&Path, &Entry> = manifest.entries.iter().map(|e| (e.path.as_path(), e)).collect();
+    let mut present: HashSet<&Path> = found.iter().map(|e| e.path.as_path()).collect();
+    present.extend(unreadable);
+
+    // the content of the entries whose path is gone, to find where it went; each is the source
+    // of one move, so of two copies of a file that was moved the second is new
+    let mut gone: HashMap<(u64, &[Vec<u8>]), VecDeque<&Path>> = HashMap::new();
+    for entry in manifest.entries.iter().filter(|e| !present.contains(e.path.as_path())) {
+        gone.entry((entry.size, &entry.digests)).or_default().push_back(&entry.path);
+    }
+
+    let mut moved_from = HashSet::new();
+    let mut result = Vec::with_capacity(found.len());
+    for entry in found {
+        let status = match known.get(entry.path.as_path()) {
+            Some(old) if old.size == entry.size && old.digests == entry.digests => Status::Matched,
+            Some(_) => Status::Changed,
+            None => match gone.get_mut(&(entry.size, &entry.digests[..])).and_then(|from| from.pop_front()) {
+                Some(from) => {
+                    moved_from.insert(from);
+                    Status::Moved(from.to_path_buf())
+                }
+                None => Status::New,
+            },
+        };
+        result.push((entry.path.clone(), status));
+    }
+
+    for entry in &manifest.entries {
+        let path = entry.path.as_path();
+        if !present.contains(path) && !moved_from.contains(path) {
+            result.push((entry.path.clone(), Status::Missing));
+        }
+    }
+
+    result
+}
+
+// The manifest command line.
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Generate{dir: PathBuf},
+    Audit{manifest: PathBuf, dir: PathBuf},
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub command: Command,
+    pub algorithms: Vec<Algorithm>,
+    pub walk_options: WalkOptions,
+    // 0 for one worker per CPU
+    pub jobs: usize,
+    pub output: Option<PathBuf>,
+    pub verbose: bool,
+}
+
+pub fn usage(program: &str) -> String {
+    let mut text = format!("Usage: {} generate [OPTION]... [DIR]\n", program);
+    text.push_str(&format!("  or:  {} audit [OPTION]... MANIFEST [DIR]\n", program));
+    text.push_str("Write a manifest of the size and digests of every file below DIR (the current directory\n");
+    text.push_str("by default), or audit DIR against a manifest.\n\n");
+    text.push_str("  -a, --algorithms=LIST the digests to store, e.g. sha256,sha512 (the default)\n");
+    text.push_str("  -o, --output=FILE     write the manifest to FILE instead of standard output\n");
+    text.push_str("  -v, --verbose         with audit, list the matching files as well\n");
+    text.push_str("  -j, --jobs=N          hash N files at a time; the default is one per CPU\n");
+    text.push_str("  -L, --follow-symlinks follow symbolic links found in the directories\n");
+    text.push_str("      --exclude=PATTERN skip files and directories matching PATTERN; may be repeated\n");
+    text.push_str("\nThe exit status of audit is 0 if every file matched, otherwise the sum of\n");
+    text.push_str("  1 if a file couldn't be read, 2 if a file changed, 4 if one moved,\n");
+    text.push_str("  8 if there are new files and 16 if some are missing.\n");
+    text
+}
+
+const OPTIONS: OptionSpec = OptionSpec{
+    long: &["algorithms", "output", "verbose", "jobs", "follow-symlinks", "exclude"],
+    short: &[('a', "algorithms"), ('o', "output"), ('v', "verbose"), ('j', "jobs"), ('L', "follow-symlinks")],
+    with_value: &["algorithms", "output", "jobs", "exclude"],
+};
+
+// args[0] is the name of the program.
+pub fn parse_args(args: &[OsString]) -> Result<Options, String> {
+    let mut algorithm_list = None;
+    let mut walk_options = WalkOptions::default();
+    let mut jobs_given = 0;
+    let mut output = None;
+    let mut verbose = false;
+    let mut operands = Vec::new();
+
+    for arg in split_args(args, &OPTIONS)? {
+        match arg {
+            Arg::Operand(operand) => operands.push(operand),
+            Arg::Option(name, value) => {
+                let value = value.unwrap_or_default();
+                match name.as_str() {
+                    "algorithms" => algorithm_list = Some(algorithms(&value)?),
+                    "output" => output = Some(PathBuf::from(value)),
+                    "verbose" => verbose = true,
+                    "jobs" => jobs_given = jobs(&value)?,
+                    "follow-symlinks" => walk_options.follow_symlinks = true,
+                    "exclude" => walk_options.exclude.push(pattern(&value)?),
+                    _ => unreachable!("option --{} is in OPTIONS but not handled", name),
+                }
+            }
+        }
+    }
+
+    let mut operands = operands.into_iter().map(PathBuf::from);
+    let command = match operands.next().as_ref().and_then(|c| c.to_str()) {
+        Some("generate") => {
+            if verbose {
+                return Err("the --verbose option is meaningful only with audit".to_string());
+            }
+            Command::Generate{dir: operands.next().unwrap_or_else(|| PathBuf::from("."))}
+        }
+        Some("audit") => {
+            if algorithm_list.is_some() || output.is_some() {
+                return Err("the --algorithms and --output options are meaningful only with generate".to_string());
+            }
+            let manifest = operands.next().ok_or("missing manifest to audit against")?;
+            Command::Audit{manifest, dir: operands.next().unwrap_or_else(|| PathBuf::from("."))}
+        }
+        Some(command) => return Err(format!("unknown command '{}'; use generate or audit", command)),
+        None => return Err("missing command; use generate or audit".to_string()),
+    };
+    if let Some(extra) = operands.next() {
+        return Err(format!("extra operand '{}'", extra.display()));
+    }
+
+    Ok(Options{
+        command,
+        algorithms: algorithm_list.unwrap_or_else(|| vec![Algorithm::Sha256, Algorithm::Sha512]),
+        walk_options,
+        jobs: jobs_given,
+        output,
+        verbose,
+    })
+}
+
+// Prints what couldn't be read; false if anything couldn't.
+fn report_scan(program: &str, scan: &mut Scan) -> bool {
+    let mut ok = report_walk_warnings(program, std::mem::take(&mut scan.warnings));
+    for (path, e) in &scan.errors {
+        eprintln!("{}: {}: {}", program, path.display(), describe_error(e));
+        ok = false;
+    }
+    ok
+}
+
+fn generate(program: &str, dir: &Path, options: &Options) -> io::Result<bool> {
+    let (out, skip): (Box<dyn Write>, _) = match &options.output {
+        Some(path) => (Box::new(File::create(path)?), Some(fs::canonicalize(path)?)),
+        None => (Box::new(io::stdout()), None),
+    };
+
+    let mut scan = scan(dir, &options.algorithms, &options.walk_options, &Pool::new(options.jobs), skip.as_deref());
+    let ok = report_scan(program, &mut scan);
+
+    let manifest = Manifest{algorithms: options.algorithms.clone(), entries: scan.entries};
+    let invoked_from = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    manifest.write(BufWriter::new(out), &[format!("Invoked from: {}", invoked_from.display())])?;
+    Ok(ok)
+}
+
+fn run_audit(program: &str, manifest_path: &Path, dir: &Path, options: &Options) -> i32 {
+    let manifest = match File::open(manifest_path).map_err(ManifestError::Io).and_then(|f| Manifest::parse(BufReader::new(f))) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            let reason = match e {
+                ManifestError::Io(e) => describe_error(&e),
+                ManifestError::MissingHeader => format!("not a manifest; the first line must be {}", HEADER),
+                ManifestError::BadColumns(line) => format!("bad column line '{}'", line),
+                ManifestError::UnknownAlgorithm(name) => format!("unsupported algorithm '{}'", name),
+                ManifestError::BadLine(number) => format!("line {}: improperly formatted", number),
+            };
+            eprintln!("{}: {}: {}", program, manifest_path.display(), reason);
+            return EXIT_ERROR;
+        }
+    };
+
+    let mut manifest = manifest;
+    manifest.strip_root(dir);
+    if let Ok(canonical) = fs::canonicalize(dir) {
+        manifest.strip_root(&canonical);
+    }
+
+    let skip = fs::canonicalize(manifest_path).ok();
+    let mut scan = scan(dir, &manifest.algorithms, &options.walk_options, &Pool::new(options.jobs), skip.as_deref());
+    let mut code = if report_scan(program, &mut scan) { 0 } else { EXIT_ERROR };
+
+    let unreadable: Vec<&Path> = scan.errors.iter().map(|(path, _)| path.strip_prefix(dir).unwrap_or(path)).collect();
+    let mut counts = [0; 5];
+    for (path, status) in audit(&manifest, &scan.entries, &unreadable) {
+        code |= status.exit_code();
+        let (index, label) = match status {
+            Status::Matched => (0, "matched"),
+            Status::Changed => (1, "changed"),
+            Status::Moved(_) => (2, "moved"),
+            Status::New => (3, "new"),
+            Status::Missing => (4, "missing"),
+        };
+        counts[index] += 1;
+        match status {
+            Status::Matched if !options.verbose => (),
+            Status::Moved(from) => println!("{}: {} (from {})", label, path.display(), from.display()),
+            _ => println!("{}: {}", label, path.display()),
+        }
+    }
+
+    println!("{} matched, {} changed, {} moved, {} new, {} missing", counts[0], counts[1], counts[2], counts[3], counts[4]);
+    code
+}
+
+pub fn run(args: Vec<OsString>) -> i32 {
+    let program = args.first().map(|p| p.to_string_lossy().into_owned()).unwrap_or_else(|| "manifest".to_string());
+
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{}: {}", program, msg);
+            eprint!("{}", usage(&program));
+            return 1;
+        }
+    };
+
+    match &options.command {
+        Command::Generate{dir} => match generate(&program, dir, &options) {
+            Ok(true) => 0,
+            Ok(false) => 1,
+            Err(e) => {
+                eprintln!("{}: {}", program, describe_error(&e));
+                1
+            }
+        },
+        Command::Audit{manifest, dir} => run_audit(&program, manifest, dir, &options),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    extern crate hex;
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    const ABC_SHA512: &str = "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f";
+
+    use crate::sha::{SHA256, SHA512};
+
+    fn entry(content: &[u8], path: &str) -> Entry {
+        let digests = vec![crate::sha::sha(SHA256, content.iter().cloned()), crate::sha::sha(SHA512, content.iter().cloned())];
+        Entry{size: content.len() as u64, digests, path: PathBuf::from(path)}
+    }
+
+    fn manifest(entries: Vec<Entry>) -> Manifest {
+        Manifest{algorithms: vec![Algorithm::Sha256, Algorithm::Sha512], entries}
+    }
+
+    fn args(line: &str) -> Vec<OsString> {
+        line.split_whitespace().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_write_and_parse() {
+        let manifest = manifest(vec![entry(b"abc", "sub/a,b.txt"), entry(b"", "new\nline")]);
+        let mut out = Vec::new();
+        manifest.write(&mut out, &["Invoked from: /tmp".to_string()]).unwrap();
+
+        let text = String::from_utf8(out.clone()).unwrap();
+        let mut lines = text.lines();
+        assert_eq!(lines.next(), Some("%%%% HASHDEEP-1.0"));
+        assert_eq!(lines.next(), Some("%%%% size,sha256,sha512,filename"));
+        assert_eq!(lines.next(), Some("## Invoked from: /tmp"));
+        assert_eq!(lines.next(), Some("##"));
+        assert_eq!(lines.next(), Some(format!("3,{},{},sub/a,b.txt", ABC_SHA256, ABC_SHA512).as_str()));
+        assert!(lines.next().unwrap().starts_with("\\0,e3b0"));
+
+        assert_eq!(Manifest::parse(&out[..]).unwrap(), manifest);
+    }
+
+    #[rstest(text, expected,
+        case::empty("", "MissingHeader"),
+        case::other_header("%%%% HASHDEEP-2.0\n", "MissingHeader"),
+        case::no_filename("%%%% HASHDEEP-1.0\n%%%% size,sha256\n", "BadColumns"),
+        case::md5("%%%% HASHDEEP-1.0\n%%%% size,md5,filename\n", "UnknownAlgorithm"),
+        case::short_digest("%%%% HASHDEEP-1.0\n%%%% size,sha256,filename\n##\n3,ba78,abc\n", "BadLine(4)"),
+        case::no_name("%%%% HASHDEEP-1.0\n%%%% size,sha256,filename\n3,ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad,\n", "BadLine(3)"),
+    )]
+    fn test_parse_errors(text: &str, expected: &str) {
+        let error = format!("{:?}", Manifest::parse(text.as_bytes()).unwrap_err());
+        assert!(error.starts_with(expected), "{}", error);
+    }
+
+    #[test]
+    fn test_parse_hashdeep_output() {
+        let text = format!("%%%% HASHDEEP-1.0\r\n%%%% size,sha256,filename\r\n## Invoked from: /home/user\r\n## $ hashdeep -r .\r\n##\r\n\
+                            3,{},./abc.txt\r\n%%%% HASHDEEP-1.0\r\n", ABC_SHA256);
+        let manifest = Manifest::parse(text.as_bytes()).unwrap();
+        assert_eq!(manifest.algorithms, vec![Algorithm::Sha256]);
+        assert_eq!(manifest.entries, vec![Entry{size: 3, digests: vec![hex::decode(ABC_SHA256).unwrap()], path: PathBuf::from("abc.txt")}]);
+    }
+
+    #[test]
+    fn test_strip_root() {
+        let mut old = manifest(vec![entry(b"abc", "/home/user/dir/abc.txt"), entry(b"", "/home/user/dir/sub/empty")]);
+        old.strip_root(Path::new("/home/user"));
+        assert_eq!(old, manifest(vec![entry(b"abc", "dir/abc.txt"), entry(b"", "dir/sub/empty")]));
+
+        // sub isn't in front of every name, so it may well be a directory of the one audited
+        old.strip_root(Path::new("./dir/sub"));
+        assert_eq!(old.entries[1].path, PathBuf::from("dir/sub/empty"));
+        old.strip_root(Path::new("./dir"));
+        assert_eq!(old, manifest(vec![entry(b"abc", "abc.txt"), entry(b"", "sub/empty")]));
+        old.strip_root(Path::new("."));
+        assert_eq!(old, manifest(vec![entry(b"abc", "abc.txt"), entry(b"", "sub/empty")]));
+    }
+
+    #[test]
+    fn test_audit_hashdeep_names() {
+        let dir = TempDir::new("manifest-hashdeep");
+        dir.write("abc.txt", b"abc");
+        dir.write("sub/empty", b"");
+        let text = format!("%%%% HASHDEEP-1.0\n%%%% size,sha256,sha512,filename\n##\n3,{},{},./abc.txt\n0,{},{},./sub/empty\n",
+            ABC_SHA256, ABC_SHA512, hex::encode(crate::sha::sha(SHA256, None)), hex::encode(crate::sha::sha(SHA512, None)));
+        let old = Manifest::parse(text.as_bytes()).unwrap();
+
+        let scan = scan(dir.path(), &old.algorithms, &WalkOptions::default(), &Pool::new(2), None);
+        let path = PathBuf::from;
+        assert_eq!(audit(&old, &scan.entries, &[]), vec![(path("abc.txt"), Status::Matched), (path("sub/empty"), Status::Matched)]);
+    }
+
+    #[test]
+    fn test_audit() {
+        let old = manifest(vec![
+            entry(b"same", "same"),
+            entry(b"old", "changed"),
+            entry(b"moving", "old/place"),
+            entry(b"gone", "gone"),
+        ]);
+        let found = vec![
+            entry(b"new", "changed"),
+            entry(b"moving", "new/place"),
+            entry(b"same", "same"),
+            entry(b"fresh", "fresh"),
+            // a copy of a file still in place is new, not moved
+            entry(b"same", "copy"),
+        ];
+
+        let path = PathBuf::from;
+        assert_eq!(audit(&old, &found, &[]), vec![
+            (path("changed"), Status::Changed),
+            (path("new/place"), Status::Moved(path("old/place"))),
+            (path("same"), Status::Matched),
+            (path("fresh"), Status::New),
+            (path("copy"), Status::New),
+            (path("gone"), Status::Missing),
+        ]);
+    }
+
+    #[test]
+    fn test_audit_moves_each_file_once() {
+        let old = manifest(vec![entry(b"moving", "a"), entry(b"twin", "b"), entry(b"twin", "c")]);
+        let found = vec![entry(b"moving", "x"), entry(b"moving", "y"), entry(b"twin", "z")];
+
+        let path = PathBuf::from;
+        assert_eq!(audit(&old, &found, &[]), vec![
+            (path("x"), Status::Moved(path("a"))),
+            (path("y"), Status::New),
+            (path("z"), Status::Moved(path("b"))),
+            (path("c"), Status::Missing),
+        ]);
+    }
+
+    #[test]
+    fn test_audit_unreadable() {
+        let old = manifest(vec![entry(b"locked", "locked"), entry(b"locked", "moved")]);
+        let found = vec![entry(b"locked", "elsewhere")];
+
+        // the unreadable file is neither missing nor where the other one went
+        let path = PathBuf::from;
+        assert_eq!(audit(&old, &found, &[Path::new("locked")]), vec![
+            (path("elsewhere"), Status::Moved(path("moved"))),
+        ]);
+    }
+
+    #[test]
+    fn test_scan_skips_manifest() {
+        let dir = TempDir::new("manifest-scan");
+        dir.write("abc.txt", b"abc");
+        dir.write("sub/empty", b"");
+        let skip = fs::canonicalize(dir.write("MANIFEST", b"")).unwrap();
+
+        let scan = scan(dir.path(), &[Algorithm::Sha256], &WalkOptions::default(), &Pool::new(2), Some(&skip));
+        let paths: Vec<_> = scan.entries.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(paths, vec![PathBuf::from("abc.txt"), PathBuf::from("sub/empty")]);
+        assert_eq!(hex::encode(&scan.entries[0].digests[0]), ABC_SHA256);
+        assert!(scan.errors.is_empty() && scan.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse_args(&args("manifest generate -a sha512,SHA-256 -o out -j2 dir")).unwrap();
+        assert_eq!(options.command, Command::Generate{dir: PathBuf::from("dir")});
+        assert_eq!(options.algorithms, vec![Algorithm::Sha512, Algorithm::Sha256]);
+        assert_eq!(options.output, Some(PathBuf::from("out")));
+        assert_eq!(options.jobs, 2);
+
+        let options = parse_args(&args("manifest audit -v MANIFEST")).unwrap();
+        assert_eq!(options.command, Command::Audit{manifest: PathBuf::from("MANIFEST"), dir: PathBuf::from(".")});
+        assert_eq!(options.algorithms, vec![Algorithm::Sha256, Algorithm::Sha512]);
+        assert!(options.verbose);
+    }
+
+    #[rstest(line, expected,
+        case::no_command("manifest", "missing command; use generate or audit"),
+        case::unknown_command("manifest check", "unknown command 'check'; use generate or audit"),
+        case::no_manifest("manifest audit", "missing manifest to audit against"),
+        case::extra("manifest generate a b", "extra operand 'b'"),
+        case::algorithms_on_audit("manifest audit -a sha256 M", "the --algorithms and --output options are meaningful only with generate"),
+        case::verbose_on_generate("manifest generate -v", "the --verbose option is meaningful only with audit"),
+        case::bad_algorithm("manifest generate -a md5", "unknown algorithm 'md5'; choose one of: sha256, sha384, sha512, sha1"),
+    )]
+    fn test_parse_args_errors(line: &str, expected: &str) {
+        assert_eq!(parse_args(&args(line)), Err(expected.to_string()));
+    }
+}
@@ -0,0 +1,156 @@
+// Reading a file by mapping it into memory, which spares copying every read into a buffer
+// on the way to the compression function. Unix only; the calls needed are declared here
+// rather than pulling in a crate for them. Whatever can't be mapped (a pipe, a file on
+// some other platform, or one too large for the address space) is read as before.
+//
+// A mapped file that is truncated by someone else while it is being hashed gets the process
+// a SIGBUS where a read would merely have come up short. That is why small files, where
+// mapping gains nothing, and files being watched for writes are read rather than mapped.
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io;
+
+use crate::{read_all, READ_BUFFER_SIZE};
+
+// Files smaller than this are read: setting up and tearing down a mapping costs more than
+// copying a few buffers' worth.
+pub const MAP_THRESHOLD: u64 = 1 << 20;
+
+#[cfg(unix)]
+mod sys {
+    use std::fs::File;
+    use std::os::raw::{c_int, c_void};
+    use std::os::unix::io::AsRawFd;
+    use std::ptr;
+
+    const PROT_READ: c_int = 1;
+    const MAP_PRIVATE: c_int = 2;
+    const MADV_SEQUENTIAL: c_int = 2;
+
+    extern "C" {
+        fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: i64) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+        fn madvise(addr: *mut c_void, len: usize, advice: c_int) -> c_int;
+    }
+
+    pub fn map(file: &File, len: usize) -> Option<*mut c_void> {
+        let addr = unsafe { mmap(ptr::null_mut(), len, PROT_READ, MAP_PRIVATE, file.as_raw_fd(), 0) };
+        // MAP_FAILED
+        if addr as isize == -1 {
+            return None;
+        }
+        // only a hint, for more read ahead; it doesn't matter if it is not taken
+        unsafe { madvise(addr, len, MADV_SEQUENTIAL) };
+        Some(addr)
+    }
+
+    pub fn unmap(addr: *mut c_void, len: usize) {
+        unsafe { munmap(addr, len) };
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    use std::fs::File;
+    use std::os::raw::c_void;
+
+    pub fn map(_file: &File, _len: usize) -> Option<*mut c_void> {
+        None
+    }
+
+    pub fn unmap(_addr: *mut c_void, _len: usize) {
+        unreachable!("nothing is ever mapped")
+    }
+}
+
+pub struct Mapping {
+    addr: *mut std::os::raw::c_void,
+    len: usize,
+}
+
+impl Mapping {
+    // The whole of file, if it is a regular file that can be mapped; an empty file can't.
+    pub fn new(file: &File) -> Option<Mapping> {
+        let metadata = file.metadata().ok()?;
+        if !metadata.is_file() || metadata.len() == 0 {
+            return None;
+        }
+        let len = usize::try_from(metadata.len()).ok()?;
+        sys::map(file, len).map(|addr| Mapping{addr, len})
+    }
+
+    pub fn data(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.addr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        sys::unmap(self.addr, self.len);
+    }
+}
+
+// Like read_all, for a file: hands all of it to update, READ_BUFFER_SIZE bytes at a time, so
+// that progress is told as often either way, and returns the number of bytes. Large regular
+// files are mapped, the rest read.
+pub fn read_file<F: FnMut(&[u8])>(file: &File, mut update: F) -> io::Result<u64> {
+    let mapping = match file.metadata() {
+        Ok(metadata) if metadata.len() >= MAP_THRESHOLD => Mapping::new(file),
+        _ => None,
+    };
+    match mapping {
+        Some(mapping) => {
+            mapping.data().chunks(READ_BUFFER_SIZE).for_each(&mut update);
+            Ok(mapping.len as u64)
+        }
+        None => read_all(file, update),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn pieces(file: &File) -> (Vec<u8>, Vec<usize>, u64) {
+        let mut data = Vec::new();
+        let mut lengths = Vec::new();
+        let size = read_file(file, |piece| {
+            data.extend_from_slice(piece);
+            lengths.push(piece.len());
+        }).unwrap();
+        (data, lengths, size)
+    }
+
+    #[test]
+    fn test_read_file() {
+        let dir = TempDir::new("mmap-read");
+        let data: Vec<u8> = (0..MAP_THRESHOLD as u32 + 3 * READ_BUFFER_SIZE as u32 + 10).map(|i| ((i * 7) >> 2) as u8).collect();
+
+        // mapped
+        let large = File::open(dir.write("large", &data)).unwrap();
+        assert!(Mapping::new(&large).is_some());
+        let (read, lengths, size) = pieces(&large);
+        assert_eq!((read, size), (data.clone(), data.len() as u64));
+        assert!(lengths[..lengths.len() - 1].iter().all(|&len| len == READ_BUFFER_SIZE));
+
+        // read
+        let small = File::open(dir.write("small", &data[..1000])).unwrap();
+        assert_eq!(pieces(&small).0, &data[..1000]);
+
+        let empty = File::open(dir.write("empty", b"")).unwrap();
+        assert!(Mapping::new(&empty).is_none());
+        assert_eq!(pieces(&empty).2, 0);
+    }
+
+    // a directory can't be mapped, and reading it fails, as it would have without mapping
+    #[cfg(unix)]
+    #[test]
+    fn test_read_file_directory() {
+        let dir = TempDir::new("mmap-dir");
+        let file = File::open(dir.path()).unwrap();
+        assert!(Mapping::new(&file).is_none());
+        assert!(read_file(&file, |_| ()).is_err());
+    }
+}
@@ -80,18 +80,18 @@ pub struct ShaPaddedStream<I> where I: IntoIterator<Item = InputItemType> {
 fn len_in_bits_encoded_as_bytes(len: ByteCounter, length_size: LengthSize) -> Vec<u8> {
     let bit_len = len * 8;
     match length_size {
-        LengthSize::Len128 => return bit_len.to_be_bytes().to_vec(),
+        LengthSize::Len128 => bit_len.to_be_bytes().to_vec(),
         LengthSize::Len64 => {
             if bit_len > u64::MAX as u128 {
                 panic!("Message is larger than the format allows")
             } else {
-                return (bit_len as u64).to_be_bytes().to_vec()
+                (bit_len as u64).to_be_bytes().to_vec()
             }
         },
     }
 }
 
-impl<'a, I> ShaPaddedStream<I> where I: IntoIterator<Item = InputItemType> {
+impl<I> ShaPaddedStream<I> where I: IntoIterator<Item = InputItemType> {
     pub fn new(blockstream: I, block_length: usize, length_size: LengthSize) -> ShaPaddedStream<I> {
         let padder = Padder::new(block_length, length_size);
         ShaPaddedStream{block_iter: blockstream.into_iter(), padder, length_in_bytes:0, padding_started: false, done: false}
@@ -99,7 +99,7 @@ impl<'a, I> ShaPaddedStream<I> where I: IntoIterator<Item = InputItemType> {
 
 }
 
-impl<'a, I> Iterator for ShaPaddedStream<I> where I: IntoIterator<Item = InputItemType> {
+impl<I> Iterator for ShaPaddedStream<I> where I: IntoIterator<Item = InputItemType> {
     type Item = OutputItemType;
 
     fn next(&mut self) -> Option<OutputItemType> {
@@ -108,7 +108,7 @@ impl<'a, I> Iterator for ShaPaddedStream<I> where I: IntoIterator<Item = InputIt
                 self.length_in_bytes += u128::try_from(block.len()).unwrap(); //usize is platformdependant
 
                 if self.padder.is_full_block(block.len()) {
-                    return Some(block);
+                    Some(block)
                 } else {
                     let result;
 
@@ -120,18 +120,18 @@ impl<'a, I> Iterator for ShaPaddedStream<I> where I: IntoIterator<Item = InputIt
                         self.padding_started = true;
                     }
 
-                    return Some(result);
+                    Some(result)
                 }
             }
             None => {
                 if self.done {
-                    return None
+                    None
                 } else {
                     self.done = true;
                     if self.padding_started {
-                        return Some(self.padder.double_pad_2nd_part(self.length_in_bytes));
+                        Some(self.padder.double_pad_2nd_part(self.length_in_bytes))
                     } else {
-                        return Some(self.padder.single_pad(&[], self.length_in_bytes))
+                        Some(self.padder.single_pad(&[], self.length_in_bytes))
                     }
                 }
             }
@@ -239,7 +239,7 @@ mod tests {
         let blockstream = block_splitter::BlockStream::new(blocksize, input.into_iter());
         let result = ShaPaddedStream::new(blockstream, blocksize, LengthSize::Len64).collect::<Vec<_>>();
 
-        let hexstr = result.iter().map(|v| hex::encode(v)).collect::<Vec<_>>();
+        let hexstr = result.iter().map(hex::encode).collect::<Vec<_>>();
         assert_eq!(hexstr, expected);
     }
 
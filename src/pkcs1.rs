@@ -0,0 +1,524 @@
+// The byte string parts of PKCS #1 (RFC 8017): MGF1, EME-OAEP, EMSA-PSS and EMSA-PKCS1-v1_5.
+//
+// None of this touches the RSA primitive itself; an encoded message (EM) is handed to, or
+// received from, whatever big-integer implementation does the modular exponentiation.
+// Randomness (the OAEP seed and the PSS salt) is supplied by the caller.
+
+use std::convert::TryFrom;
+
+use crate::sha::{sha, ShaParams};
+
+// DER encoded DigestInfo prefixes for EMSA-PKCS1-v1_5 (RFC 8017, section 9.2, note 1)
+pub const DIGEST_INFO_SHA256: [u8;19] = [
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05, 0x00, 0x04, 0x20
+];
+pub const DIGEST_INFO_SHA512: [u8;19] = [
+    0x30, 0x51, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03, 0x05, 0x00, 0x04, 0x40
+];
+
+#[derive(Debug, PartialEq)]
+pub enum Pkcs1Error {
+    MaskTooLong,
+    MessageTooLong,
+    EncodingError,
+    Inconsistent,
+    DecryptionError,
+    IntendedEncodedMessageLengthTooShort,
+    WrongSeedLength,
+}
+
+fn hash<T: Copy>(params: ShaParams<T>, parts: &[&[u8]]) -> Vec<u8> {
+    sha(params, parts.iter().flat_map(|p| p.iter().cloned()))
+}
+
+fn xor_into(target: &mut [u8], mask: &[u8]) {
+    target.iter_mut().zip(mask).for_each(|(t, m)| *t ^= m);
+}
+
+pub fn mgf1<T: Copy>(params: ShaParams<T>, seed: &[u8], mask_len: usize) -> Result<Vec<u8>, Pkcs1Error> {
+    let blocks = mask_len.div_ceil(params.digest_size);
+    if u64::try_from(blocks).unwrap() > 1u64 << 32 {
+        return Err(Pkcs1Error::MaskTooLong);
+    }
+
+    let mut mask = Vec::with_capacity(blocks * params.digest_size);
+    for counter in 0..blocks {
+        let c = (counter as u32).to_be_bytes();
+        mask.extend(hash(params, &[seed, &c]));
+    }
+    mask.truncate(mask_len);
+
+    Ok(mask)
+}
+
+// k is the length of the RSA modulus in bytes; seed must be digest_size random bytes.
+pub fn eme_oaep_encode<T: Copy>(params: ShaParams<T>, k: usize, msg: &[u8], label: &[u8], seed: &[u8]) -> Result<Vec<u8>, Pkcs1Error> {
+    let h_len = params.digest_size;
+    if k < 2 * h_len + 2 || msg.len() > k - 2 * h_len - 2 {
+        return Err(Pkcs1Error::MessageTooLong);
+    }
+    if seed.len() != h_len {
+        return Err(Pkcs1Error::WrongSeedLength);
+    }
+
+    // DB = lHash || PS || 0x01 || M
+    let mut db = hash(params, &[label]);
+    db.resize(k - msg.len() - h_len - 2, 0);
+    db.push(0x01);
+    db.extend_from_slice(msg);
+
+    xor_into(&mut db, &mgf1(params, seed, k - h_len - 1)?);
+    let mut masked_seed = seed.to_vec();
+    xor_into(&mut masked_seed, &mgf1(params, &db, h_len)?);
+
+    let mut em = Vec::with_capacity(k);
+    em.push(0x00);
+    em.append(&mut masked_seed);
+    em.append(&mut db);
+
+    Ok(em)
+}
+
+// All failures are reported as the same DecryptionError, and the padding checks do not
+// branch on the data, so as not to become a Manger style oracle.
+pub fn eme_oaep_decode<T: Copy>(params: ShaParams<T>, k: usize, em: &[u8], label: &[u8]) -> Result<Vec<u8>, Pkcs1Error> {
+    let h_len = params.digest_size;
+    if k < 2 * h_len + 2 || em.len() != k {
+        return Err(Pkcs1Error::DecryptionError);
+    }
+
+    let l_hash = hash(params, &[label]);
+    let mut seed = em[1..=h_len].to_vec();
+    let mut db = em[h_len+1..].to_vec();
+    xor_into(&mut seed, &mgf1(params, &db, h_len)?);
+    xor_into(&mut db, &mgf1(params, &seed, k - h_len - 1)?);
+
+    let mut bad = em[0];
+    for (a, b) in l_hash.iter().zip(&db[..h_len]) {
+        bad |= a ^ b;
+    }
+
+    // find the 0x01 separating PS from M, remembering whether anything but zeros came before it
+    let mut found = 0u8;
+    let mut msg_start = 0usize;
+    for (i, &b) in db[h_len..].iter().enumerate() {
+        let is_one = (b == 0x01) as u8;
+        let is_zero = (b == 0) as u8;
+        let first_one = is_one & !found & 1;
+        msg_start |= (h_len + i + 1) * first_one as usize;
+        bad |= !found & 1 & !is_one & !is_zero;
+        found |= is_one;
+    }
+    bad |= !found & 1;
+
+    if bad != 0 {
+        return Err(Pkcs1Error::DecryptionError);
+    }
+
+    Ok(db[msg_start..].to_vec())
+}
+
+fn pss_prefix_hash<T: Copy>(params: ShaParams<T>, m_hash: &[u8], salt: &[u8]) -> Vec<u8> {
+    hash(params, &[&[0u8;8], m_hash, salt])
+}
+
+// em_bits is the bit length of the modulus minus one; salt is typically digest_size random bytes.
+pub fn emsa_pss_encode<T: Copy>(params: ShaParams<T>, msg: &[u8], salt: &[u8], em_bits: usize) -> Result<Vec<u8>, Pkcs1Error> {
+    let h_len = params.digest_size;
+    let em_len = em_bits.div_ceil(8);
+    if em_len < h_len + salt.len() + 2 {
+        return Err(Pkcs1Error::EncodingError);
+    }
+
+    let m_hash = hash(params, &[msg]);
+    let h = pss_prefix_hash(params, &m_hash, salt);
+
+    // DB = PS || 0x01 || salt
+    let mut db = vec![0u8; em_len - salt.len() - h_len - 2];
+    db.push(0x01);
+    db.extend_from_slice(salt);
+    xor_into(&mut db, &mgf1(params, &h, em_len - h_len - 1)?);
+    db[0] &= 0xff >> (8 * em_len - em_bits);
+
+    let mut em = db;
+    em.extend(h);
+    em.push(0xbc);
+
+    Ok(em)
+}
+
+pub fn emsa_pss_verify<T: Copy>(params: ShaParams<T>, msg: &[u8], em: &[u8], em_bits: usize, salt_len: usize) -> Result<(), Pkcs1Error> {
+    let h_len = params.digest_size;
+    let em_len = em_bits.div_ceil(8);
+    if em.len() != em_len || em_len < h_len + salt_len + 2 {
+        return Err(Pkcs1Error::Inconsistent);
+    }
+    if em[em_len-1] != 0xbc {
+        return Err(Pkcs1Error::Inconsistent);
+    }
+
+    let unused_bits_mask = !(0xffu8 >> (8 * em_len - em_bits));
+    let (masked_db, h) = em[..em_len-1].split_at(em_len - h_len - 1);
+    if masked_db[0] & unused_bits_mask != 0 {
+        return Err(Pkcs1Error::Inconsistent);
+    }
+
+    let mut db = masked_db.to_vec();
+    xor_into(&mut db, &mgf1(params, h, em_len - h_len - 1)?);
+    db[0] &= !unused_bits_mask;
+
+    let ps_len = em_len - h_len - salt_len - 2;
+    if db[..ps_len].iter().any(|&b| b != 0) || db[ps_len] != 0x01 {
+        return Err(Pkcs1Error::Inconsistent);
+    }
+
+    let salt = &db[db.len()-salt_len..];
+    let m_hash = hash(params, &[msg]);
+    if pss_prefix_hash(params, &m_hash, salt) != h {
+        return Err(Pkcs1Error::Inconsistent);
+    }
+
+    Ok(())
+}
+
+// EM = 0x00 || 0x01 || PS || 0x00 || DigestInfo, where digest_info is one of the DIGEST_INFO_ prefixes matching params.
+pub fn emsa_pkcs1_v15_encode<T: Copy>(params: ShaParams<T>, digest_info: &[u8], msg: &[u8], em_len: usize) -> Result<Vec<u8>, Pkcs1Error> {
+    let t_len = digest_info.len() + params.digest_size;
+    if em_len < t_len + 11 {
+        return Err(Pkcs1Error::IntendedEncodedMessageLengthTooShort);
+    }
+
+    let mut em = vec![0x00, 0x01];
+    em.resize(em_len - t_len - 1, 0xff);
+    em.push(0x00);
+    em.extend_from_slice(digest_info);
+    em.extend(hash(params, &[msg]));
+
+    Ok(em)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha::{SHA1, SHA256, SHA512};
+
+    extern crate hex;
+    extern crate rstest;
+    use rstest::rstest;
+
+    // The encoded messages below were produced by OpenSSL: a message was OAEP encrypted / PSS signed
+    // with a fresh RSA key, and the raw RSA operation undone to expose EM. Seed and salt were
+    // recovered from EM, so that encoding can be checked byte for byte as well.
+    const MSG: &str = "74686520717569636b2062726f776e20666f78";
+
+    const OAEP_SHA256_SEED: &str = "0fa4a945de1392bb500e881f2ac97b2da37058a06cd32f99b06b20dc7c315d81";
+    const OAEP_SHA256_EM: &str = "006b918c12a6a86110f9d17eaeaeac70398c5a8b060af913e80fe0530d16cc866dd1a6e1f089e684a111f32a7545f6950527a167bcc61bd68dff8aee52004d6ac9253f542a834b6787c10b4dd33276ef39f30e5017d2b52b4e516d5d92e599ec8e33124e0b486d4e529c622d0e4168aca2265d44e2bfc6cf1ef65e431204c692";
+
+    const OAEP_SHA512_SEED: &str = "086a053fe7d029cc1dcba915d0c73a622f53056111f6c28b5a8ef70c596321d0b0d199b305907a787b522042bf3635a9fe5d81c12d47fb035e7581183cd45570";
+    const OAEP_SHA512_EM: &str = "004c6038862b3e0ed5984aa8965067ab2ff20e39e0859258f040ea760ff93b050865237fc252e0b0d29f261c5e312cf90dfb220c9ad7e96f6c18240dfb03b1dad40861a71a1518d0ecff00f4134681478ba15cce9e531682c145c78e84f0438c8349ed360fe053c66323ad650182dd1134db9b76c291283b3fa3bd65c9ea5f3b9f532a1aeb2bcd0bb9cafab8bef8f0a2aa44ab6ece2d64b205747c6d911e4bfa80c650dade68f286c04ed1f5b3faa37750bf219b32864765fe570ddc778630cbf645ef18e10a04ec57d8720d0231ec90c1439131a140995c02c4816035566bc17a6b6c57ec468dc301b910de2132db63a31ac23684c407481700da6984d53ee1";
+
+    const PSS_SHA256_SALT: &str = "b3263ead49dd3f223439f86ce705181e42a42b19fc5632f066a8fa20c9637131";
+    const PSS_SHA256_EM: &str = "17f6ab18534bfa4f6321b05edf99debd47b4b0427efcbaf8957fee79a723a1df166734d0c173610fbc52b12b95f77f2d55d77d1ce78e438f94d32d09a3528872f0b2d2a36c777ab49cd8a978a26df304cccffa9c2233be0f1838afd7b77723b924f7fd2175492228afa839ce96b1ae3aca4c9b5682dc5a6a243d6b2093b656bc";
+
+    const PSS_SHA512_SALT: &str = "2ba8279bce5e1e9bb18eb5794564ce26dbf80b7f0a69b990cde5c6b746c1b0f946dd61a8c474ad1219ee9fe53433d68a08cf98bb441b10ab790bc0855ce8171c";
+    const PSS_SHA512_EM: &str = "3fb23c3e5434bef9ba2c7b6348da7faa097eff110911ed7fe66ed5545f361c1df8d38af22afe16eaadc852dde06c2948bcdd10a3721cedd4b9922a928e11fdbd1a2de2c9e086bfdd0e2324c0a59a61761aed4082c68c1063aac45dc5aabb8858437327e6c0d5191c7fc7cf686c5508b07426fc3970f628d6c89b4c7ea6bfd64a7d231f98e265df5a3f45913723c5db227afa31a7b640ce037916b6bdf7af7b078f61903ebc8447a44c8865eb8aa9e2ba8df9e1b6ea0422d42a6867d50a0801c1e232c0b9ce2c006c9e630cec534292d01da90bb2928fb2940db850ee76db72896ea9f2575fe52937fc73a7936f47ee1e95d5e5e283dbf4c614a99295d2ae2ebc";
+
+    // RSAES-OAEP Example 1.1 of the RSA Laboratories vectors that come with RFC 8017 (SHA-1,
+    // a 1024 bit modulus and e = 65537).
+    const RFC8017_N: &str = "a8b3b284af8eb50b387034a860f146c4919f318763cd6c5598c8ae4811a1e0abc4c7e0b082d693a5e7fced675cf4668512772c0cbc64a742c6c630f533c8cc72f62ae833c40bf25842e984bb78bdbf97c0107d55bdb662f5c4e0fab9845cb5148ef7392dd3aaff93ae1e6b667bb3d4247616d4f5ba10d4cfd226de88d39f16fb";
+    const RFC8017_OAEP_MSG: &str = "6628194e12073db03ba94cda9ef9532397d50dba79b987004afefe34";
+    const RFC8017_OAEP_SEED: &str = "18b776ea21069d69776a33e96bad48e1dda0a5ef";
+    const RFC8017_OAEP_C: &str = "354fe67b4a126d5d35fe36c777791a3f7ba13def484e2d3908aff722fad468fb21696de95d0be911c2d3174f8afcc201035f7b6d8e69402de5451618c21a535fa9d7bfc5b8dd9fc243f8cf927db31322d6e881eaa91a996170e657a05a266426d98c88003f8477c1227094a0d9fa1e8c4024309ce1ecccb5210035d47ac72e8a";
+
+    // The first SHA-256 case of SigGenPSS_186-3.txt from the NIST CAVP RSA test vectors
+    // (FIPS 186-4): a 3072 bit modulus with e = 0x1415a7.
+    const NIST_PSS_N: &str = "a7a1882a7fb896786034d07fb1b9f6327c27bdd7ce6fe39c285ae3b6c34259adc0dc4f7b9c7dec3ca4a20d3407339eedd7a12a421da18f5954673cac2ff059156ecc73c6861ec761e6a0f2a5a033a6768c6a42d8b459e1b4932349e84efd92df59b45935f3d0e30817c66201aa99d07ae36c5d74f408d69cc08f044151ff4960e531360cb19077833adf7bce77ecfaa133c0ccc63c93b856814569e0b9884ee554061b9a20ab46c38263c094dae791aa61a17f8d16f0e85b7e5ce3b067ece89e20bc4e8f1ae814b276d234e04f4e766f501da74ea7e3817c24ea35d016676cece652b823b051625573ca92757fc720d254ecf1dcbbfd21d98307561ecaab545480c7c52ad7e9fa6b597f5fe550559c2fe923205ac1761a99737ca02d7b19822e008a8969349c87fb874c81620e38f613c8521f0381fe5ba55b74827dad3e1cf2aa29c6933629f2b286ad11be88fa6436e7e3f64a75e3595290dc0d1cd5eee7aaac54959cc53bd5a934a365e72dd81a2bd4fb9a67821bffedf2ef2bd94913de8b";
+    const NIST_PSS_E: u32 = 0x1415a7;
+    const NIST_PSS_MSG: &str = "c16499110ed577202aed2d3e4d51ded6c66373faef6533a860e1934c63484f87a8d9b92f3ac45197b2909710abba1daf759fe0510e9bd8dd4d73cec961f06ee07acd9d42c6d40dac9f430ef90374a7e944bde5220096737454f96b614d0f6cdd9f08ed529a4ad0e759cf3a023dc8a30b9a872974af9b2af6dc3d111d0feb7006";
+    const NIST_PSS_SALT: &str = "3e07ade72a3f52530f53135a5d7d93217435ba001ea55a8f5d5d1304684874bc";
+    const NIST_PSS_S: &str = "4335707da735cfd10411c9c048ca9b60bb46e2fe361e51fbe336f9508dc945afe075503d24f836610f2178996b52c411693052d5d7aed97654a40074ed20ed6689c0501b7fbac21dc46b665ac079760086414406cd66f8537d1ebf0dce4cf0c98d4c30c71da359e9cd401ff49718fdd4d0f99efe70ad8dd8ba1304cefb88f24b0eedf70116da15932c76f0069551a245b5fc3b91ec101f1d63b9853b598c6fa1c1acdbacf9626356c760119be0955644301896d9d0d3ea5e6443cb72ca29f4d45246d16d74d00568c219182feb191179e4593dc152c608fd80536329a533b3a631566814cd654f587c2d8ce696085e6ed1b0b0278e60a049ec7a399f94fccae6462371a69695ef525e00936fa7d9781f9ee289d4105ee827a27996583033cedb2f297e7b4926d906ce0d09d84128406ab33d7da0f8a1d4d2f666568686c394d139b0e5e99337758de85910a5fa25ca2aa6d8fb1c777244e7d98de4c79bbd426a5e6f657e37477e01247432f83797fbf31b50d02b83f69ded26d4945b2bc3f86e";
+
+    fn unhex(s: &str) -> Vec<u8> {
+        hex::decode(s).unwrap()
+    }
+
+    // Just enough RSA to take the published vectors from ciphertext or signature to EM:
+    // x^e mod n on big endian byte strings, multiplying by doubling and adding. Slow, but short.
+    fn rsa_public(n: &[u8], e: u32, x: &[u8]) -> Vec<u8> {
+        // little endian 32 bit limbs, with a spare one for the carry of an addition
+        let limbs = |bytes: &[u8]| {
+            let mut v: Vec<u64> = bytes.rchunks(4).map(|c| c.iter().fold(0, |acc, &b| acc << 8 | b as u64)).collect();
+            v.resize(n.len().div_ceil(4) + 1, 0);
+            v
+        };
+        let n_limbs = limbs(n);
+        let add_mod = |a: &mut Vec<u64>, b: &[u64]| {
+            let mut carry = 0;
+            for (x, y) in a.iter_mut().zip(b) {
+                let sum = *x + y + carry;
+                *x = sum & 0xffff_ffff;
+                carry = sum >> 32;
+            }
+            if a.iter().rev().cmp(n_limbs.iter().rev()) != std::cmp::Ordering::Less {
+                let mut borrow = 0;
+                for (x, y) in a.iter_mut().zip(&n_limbs) {
+                    let diff = (*x | 1 << 32) - y - borrow;
+                    *x = diff & 0xffff_ffff;
+                    borrow = 1 - (diff >> 32);
+                }
+            }
+        };
+        let mul_mod = |a: &[u64], b: &[u64]| {
+            let mut r = vec![0u64; a.len()];
+            for bit in (0..32 * b.len()).rev() {
+                let doubled = r.clone();
+                add_mod(&mut r, &doubled);
+                if b[bit / 32] >> (bit % 32) & 1 == 1 {
+                    add_mod(&mut r, a);
+                }
+            }
+            r
+        };
+
+        let x = limbs(x);
+        let mut r = limbs(&[1]);
+        for bit in (0..32).rev() {
+            r = mul_mod(&r, &r);
+            if e >> bit & 1 == 1 {
+                r = mul_mod(&r, &x);
+            }
+        }
+        r.iter().rev().skip(1).flat_map(|&w| (w as u32).to_be_bytes()).skip(4 * (n.len().div_ceil(4)) - n.len()).collect()
+    }
+
+    #[test]
+    fn test_mgf1_sha256() {
+        let mask = mgf1(SHA256, b"foo", 50).unwrap();
+        assert_eq!(hex::encode(mask), "3bdaba83cff13337b323ac383ca3995863e922f511b931b9efd4e0118cfc70f08678390d67e3c12dbeb2d7a78bdfa597b5a3");
+    }
+
+    #[test]
+    fn test_mgf1_sha512() {
+        let mask = mgf1(SHA512, b"foo", 50).unwrap();
+        assert_eq!(hex::encode(mask), "e8ac84d032cd89026d0654d269a810e61c81fc8f978e8b22b1556897463fe36c86955dfee5dc87f38c5e0c4b6faae03da5c7");
+    }
+
+    #[test]
+    fn test_oaep_rfc8017_example() {
+        let n = unhex(RFC8017_N);
+        let em = eme_oaep_encode(SHA1, 128, &unhex(RFC8017_OAEP_MSG), b"", &unhex(RFC8017_OAEP_SEED)).unwrap();
+        assert_eq!(hex::encode(rsa_public(&n, 65537, &em)), RFC8017_OAEP_C);
+        assert_eq!(hex::encode(eme_oaep_decode(SHA1, 128, &em, b"").unwrap()), RFC8017_OAEP_MSG);
+    }
+
+    #[test]
+    fn test_oaep_sha256_encode() {
+        let em = eme_oaep_encode(SHA256, 128, &unhex(MSG), b"", &unhex(OAEP_SHA256_SEED)).unwrap();
+        assert_eq!(hex::encode(em), OAEP_SHA256_EM);
+    }
+
+    #[test]
+    fn test_oaep_sha256_decode() {
+        let msg = eme_oaep_decode(SHA256, 128, &unhex(OAEP_SHA256_EM), b"").unwrap();
+        assert_eq!(hex::encode(msg), MSG);
+    }
+
+    #[test]
+    fn test_oaep_sha512_with_label() {
+        let em = eme_oaep_encode(SHA512, 256, &unhex(MSG), b"label", &unhex(OAEP_SHA512_SEED)).unwrap();
+        assert_eq!(hex::encode(&em), OAEP_SHA512_EM);
+        assert_eq!(hex::encode(eme_oaep_decode(SHA512, 256, &em, b"label").unwrap()), MSG);
+    }
+
+    #[test]
+    fn test_oaep_wrong_label() {
+        assert_eq!(eme_oaep_decode(SHA512, 256, &unhex(OAEP_SHA512_EM), b""), Err(Pkcs1Error::DecryptionError));
+    }
+
+    #[test]
+    fn test_oaep_nonzero_leading_byte() {
+        let mut em = unhex(OAEP_SHA256_EM);
+        em[0] = 1;
+        assert_eq!(eme_oaep_decode(SHA256, 128, &em, b""), Err(Pkcs1Error::DecryptionError));
+    }
+
+    #[test]
+    fn test_oaep_corrupted_db() {
+        let mut em = unhex(OAEP_SHA256_EM);
+        em[100] ^= 0x40;
+        assert_eq!(eme_oaep_decode(SHA256, 128, &em, b""), Err(Pkcs1Error::DecryptionError));
+    }
+
+    #[test]
+    fn test_oaep_message_too_long() {
+        let seed = unhex(OAEP_SHA256_SEED);
+        assert!(eme_oaep_encode(SHA256, 128, &[0u8;62], b"", &seed).is_ok());
+        assert_eq!(eme_oaep_encode(SHA256, 128, &[0u8;63], b"", &seed), Err(Pkcs1Error::MessageTooLong));
+    }
+
+    #[test]
+    fn test_oaep_wrong_seed_length() {
+        let seed = unhex(OAEP_SHA256_SEED);
+        assert_eq!(eme_oaep_encode(SHA256, 128, &unhex(MSG), b"", &seed[1..]), Err(Pkcs1Error::WrongSeedLength));
+        assert_eq!(eme_oaep_encode(SHA512, 256, &unhex(MSG), b"", &seed), Err(Pkcs1Error::WrongSeedLength));
+    }
+
+    #[test]
+    fn test_oaep_empty_message_roundtrip() {
+        let seed = unhex(OAEP_SHA256_SEED);
+        let em = eme_oaep_encode(SHA256, 128, &[], b"", &seed).unwrap();
+        assert_eq!(eme_oaep_decode(SHA256, 128, &em, b"").unwrap(), Vec::<u8>::new());
+    }
+
+    // EM put together by hand from a DB that is well formed but for what the case changes, so
+    // that each check of the decoding is reached on its own rather than by a random bit flip.
+    fn oaep_em(db: &[u8], seed: &[u8]) -> Vec<u8> {
+        let mut masked_db = db.to_vec();
+        xor_into(&mut masked_db, &mgf1(SHA256, seed, db.len()).unwrap());
+        let mut masked_seed = seed.to_vec();
+        xor_into(&mut masked_seed, &mgf1(SHA256, &masked_db, seed.len()).unwrap());
+        [&[0u8][..], &masked_seed, &masked_db].concat()
+    }
+
+    // DB = lHash || PS || 0x01 || M for k = 128, with the given change applied
+    fn oaep_db(change: fn(&mut Vec<u8>)) -> Vec<u8> {
+        let mut db = sha(SHA256, Vec::new());
+        db.resize(127 - 32 - 3 - 1, 0);
+        db.push(0x01);
+        db.extend_from_slice(b"abc");
+        change(&mut db);
+        db
+    }
+
+    #[rstest(change, valid,
+        case::well_formed(|_: &mut Vec<u8>| (), true),
+        case::wrong_label_hash(|db: &mut Vec<u8>| db[31] ^= 1, false),
+        case::nonzero_padding(|db: &mut Vec<u8>| db[40] = 0x02, false),
+        case::padding_ends_in_two(|db: &mut Vec<u8>| db[90] = 0x02, false),
+        case::no_separator(|db: &mut Vec<u8>| db.iter_mut().skip(32).for_each(|b| *b = 0), false),
+        case::empty_message(|db: &mut Vec<u8>| { db[91..94].fill(0); db[94] = 0x01 }, true),
+        case::separator_right_after_hash(|db: &mut Vec<u8>| db[32] = 0x01, true),
+    )]
+    fn test_oaep_decode_padding(change: fn(&mut Vec<u8>), valid: bool) {
+        let db = oaep_db(change);
+        let em = oaep_em(&db, &unhex(OAEP_SHA256_SEED));
+        let expected = db.iter().skip(32).position(|&b| b == 0x01).map(|i| db[33 + i..].to_vec());
+        assert_eq!(eme_oaep_decode(SHA256, 128, &em, b"").ok(), if valid { expected } else { None });
+    }
+
+    #[test]
+    fn test_oaep_wrong_length() {
+        let em = unhex(OAEP_SHA256_EM);
+        assert_eq!(eme_oaep_decode(SHA256, 128, &em[1..], b""), Err(Pkcs1Error::DecryptionError));
+        assert_eq!(eme_oaep_decode(SHA256, 129, &[&[0u8][..], &em].concat(), b""), Err(Pkcs1Error::DecryptionError));
+        // too short a key for the hash leaves no room for DB
+        assert_eq!(eme_oaep_decode(SHA512, 129, &[0u8;129], b""), Err(Pkcs1Error::DecryptionError));
+    }
+
+    #[test]
+    fn test_pss_nist_vector() {
+        let em = rsa_public(&unhex(NIST_PSS_N), NIST_PSS_E, &unhex(NIST_PSS_S));
+        assert_eq!(hex::encode(emsa_pss_encode(SHA256, &unhex(NIST_PSS_MSG), &unhex(NIST_PSS_SALT), 3071).unwrap()), hex::encode(&em));
+        assert_eq!(emsa_pss_verify(SHA256, &unhex(NIST_PSS_MSG), &em, 3071, 32), Ok(()));
+    }
+
+    #[test]
+    fn test_pss_sha256_encode() {
+        let em = emsa_pss_encode(SHA256, &unhex(MSG), &unhex(PSS_SHA256_SALT), 1023).unwrap();
+        assert_eq!(hex::encode(em), PSS_SHA256_EM);
+    }
+
+    #[test]
+    fn test_pss_sha256_verify() {
+        assert_eq!(emsa_pss_verify(SHA256, &unhex(MSG), &unhex(PSS_SHA256_EM), 1023, 32), Ok(()));
+    }
+
+    #[test]
+    fn test_pss_sha512() {
+        let em = emsa_pss_encode(SHA512, &unhex(MSG), &unhex(PSS_SHA512_SALT), 2047).unwrap();
+        assert_eq!(hex::encode(&em), PSS_SHA512_EM);
+        assert_eq!(emsa_pss_verify(SHA512, &unhex(MSG), &em, 2047, 64), Ok(()));
+    }
+
+    #[test]
+    fn test_pss_wrong_message() {
+        assert_eq!(emsa_pss_verify(SHA256, b"the quick brown fix", &unhex(PSS_SHA256_EM), 1023, 32), Err(Pkcs1Error::Inconsistent));
+    }
+
+    #[test]
+    fn test_pss_wrong_salt_length() {
+        assert_eq!(emsa_pss_verify(SHA256, &unhex(MSG), &unhex(PSS_SHA256_EM), 1023, 20), Err(Pkcs1Error::Inconsistent));
+    }
+
+    #[test]
+    fn test_pss_bad_trailer() {
+        let mut em = unhex(PSS_SHA256_EM);
+        em[127] = 0xbb;
+        assert_eq!(emsa_pss_verify(SHA256, &unhex(MSG), &em, 1023, 32), Err(Pkcs1Error::Inconsistent));
+    }
+
+    #[test]
+    fn test_pss_leftmost_bit_set() {
+        let mut em = unhex(PSS_SHA256_EM);
+        em[0] |= 0x80;
+        assert_eq!(emsa_pss_verify(SHA256, &unhex(MSG), &em, 1023, 32), Err(Pkcs1Error::Inconsistent));
+    }
+
+    // EM for "abc" with a 1023 bit modulus, H made over the salt at the end of DB, and DB
+    // otherwise as the case makes it.
+    fn pss_em(change: fn(&mut Vec<u8>)) -> Vec<u8> {
+        let salt = unhex(PSS_SHA256_SALT);
+        let h = pss_prefix_hash(SHA256, &sha(SHA256, b"abc".to_vec()), &salt);
+        let mut db = vec![0u8; 128 - 32 - 32 - 2];
+        db.push(0x01);
+        db.extend_from_slice(&salt);
+        change(&mut db);
+        xor_into(&mut db, &mgf1(SHA256, &h, 95).unwrap());
+        db[0] &= 0x7f;
+        [db, h, vec![0xbc]].concat()
+    }
+
+    #[rstest(change, valid,
+        case::well_formed(|_: &mut Vec<u8>| (), true),
+        case::nonzero_padding(|db: &mut Vec<u8>| db[10] = 0x01, false),
+        case::separator_missing(|db: &mut Vec<u8>| db[62] = 0x00, false),
+        case::separator_wrong(|db: &mut Vec<u8>| db[62] = 0x02, false),
+        case::separator_early(|db: &mut Vec<u8>| { db[61] = 0x01; db[62] = 0x00 }, false),
+        // the unused top bit is cleared after unmasking, so what DB has there doesn't count
+        case::unused_bit_in_db(|db: &mut Vec<u8>| db[0] = 0x80, true),
+    )]
+    fn test_pss_verify_padding(change: fn(&mut Vec<u8>), valid: bool) {
+        let result = emsa_pss_verify(SHA256, b"abc", &pss_em(change), 1023, 32);
+        assert_eq!(result, if valid { Ok(()) } else { Err(Pkcs1Error::Inconsistent) });
+    }
+
+    #[test]
+    fn test_pss_wrong_length() {
+        let em = unhex(PSS_SHA256_EM);
+        assert_eq!(emsa_pss_verify(SHA256, &unhex(MSG), &em[1..], 1023, 32), Err(Pkcs1Error::Inconsistent));
+        assert_eq!(emsa_pss_verify(SHA256, &unhex(MSG), &em, 1031, 32), Err(Pkcs1Error::Inconsistent));
+        // no room for the salt asked for
+        assert_eq!(emsa_pss_verify(SHA256, &unhex(MSG), &em, 1023, 95), Err(Pkcs1Error::Inconsistent));
+        assert_eq!(emsa_pss_encode(SHA256, &unhex(MSG), &[0u8;95], 1023), Err(Pkcs1Error::EncodingError));
+    }
+
+    #[test]
+    fn test_pss_empty_salt_roundtrip() {
+        let em = emsa_pss_encode(SHA256, b"abc", &[], 1023).unwrap();
+        assert_eq!(emsa_pss_verify(SHA256, b"abc", &em, 1023, 0), Ok(()));
+    }
+
+    #[test]
+    fn test_pkcs1_v15_sha256() {
+        let em = emsa_pkcs1_v15_encode(SHA256, &DIGEST_INFO_SHA256, b"abc", 64).unwrap();
+        assert_eq!(hex::encode(em),
+            "0001ffffffffffffffffffff003031300d060960864801650304020105000420ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    #[test]
+    fn test_pkcs1_v15_too_short() {
+        assert_eq!(emsa_pkcs1_v15_encode(SHA512, &DIGEST_INFO_SHA512, b"abc", 93),
+            Err(Pkcs1Error::IntendedEncodedMessageLengthTooShort));
+        assert!(emsa_pkcs1_v15_encode(SHA512, &DIGEST_INFO_SHA512, b"abc", 94).is_ok());
+    }
+}
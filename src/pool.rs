@@ -0,0 +1,130 @@
+// Hashing many files on several threads, handing out the results in input order.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::sha::ShaParams;
+use crate::sha_sum_input;
+
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pool {
+    jobs: usize,
+}
+
+impl Pool {
+    // jobs is the number of worker threads; 0 means one per CPU.
+    pub fn new(jobs: usize) -> Pool {
+        Pool{jobs: if jobs == 0 { default_jobs() } else { jobs }}
+    }
+
+    pub fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    // Runs work on every item, and emit on every result, in the order of the items. emit runs
+    // on the calling thread, as soon as a result and all results before it are ready.
+    pub fn map_ordered<I, O, W, E>(&self, items: &[I], work: W, mut emit: E)
+        where I: Sync, O: Send, W: Fn(&I) -> O + Sync, E: FnMut(usize, &I, O)
+    {
+        if self.jobs == 1 || items.len() < 2 {
+            for (i, item) in items.iter().enumerate() {
+                emit(i, item, work(item));
+            }
+            return;
+        }
+
+        let next = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|scope| {
+            for _ in 0..self.jobs.min(items.len()) {
+                let sender = sender.clone();
+                let (next, work) = (&next, &work);
+                scope.spawn(move || loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= items.len() || sender.send((i, work(&items[i]))).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(sender);
+
+            let mut pending = BTreeMap::new();
+            let mut emitted = 0;
+            for (i, result) in receiver {
+                pending.insert(i, result);
+                while let Some(result) = pending.remove(&emitted) {
+                    emit(emitted, &items[emitted], result);
+                    emitted += 1;
+                }
+            }
+        });
+    }
+
+    // Hashes every file ("-" being standard input); errors are handed to emit like digests.
+    pub fn hash_files<T, E>(&self, algo: ShaParams<T>, files: &[PathBuf], mut emit: E)
+        where T: Copy + Send + Sync, E: FnMut(&PathBuf, io::Result<Vec<u8>>)
+    {
+        self.map_ordered(files, |file| sha_sum_input(algo, file), |_, file, result| emit(file, result));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha::SHA256;
+    use crate::test_util::TempDir;
+    use std::time::Duration;
+
+    extern crate hex;
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    #[rstest(jobs,
+        case::serial(1),
+        case::two(2),
+        case::many(8),
+    )]
+    fn test_map_ordered(jobs: usize) {
+        let items: Vec<u64> = (0..50).collect();
+        let mut results = Vec::new();
+        // later items finish first, so results arrive out of order
+        Pool::new(jobs).map_ordered(&items, |&n| {
+            thread::sleep(Duration::from_micros(50 - n));
+            n * n
+        }, |i, &n, square| results.push((i, n, square)));
+
+        let expected: Vec<_> = items.iter().map(|&n| (n as usize, n, n * n)).collect();
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn test_hash_files_with_errors() {
+        let dir = TempDir::new("pool");
+        let files = vec![dir.write("a", b"abc"), dir.path().join("missing"), dir.write("b", b"")];
+
+        let mut results = Vec::new();
+        Pool::new(3).hash_files(SHA256, &files, |file, result| results.push((file.clone(), result.map(hex::encode).map_err(|e| e.kind()))));
+
+        assert_eq!(results, vec![
+            (files[0].clone(), Ok("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string())),
+            (files[1].clone(), Err(io::ErrorKind::NotFound)),
+            (files[2].clone(), Ok("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".to_string())),
+        ]);
+    }
+
+    #[test]
+    fn test_default_jobs() {
+        assert!(Pool::new(0).jobs() >= 1);
+        assert_eq!(Pool::new(3).jobs(), 3);
+    }
+}
@@ -0,0 +1,246 @@
+// Progress while hashing large files: a reader telling a callback how far it has got, the
+// bar --progress draws on a terminal, and the summary --stats prints at the end.
+//
+// The callback is given the bytes read so far and the total, if known. It is called after
+// every read from the file, that is about every READ_BUFFER_SIZE bytes.
+
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::sha::{Algorithm, BusyTime, ShaParams};
+use crate::{hash_file, hash_reader, is_stdin, multi_sum_file_timed, multi_sum_reader_timed};
+
+pub struct ProgressReader<R, F> {
+    reader: R,
+    read: u64,
+    total: Option<u64>,
+    progress: F,
+}
+
+impl<R: Read, F: FnMut(u64, Option<u64>)> ProgressReader<R, F> {
+    pub fn new(reader: R, total: Option<u64>, progress: F) -> ProgressReader<R, F> {
+        ProgressReader{reader, read: 0, total, progress}
+    }
+}
+
+impl<R: Read, F: FnMut(u64, Option<u64>)> Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        if n > 0 {
+            self.read += n as u64;
+            (self.progress)(self.read, self.total);
+        }
+        Ok(n)
+    }
+}
+
+// Like sha_sum_input, telling progress how far it has got.
+pub fn sha_sum_progress<T: Copy, F: FnMut(u64, Option<u64>)>(algo: ShaParams<T>, file: &PathBuf, mut progress: F) -> io::Result<Vec<u8>> {
+    if is_stdin(file) {
+        return hash_reader(algo, ProgressReader::new(io::stdin().lock(), None, progress)).map(|(digest, _)| digest);
+    }
+    let f = File::open(file)?;
+    let size = f.metadata()?.len();
+    hash_file(algo, &f, |hashed| progress(hashed, Some(size))).map(|(digest, _)| digest)
+}
+
+// Like multi_sum_input, telling progress how far it has got, and adding the time spent in
+// each algorithm to busy.
+pub fn multi_sum_input_progress<F: FnMut(u64, Option<u64>)>(algorithms: &[Algorithm], file: &PathBuf, mut progress: F,
+    busy: Option<&BusyTime>) -> io::Result<(Vec<Vec<u8>>, u64)>
+{
+    if is_stdin(file) {
+        return multi_sum_reader_timed(algorithms, ProgressReader::new(io::stdin().lock(), None, progress), busy);
+    }
+    let f = File::open(file)?;
+    let size = f.metadata()?.len();
+    multi_sum_file_timed(algorithms, &f, |hashed| progress(hashed, Some(size)), busy)
+}
+
+// Sizes in powers of 1000, to go with MB/s.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["kB", "MB", "GB", "TB", "PB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while value >= 999.95 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+pub fn rate(bytes: u64, elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs_f64();
+    if seconds == 0.0 {
+        return "- MB/s".to_string();
+    }
+    format!("{:.1} MB/s", bytes as f64 / seconds / 1e6)
+}
+
+const BAR_WIDTH: usize = 30;
+
+// One line of progress: a bar and a percentage if the total is known, and the rate so far.
+pub fn render(done: u64, total: Option<u64>, elapsed: Duration) -> String {
+    match total {
+        Some(total) => {
+            let fraction = if total == 0 { 1.0 } else { (done as f64 / total as f64).min(1.0) };
+            let filled = (fraction * BAR_WIDTH as f64) as usize;
+            format!("[{}{}] {:3.0}% {} of {} {}", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled),
+                fraction * 100.0, human_size(done), human_size(total), rate(done, elapsed))
+        }
+        None => format!("{} {}", human_size(done), rate(done, elapsed)),
+    }
+}
+
+// Bytes read so far, added to by the workers hashing files.
+pub type Counter = Arc<AtomicU64>;
+
+struct Shared {
+    done: Counter,
+    total: Option<u64>,
+    start: Instant,
+    // whether the bar is on the terminal now, and whether to stop drawing it
+    state: Mutex<(bool, bool)>,
+    stop: Condvar,
+}
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(200);
+
+// A progress bar redrawn on standard error several times a second, by a thread of its own,
+// from a counter of bytes read.
+pub struct ProgressBar {
+    shared: Arc<Shared>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+fn erase(drawn: &mut bool) {
+    if *drawn {
+        eprint!("\r\x1b[K");
+        *drawn = false;
+    }
+}
+
+impl ProgressBar {
+    pub fn start(done: Counter, total: Option<u64>) -> ProgressBar {
+        let shared = Arc::new(Shared{done, total, start: Instant::now(), state: Mutex::new((false, false)), stop: Condvar::new()});
+        let drawing = shared.clone();
+        let thread = thread::spawn(move || {
+            let mut state = drawing.state.lock().unwrap();
+            while !state.1 {
+                let line = render(drawing.done.load(Ordering::Relaxed), drawing.total, drawing.start.elapsed());
+                eprint!("\r\x1b[K{}", line);
+                state.0 = true;
+                state = drawing.stop.wait_timeout(state, REDRAW_INTERVAL).unwrap().0;
+            }
+            erase(&mut state.0);
+        });
+        ProgressBar{shared, thread: Some(thread)}
+    }
+
+    // Runs f with the bar off the terminal, so that what f prints isn't mixed up with it;
+    // the bar is back at the next redraw.
+    pub fn hidden<T, F: FnOnce() -> T>(&self, f: F) -> T {
+        let mut state = self.shared.state.lock().unwrap();
+        erase(&mut state.0);
+        f()
+    }
+
+    // Stops the bar and takes it off the terminal.
+    pub fn finish(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.shared.state.lock().unwrap().1 = true;
+        self.shared.stop.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ProgressBar {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// What --stats prints: the files hashed, the bytes read for them, how long that took and the
+// rate overall, then the rate of each algorithm by the time spent in it (see sha::BusyTime),
+// which with several workers may add up to more than the time elapsed.
+pub fn stats(files: usize, bytes: u64, elapsed: Duration, spent: &[(Algorithm, Duration)]) -> String {
+    let mut text = format!("files:   {}\n", files);
+    text.push_str(&format!("bytes:   {} ({})\n", bytes, human_size(bytes)));
+    text.push_str(&format!("elapsed: {:.3} s\n", elapsed.as_secs_f64()));
+    text.push_str(&format!("rate:    {}\n", rate(bytes, elapsed)));
+    for (algorithm, time) in spent {
+        text.push_str(&format!("{}:{}{} ({:.3} s hashing)\n", algorithm.name(), " ".repeat(8 - algorithm.name().len()),
+            rate(bytes, *time), time.as_secs_f64()));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha::{sha, SHA256};
+    use crate::test_util::TempDir;
+    use crate::READ_BUFFER_SIZE;
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    #[test]
+    fn test_progress_callback() {
+        let dir = TempDir::new("progress");
+        let data = vec![7u8; 2 * READ_BUFFER_SIZE + 10];
+        let file = dir.write("data", &data);
+
+        let mut calls = Vec::new();
+        let digest = sha_sum_progress(SHA256, &file, |read, total| calls.push((read, total))).unwrap();
+        assert_eq!(digest, sha(SHA256, data.iter().cloned()));
+        let total = Some(data.len() as u64);
+        assert_eq!(calls.last(), Some(&(data.len() as u64, total)));
+        assert!(calls.windows(2).all(|w| w[0].0 < w[1].0));
+
+        let mut last = 0;
+        let (_, size) = multi_sum_input_progress(&[Algorithm::Sha256], &file, |read, _| last = read, None).unwrap();
+        assert_eq!((last, size), (data.len() as u64, data.len() as u64));
+    }
+
+    #[rstest(bytes, expected,
+        case::bytes(999, "999 B"),
+        case::kilo(1500, "1.5 kB"),
+        case::rounding(999_999, "1.0 MB"),
+        case::giga(214_748_364_800, "214.7 GB"),
+    )]
+    fn test_human_size(bytes: u64, expected: &str) {
+        assert_eq!(human_size(bytes), expected);
+    }
+
+    #[test]
+    fn test_render() {
+        let second = Duration::from_secs(1);
+        assert_eq!(render(500_000_000, Some(1_000_000_000), second),
+            "[###############---------------]  50% 500.0 MB of 1.0 GB 500.0 MB/s");
+        assert_eq!(render(0, Some(0), second), format!("[{}] 100% 0 B of 0 B 0.0 MB/s", "#".repeat(30)));
+        assert_eq!(render(2_000_000, None, 2 * second), "2.0 MB 1.0 MB/s");
+    }
+
+    #[test]
+    fn test_stats() {
+        let spent = [(Algorithm::Sha256, Duration::from_millis(600)), (Algorithm::Sha512, Duration::from_millis(500))];
+        let text = stats(2, 3_000_000, Duration::from_millis(1500), &spent);
+        assert_eq!(text, "files:   2\nbytes:   3000000 (3.0 MB)\nelapsed: 1.500 s\nrate:    2.0 MB/s\n\
+            SHA256:  5.0 MB/s (0.600 s hashing)\nSHA512:  6.0 MB/s (0.500 s hashing)\n");
+    }
+}
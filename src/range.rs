@@ -0,0 +1,266 @@
+// Hashing part of a file: the bytes [offset, offset + length), read straight from the file
+// through the usual buffer, or a file cut into chunks with a digest each. The digest of a
+// range is that of the range as a message of its own, padded for its own length.
+
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::path::PathBuf;
+
+use crate::progress::ProgressReader;
+use crate::sha::{Algorithm, BusyTime, MultiHasher, ShaParams};
+use crate::{hash_reader, is_stdin, multi_sum_reader_timed, read_all};
+
+fn short_range() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "the input ends before the end of the range")
+}
+
+// The digest of length bytes from offset on; an error if the input ends before that.
+pub fn sha_sum_range<T: Copy, R: Read + Seek>(algo: ShaParams<T>, mut reader: R, offset: u64, length: u64) -> io::Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let (digest, size) = hash_reader(algo, reader.take(length))?;
+    if size < length {
+        return Err(short_range());
+    }
+    Ok(digest)
+}
+
+pub fn sha_sum_file_range<T: Copy>(algo: ShaParams<T>, file: &PathBuf, offset: u64, length: u64) -> io::Result<Vec<u8>> {
+    sha_sum_range(algo, File::open(file)?, offset, length)
+}
+
+// A file ("-" being standard input) positioned at offset, giving at most length bytes, or
+// all the rest if length is None. Standard input may be a pipe, so it is read up to offset.
+pub fn range_input(file: &PathBuf, offset: u64, length: Option<u64>) -> io::Result<Box<dyn Read>> {
+    let reader: Box<dyn Read> = if is_stdin(file) {
+        let mut stdin = io::stdin().lock();
+        if io::copy(&mut (&mut stdin).take(offset), &mut io::sink())? < offset {
+            return Err(short_range());
+        }
+        Box::new(stdin)
+    } else {
+        let mut f = File::open(file)?;
+        f.seek(SeekFrom::Start(offset))?;
+        Box::new(f)
+    };
+    Ok(match length {
+        Some(length) => Box::new(reader.take(length)),
+        None => reader,
+    })
+}
+
+// Like multi_sum_input, for a range of the file.
+pub fn multi_sum_input_range(algorithms: &[Algorithm], file: &PathBuf, offset: u64, length: Option<u64>)
+    -> io::Result<(Vec<Vec<u8>>, u64)>
+{
+    multi_sum_input_range_progress(algorithms, file, offset, length, |_, _| (), None)
+}
+
+// Like multi_sum_input_range, telling progress how far into the range it has got, and adding
+// the time spent in each algorithm to busy.
+pub fn multi_sum_input_range_progress<F: FnMut(u64, Option<u64>)>(algorithms: &[Algorithm], file: &PathBuf,
+    offset: u64, length: Option<u64>, progress: F, busy: Option<&BusyTime>) -> io::Result<(Vec<Vec<u8>>, u64)>
+{
+    let reader = ProgressReader::new(range_input(file, offset, length)?, length, progress);
+    let (digests, size) = multi_sum_reader_timed(algorithms, reader, busy)?;
+    if length.is_some_and(|length| size < length) {
+        return Err(short_range());
+    }
+    Ok((digests, size))
+}
+
+// A chunk of the input: where it starts in the input, its length and its digests.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chunk {
+    pub offset: u64,
+    pub length: u64,
+    pub digests: Vec<Vec<u8>>,
+}
+
+// Cuts the input into chunks of chunk_size bytes, the last one possibly shorter, and hashes
+// each on its own. An empty input is one empty chunk. The time spent in each algorithm is
+// added to busy.
+pub fn multi_sum_chunks<R: Read>(algorithms: &[Algorithm], reader: R, chunk_size: u64, busy: Option<&BusyTime>)
+    -> io::Result<Vec<Chunk>>
+{
+    assert!(chunk_size > 0, "chunks must not be empty");
+    let new_hasher = || {
+        let mut hasher = MultiHasher::new(algorithms);
+        if busy.is_some() {
+            hasher.time();
+        }
+        hasher
+    };
+    let finish = |hasher: MultiHasher| {
+        if let Some(busy) = busy {
+            busy.add(&hasher);
+        }
+        hasher.finish()
+    };
+    let mut chunks = Vec::new();
+    let mut hasher = new_hasher();
+    let mut offset = 0;
+    let mut length = 0;
+
+    read_all(reader, |mut data| {
+        while !data.is_empty() {
+            let take = std::cmp::min(chunk_size - length, data.len() as u64) as usize;
+            hasher.update(&data[..take]);
+            data = &data[take..];
+            length += take as u64;
+            if length == chunk_size {
+                let digests = finish(std::mem::replace(&mut hasher, new_hasher()));
+                chunks.push(Chunk{offset, length, digests});
+                offset += length;
+                length = 0;
+            }
+        }
+    })?;
+
+    if length > 0 || chunks.is_empty() {
+        chunks.push(Chunk{offset, length, digests: finish(hasher)});
+    }
+    Ok(chunks)
+}
+
+// Like multi_sum_chunks, for a range of the file; the offsets of the chunks are those in the file.
+pub fn multi_sum_input_chunks(algorithms: &[Algorithm], file: &PathBuf, offset: u64, length: Option<u64>, chunk_size: u64)
+    -> io::Result<Vec<Chunk>>
+{
+    multi_sum_input_chunks_progress(algorithms, file, offset, length, chunk_size, |_, _| (), None)
+}
+
+// Like multi_sum_input_chunks, telling progress how far into the range it has got, and adding
+// the time spent in each algorithm to busy.
+pub fn multi_sum_input_chunks_progress<F: FnMut(u64, Option<u64>)>(algorithms: &[Algorithm], file: &PathBuf,
+    offset: u64, length: Option<u64>, chunk_size: u64, progress: F, busy: Option<&BusyTime>) -> io::Result<Vec<Chunk>>
+{
+    let reader = ProgressReader::new(range_input(file, offset, length)?, length, progress);
+    let mut chunks = multi_sum_chunks(algorithms, reader, chunk_size, busy)?;
+    let size: u64 = chunks.iter().map(|c| c.length).sum();
+    if length.is_some_and(|length| size < length) {
+        return Err(short_range());
+    }
+    for chunk in chunks.iter_mut() {
+        chunk.offset += offset;
+    }
+    Ok(chunks)
+}
+
+// A number of bytes, as 4096, 64K or 1MiB (powers of 1024), or 1MB (powers of 1000).
+pub fn parse_size(text: &str) -> Option<u64> {
+    let digits = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, suffix) = text.split_at(digits);
+    let number: u64 = number.parse().ok()?;
+    let unit: u64 = match suffix {
+        "" => 1,
+        "K" | "KiB" => 1 << 10,
+        "M" | "MiB" => 1 << 20,
+        "G" | "GiB" => 1 << 30,
+        "T" | "TiB" => 1 << 40,
+        "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "TB" => 1_000_000_000_000,
+        _ => return None,
+    };
+    number.checked_mul(unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha::{sha, SHA256, SHA512};
+    use crate::test_util::TempDir;
+    use std::io::Cursor;
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    fn data() -> Vec<u8> {
+        (0..1000u32).map(|i| (i * 13) as u8).collect()
+    }
+
+    #[rstest(offset, length,
+        case::start(0, 3),
+        case::middle(100, 200),
+        case::block_boundary(64, 128),
+        case::to_end(900, 100),
+        case::empty(500, 0),
+    )]
+    fn test_sha_sum_range(offset: u64, length: u64) {
+        let data = data();
+        let expected = sha(SHA256, data[offset as usize..(offset + length) as usize].iter().cloned());
+        assert_eq!(sha_sum_range(SHA256, Cursor::new(&data), offset, length).unwrap(), expected);
+
+        let dir = TempDir::new(&format!("range-{}-{}", offset, length));
+        let file = dir.write("data", &data);
+        assert_eq!(sha_sum_file_range(SHA256, &file, offset, length).unwrap(), expected);
+        let (digests, size) = multi_sum_input_range(&[Algorithm::Sha256], &file, offset, Some(length)).unwrap();
+        assert_eq!((digests, size), (vec![expected], length));
+    }
+
+    #[test]
+    fn test_range_past_end() {
+        let data = data();
+        let error = sha_sum_range(SHA512, Cursor::new(&data), 990, 11).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(sha_sum_range(SHA512, Cursor::new(&data), 2000, 1).is_err());
+
+        let dir = TempDir::new("range-past-end");
+        let file = dir.write("data", &data);
+        let (_, size) = multi_sum_input_range(&[Algorithm::Sha256], &file, 990, None).unwrap();
+        assert_eq!(size, 10);
+        assert!(multi_sum_input_range(&[Algorithm::Sha256], &file, 990, Some(11)).is_err());
+    }
+
+    #[rstest(chunk_size, expected_lengths,
+        case::even(250, vec![250, 250, 250, 250]),
+        case::uneven(300, vec![300, 300, 300, 100]),
+        case::larger(4096, vec![1000]),
+        case::blocks(64, [vec![64; 15], vec![40]].concat()),
+    )]
+    fn test_chunks(chunk_size: u64, expected_lengths: Vec<u64>) {
+        let data = data();
+        let chunks = multi_sum_chunks(&[Algorithm::Sha256, Algorithm::Sha512], Cursor::new(&data), chunk_size, None).unwrap();
+        assert_eq!(chunks.iter().map(|c| c.length).collect::<Vec<_>>(), expected_lengths);
+
+        for chunk in chunks {
+            let part = &data[chunk.offset as usize..(chunk.offset + chunk.length) as usize];
+            assert_eq!(chunk.digests, vec![sha(SHA256, part.iter().cloned()), sha(SHA512, part.iter().cloned())]);
+        }
+    }
+
+    #[test]
+    fn test_input_chunks() {
+        let data = data();
+        let dir = TempDir::new("range-chunks");
+        let file = dir.write("data", &data);
+
+        let chunks = multi_sum_input_chunks(&[Algorithm::Sha256], &file, 100, Some(500), 200).unwrap();
+        assert_eq!(chunks.iter().map(|c| (c.offset, c.length)).collect::<Vec<_>>(), vec![(100, 200), (300, 200), (500, 100)]);
+        assert_eq!(chunks[2].digests[0], sha(SHA256, data[500..600].iter().cloned()));
+        assert!(multi_sum_input_chunks(&[Algorithm::Sha256], &file, 900, Some(200), 64).is_err());
+    }
+
+    #[test]
+    fn test_chunks_empty() {
+        let chunks = multi_sum_chunks(&[Algorithm::Sha256], Cursor::new(b""), 10, None).unwrap();
+        assert_eq!(chunks, vec![Chunk{offset: 0, length: 0, digests: vec![sha(SHA256, "".bytes())]}]);
+    }
+
+    #[rstest(text, expected,
+        case::plain("4096", Some(4096)),
+        case::kibi("64K", Some(65536)),
+        case::mebi("1MiB", Some(1 << 20)),
+        case::giga("2GB", Some(2_000_000_000)),
+        case::empty("", None),
+        case::unit_only("K", None),
+        case::bad_unit("1X", None),
+        case::overflow("20000000T", None),
+    )]
+    fn test_parse_size(text: &str, expected: Option<u64>) {
+        assert_eq!(parse_size(text), expected);
+    }
+}
@@ -0,0 +1,187 @@
+// Machine-readable output of the checksum binaries, one record per file:
+//   json   a single array of objects
+//   jsonl  one object per line
+//   csv    a header line, then one row per file
+// Every record has path, algorithm, digest, size and error; see the README for the schema.
+
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordFormat {
+    Json,
+    Jsonl,
+    Csv,
+}
+
+impl RecordFormat {
+    pub fn from_name(name: &str) -> Option<RecordFormat> {
+        match name {
+            "json" => Some(RecordFormat::Json),
+            "jsonl" => Some(RecordFormat::Jsonl),
+            "csv" => Some(RecordFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+// Either digest and size, or error, are set.
+#[derive(Debug, PartialEq)]
+pub struct Record<'a> {
+    pub path: &'a Path,
+    pub algorithm: &'a str,
+    pub digest: Option<&'a str>,
+    pub size: Option<u64>,
+    pub error: Option<&'a str>,
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+// Quoted only when needed, as RFC 4180 has it.
+pub fn csv_field(s: &str) -> String {
+    if s.contains(['"', ',', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn json_object(record: &Record) -> String {
+    let optional = |s: Option<&str>| s.map(json_string).unwrap_or_else(|| "null".to_string());
+    format!("{{\"path\":{},\"algorithm\":{},\"digest\":{},\"size\":{},\"error\":{}}}",
+        json_string(&record.path.to_string_lossy()),
+        json_string(record.algorithm),
+        optional(record.digest),
+        record.size.map(|s| s.to_string()).unwrap_or_else(|| "null".to_string()),
+        optional(record.error))
+}
+
+fn csv_row(record: &Record) -> String {
+    format!("{},{},{},{},{}",
+        csv_field(&record.path.to_string_lossy()),
+        csv_field(record.algorithm),
+        csv_field(record.digest.unwrap_or("")),
+        record.size.map(|s| s.to_string()).unwrap_or_default(),
+        csv_field(record.error.unwrap_or("")))
+}
+
+pub struct RecordWriter<W: Write> {
+    out: W,
+    format: RecordFormat,
+    count: usize,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(mut out: W, format: RecordFormat) -> io::Result<RecordWriter<W>> {
+        if format == RecordFormat::Csv {
+            writeln!(out, "path,algorithm,digest,size,error")?;
+        }
+        Ok(RecordWriter{out, format, count: 0})
+    }
+
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        match self.format {
+            RecordFormat::Json => {
+                let separator = if self.count == 0 { "[\n" } else { ",\n" };
+                write!(self.out, "{}  {}", separator, json_object(record))?;
+            }
+            RecordFormat::Jsonl => writeln!(self.out, "{}", json_object(record))?,
+            RecordFormat::Csv => writeln!(self.out, "{}", csv_row(record))?,
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    // Closes the JSON array; must be called once all records are written.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.format == RecordFormat::Json {
+            let end = if self.count == 0 { "[]\n" } else { "\n]\n" };
+            self.out.write_all(end.as_bytes())?;
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    fn records() -> Vec<Record<'static>> {
+        vec![
+            Record{path: Path::new("a \"b\".txt"), algorithm: "SHA256", digest: Some("fbff"), size: Some(3), error: None},
+            Record{path: Path::new("dir/c,d"), algorithm: "SHA256", digest: None, size: None, error: Some("Permission denied")},
+        ]
+    }
+
+    fn write_all(format: RecordFormat, records: &[Record]) -> String {
+        let mut writer = RecordWriter::new(Vec::new(), format).unwrap();
+        for record in records {
+            writer.write(record).unwrap();
+        }
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[rstest(input, expected,
+        case::plain("abc", "\"abc\""),
+        case::quotes("a\"b\\c", "\"a\\\"b\\\\c\""),
+        case::control("a\nb\u{1}", "\"a\\nb\\u0001\""),
+        case::unicode("café", "\"café\""),
+    )]
+    fn test_json_string(input: &str, expected: &str) {
+        assert_eq!(json_string(input), expected);
+    }
+
+    #[rstest(input, expected,
+        case::plain("abc", "abc"),
+        case::comma("a,b", "\"a,b\""),
+        case::quote("a\"b", "\"a\"\"b\""),
+        case::newline("a\nb", "\"a\nb\""),
+    )]
+    fn test_csv_field(input: &str, expected: &str) {
+        assert_eq!(csv_field(input), expected);
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(write_all(RecordFormat::Json, &records()),
+            "[\n  {\"path\":\"a \\\"b\\\".txt\",\"algorithm\":\"SHA256\",\"digest\":\"fbff\",\"size\":3,\"error\":null},\n  \
+             {\"path\":\"dir/c,d\",\"algorithm\":\"SHA256\",\"digest\":null,\"size\":null,\"error\":\"Permission denied\"}\n]\n");
+        assert_eq!(write_all(RecordFormat::Json, &[]), "[]\n");
+    }
+
+    #[test]
+    fn test_jsonl() {
+        assert_eq!(write_all(RecordFormat::Jsonl, &records()),
+            "{\"path\":\"a \\\"b\\\".txt\",\"algorithm\":\"SHA256\",\"digest\":\"fbff\",\"size\":3,\"error\":null}\n\
+             {\"path\":\"dir/c,d\",\"algorithm\":\"SHA256\",\"digest\":null,\"size\":null,\"error\":\"Permission denied\"}\n");
+    }
+
+    #[test]
+    fn test_csv() {
+        assert_eq!(write_all(RecordFormat::Csv, &records()),
+            "path,algorithm,digest,size,error\n\
+             \"a \"\"b\"\".txt\",SHA256,fbff,3,\n\
+             \"dir/c,d\",SHA256,,,Permission denied\n");
+    }
+}
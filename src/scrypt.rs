@@ -0,0 +1,205 @@
+// scrypt (RFC 7914): PBKDF2-HMAC-SHA256 wrapped around ROMix, a sequential memory-hard
+// mixing of Salsa20/8 blocks. Blocks are kept as little endian u32 words throughout.
+
+use std::convert::TryInto;
+
+use crate::kdf::pbkdf2;
+use crate::sha::SHA256;
+
+// Refuse to allocate more than this in all, unless asked to with scrypt_with_memory_limit
+pub const DEFAULT_MAX_MEMORY: u64 = 1 << 30;
+
+#[derive(Debug, PartialEq)]
+pub enum ScryptError {
+    InvalidCostParameter,
+    InvalidBlockSize,
+    InvalidParallelization,
+    MemoryLimitExceeded,
+    OutputTooLong,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScryptParams {
+    n: u64,
+    r: u32,
+    p: u32,
+}
+
+impl ScryptParams {
+    // n: CPU/memory cost (a power of two above 1), r: block size, p: parallelization
+    pub fn new(n: u64, r: u32, p: u32) -> Result<ScryptParams, ScryptError> {
+        if r == 0 {
+            return Err(ScryptError::InvalidBlockSize);
+        }
+        // N must be less than 2^(128 * r / 8)
+        if n < 2 || !n.is_power_of_two() || (r < 8 && n.trailing_zeros() >= 16 * r) {
+            return Err(ScryptError::InvalidCostParameter);
+        }
+        if p == 0 || (p as u64) * (r as u64) >= 1 << 30 {
+            return Err(ScryptError::InvalidParallelization);
+        }
+
+        Ok(ScryptParams{n, r, p})
+    }
+
+    // The bytes allocated: N blocks of 128r bytes for V, p for B, and X and Y.
+    pub fn memory(&self) -> u64 {
+        let blocks = self.n.saturating_add(self.p as u64).saturating_add(2);
+        128u64.saturating_mul(self.r as u64).saturating_mul(blocks)
+    }
+}
+
+fn salsa20_8(b: &mut [u32]) {
+    let mut x: [u32;16] = b[..16].try_into().unwrap();
+
+    macro_rules! quarter {
+        ($a:expr, $b:expr, $c:expr, $d:expr) => {
+            x[$b] ^= x[$a].wrapping_add(x[$d]).rotate_left(7);
+            x[$c] ^= x[$b].wrapping_add(x[$a]).rotate_left(9);
+            x[$d] ^= x[$c].wrapping_add(x[$b]).rotate_left(13);
+            x[$a] ^= x[$d].wrapping_add(x[$c]).rotate_left(18);
+        }
+    }
+
+    for _ in 0..4 {
+        // columns
+        quarter!(0, 4, 8, 12);
+        quarter!(5, 9, 13, 1);
+        quarter!(10, 14, 2, 6);
+        quarter!(15, 3, 7, 11);
+        // rows
+        quarter!(0, 1, 2, 3);
+        quarter!(5, 6, 7, 4);
+        quarter!(10, 11, 8, 9);
+        quarter!(15, 12, 13, 14);
+    }
+
+    b.iter_mut().zip(&x).for_each(|(b, x)| *b = b.wrapping_add(*x));
+}
+
+// B is 2r 64 byte blocks; the result has the even numbered blocks first, then the odd numbered.
+fn block_mix(b: &[u32], y: &mut [u32], r: usize) {
+    let mut x: [u32;16] = b[(2*r - 1) * 16..].try_into().unwrap();
+
+    for i in 0..2*r {
+        x.iter_mut().zip(&b[i*16..(i+1)*16]).for_each(|(x, b)| *x ^= b);
+        salsa20_8(&mut x);
+        let target = (i / 2 + (i % 2) * r) * 16;
+        y[target..target + 16].copy_from_slice(&x);
+    }
+}
+
+fn integerify(x: &[u32], r: usize) -> u64 {
+    let last = (2*r - 1) * 16;
+    (x[last] as u64) | ((x[last + 1] as u64) << 32)
+}
+
+fn ro_mix(b: &mut [u8], n: u64, r: usize) {
+    let words = 32 * r;
+    let mut x: Vec<u32> = b.chunks_exact(4).map(|c| u32::from_le_bytes(c.try_into().unwrap())).collect();
+    let mut y = vec![0u32; words];
+    let mut v = vec![0u32; words * n as usize];
+
+    for i in 0..n as usize {
+        v[i*words..(i+1)*words].copy_from_slice(&x);
+        block_mix(&x, &mut y, r);
+        std::mem::swap(&mut x, &mut y);
+    }
+
+    for _ in 0..n {
+        let j = (integerify(&x, r) & (n - 1)) as usize;
+        x.iter_mut().zip(&v[j*words..(j+1)*words]).for_each(|(x, v)| *x ^= v);
+        block_mix(&x, &mut y, r);
+        std::mem::swap(&mut x, &mut y);
+    }
+
+    b.chunks_exact_mut(4).zip(&x).for_each(|(c, w)| c.copy_from_slice(&w.to_le_bytes()));
+}
+
+pub fn scrypt(password: &[u8], salt: &[u8], params: &ScryptParams, dk_len: usize) -> Result<Vec<u8>, ScryptError> {
+    scrypt_with_memory_limit(password, salt, params, dk_len, DEFAULT_MAX_MEMORY)
+}
+
+pub fn scrypt_with_memory_limit(password: &[u8], salt: &[u8], params: &ScryptParams, dk_len: usize, max_memory: u64) -> Result<Vec<u8>, ScryptError> {
+    if params.memory() > max_memory || params.memory() > usize::MAX as u64 {
+        return Err(ScryptError::MemoryLimitExceeded);
+    }
+    if dk_len as u64 > ((1u64 << 32) - 1) * 32 {
+        return Err(ScryptError::OutputTooLong);
+    }
+
+    let r = params.r as usize;
+    let block_len = 128 * r;
+    let mut b = pbkdf2(SHA256, password, salt, 1, params.p as usize * block_len).unwrap();
+    for chunk in b.chunks_exact_mut(block_len) {
+        ro_mix(chunk, params.n, r);
+    }
+
+    Ok(pbkdf2(SHA256, password, &b, 1, dk_len).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    extern crate hex;
+
+    fn words(s: &str) -> Vec<u32> {
+        hex::decode(s).unwrap().chunks(4).map(|c| u32::from_le_bytes(c.try_into().unwrap())).collect()
+    }
+
+    // RFC 7914, section 8
+    #[test]
+    fn test_salsa20_8_core() {
+        let mut b = words("7e879a214f3ec9867ca940e641718f26baee555b8c61c1b50df846116dcd3b1dee24f319df9b3d8514121e4b5ac5aa3276021d2909c74829edebc68db8b8c25e");
+        salsa20_8(&mut b);
+        assert_eq!(b, words("a41f859c6608cc993b81cacb020cef05044b2181a2fd337dfd7b1c6396682f29b4393168e3c9e6bcfe6bc5b7a06d96bae424cc102c91745c24ad673dc7618f81"));
+    }
+
+    // RFC 7914, section 12. The 1 GiB case (N = 1048576) is left out to keep the test run short.
+    #[rstest(password, salt, n, r, p, expected,
+        case::empty("", "", 16, 1, 1, "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906"),
+        case::nacl("password", "NaCl", 1024, 8, 16, "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b3731622eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640"),
+        case::sodium_chloride("pleaseletmein", "SodiumChloride", 16384, 8, 1, "7023bdcb3afd7348461c06cd81fd38ebfda8fbba904f8e3ea9b543f6545da1f2d5432955613f0fcf62d49705242a9af9e61e85dc0d651e40dfcf017b45575887"),
+    )]
+    fn test_rfc7914(password: &str, salt: &str, n: u64, r: u32, p: u32, expected: &str) {
+        let params = ScryptParams::new(n, r, p).unwrap();
+        let result = scrypt(password.as_bytes(), salt.as_bytes(), &params, 64).unwrap();
+        assert_eq!(hex::encode(result), expected);
+    }
+
+    #[rstest(n, r, p, expected,
+        case::n_one(1, 1, 1, ScryptError::InvalidCostParameter),
+        case::n_not_power_of_two(1000, 8, 1, ScryptError::InvalidCostParameter),
+        case::n_too_large_for_r(1 << 16, 1, 1, ScryptError::InvalidCostParameter),
+        case::r_zero(16, 0, 1, ScryptError::InvalidBlockSize),
+        case::p_zero(16, 8, 0, ScryptError::InvalidParallelization),
+        case::pr_too_large(16, 1 << 15, 1 << 15, ScryptError::InvalidParallelization),
+    )]
+    fn test_invalid_params(n: u64, r: u32, p: u32, expected: ScryptError) {
+        assert_eq!(ScryptParams::new(n, r, p), Err(expected));
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let params = ScryptParams::new(16, 1, 1).unwrap();
+        assert_eq!(params.memory(), 128 * (16 + 1 + 2));
+        assert!(scrypt_with_memory_limit(b"", b"", &params, 64, 128 * 19).is_ok());
+        assert_eq!(scrypt_with_memory_limit(b"", b"", &params, 64, 128 * 19 - 1), Err(ScryptError::MemoryLimitExceeded));
+
+        // V alone is 1 GiB, so with B, X and Y it is just over
+        let params = ScryptParams::new(1 << 20, 8, 1).unwrap();
+        assert_eq!(scrypt(b"", b"", &params, 64), Err(ScryptError::MemoryLimitExceeded));
+    }
+
+    #[test]
+    fn test_memory_limit_counts_p() {
+        // V is a mere 16 KiB, but B would be 1 GiB
+        let params = ScryptParams::new(16, 8, 1 << 20).unwrap();
+        assert!(params.memory() > 1 << 30);
+        assert_eq!(scrypt(b"", b"", &params, 64), Err(ScryptError::MemoryLimitExceeded));
+    }
+}
@@ -1,33 +1,117 @@
 use crate::block_splitter;
-use crate::padder::{ShaPaddedStream, LengthSize};
-use crate::sha256::{sha256_block, u32_to_u8, H0 as SHA256_H0};
-use crate::sha512::{sha512_block, u64_to_u8, H0 as SHA512_H0};
+use crate::padder::{Padder, ShaPaddedStream, LengthSize};
+use std::convert::{TryFrom, TryInto};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::sha1::{sha1_block, sha1_blocks, u32_to_u8_160, H0 as SHA1_H0};
+use crate::sha256::{sha256_block, sha256_blocks, u32_to_u8, H0 as SHA256_H0};
+use crate::sha512::{sha512_block, sha512_blocks, u64_to_u8, u64_to_u8_384, H0 as SHA512_H0, H0_384 as SHA384_H0};
 
+#[derive(Clone, Copy)]
 pub struct ShaParams<T> {
+    pub name: &'static str,
     pub block_size: usize,
+    pub digest_size: usize,
     pub length_size: LengthSize,
     pub h0: [T;8],
     pub sha_func: fn([T;8], &[u8]) -> [T;8],
+    // the same for a run of whole blocks, saving a call through the pointer per block
+    pub blocks_func: fn([T;8], &[u8]) -> [T;8],
     pub convert_func: fn(Vec<T>) -> Vec<u8>,
 }
 
 pub const SHA256: ShaParams<u32> = ShaParams {
+    name: "SHA256",
     block_size: 64,
+    digest_size: 32,
     length_size: LengthSize::Len64,
     h0: SHA256_H0,
     sha_func: sha256_block,
+    blocks_func: sha256_blocks,
     convert_func: u32_to_u8
 };
 
+pub const SHA384: ShaParams<u64> = ShaParams {
+    name: "SHA384",
+    block_size: 128,
+    digest_size: 48,
+    length_size: LengthSize::Len128,
+    h0: SHA384_H0,
+    sha_func: sha512_block,
+    blocks_func: sha512_blocks,
+    convert_func: u64_to_u8_384
+};
+
 pub const SHA512: ShaParams<u64> = ShaParams {
+    name: "SHA512",
     block_size: 128,
+    digest_size: 64,
     length_size: LengthSize::Len128,
     h0: SHA512_H0,
     sha_func: sha512_block,
+    blocks_func: sha512_blocks,
     convert_func: u64_to_u8
 };
 
-pub fn sha<'a, I, T>(params: ShaParams<T>, msg: I) -> Vec<u8> where I: IntoIterator<Item=u8>, T: std::clone::Clone
+// Broken for collisions; see sha1.rs.
+pub const SHA1: ShaParams<u32> = ShaParams {
+    name: "SHA1",
+    block_size: 64,
+    digest_size: 20,
+    length_size: LengthSize::Len64,
+    h0: SHA1_H0,
+    sha_func: sha1_block,
+    blocks_func: sha1_blocks,
+    convert_func: u32_to_u8_160
+};
+
+// For choosing an algorithm at run time, e.g. from the command line; ShaParams differ
+// in their word type, so they can't be picked from a list directly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+    Sha1,
+}
+
+pub const ALGORITHMS: [Algorithm;4] = [Algorithm::Sha256, Algorithm::Sha384, Algorithm::Sha512, Algorithm::Sha1];
+
+impl Algorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => SHA256.name,
+            Algorithm::Sha384 => SHA384.name,
+            Algorithm::Sha512 => SHA512.name,
+            Algorithm::Sha1 => SHA1.name,
+        }
+    }
+
+    pub fn digest_size(&self) -> usize {
+        match self {
+            Algorithm::Sha256 => SHA256.digest_size,
+            Algorithm::Sha384 => SHA384.digest_size,
+            Algorithm::Sha512 => SHA512.digest_size,
+            Algorithm::Sha1 => SHA1.digest_size,
+        }
+    }
+
+    // Accepts "sha256", "SHA-256" and the like.
+    pub fn from_name(name: &str) -> Option<Algorithm> {
+        let name = name.to_uppercase().replace('-', "");
+        ALGORITHMS.iter().cloned().find(|a| a.name() == name)
+    }
+
+    // The algorithm of a program named like "sha256sum", or "/usr/bin/sha512sum.exe".
+    pub fn from_program_name(program: &str) -> Option<Algorithm> {
+        let base = program.rsplit(['/', '\\']).next().unwrap_or(program);
+        let base = base.strip_suffix(".exe").unwrap_or(base);
+        Algorithm::from_name(base.strip_suffix("sum")?)
+    }
+}
+
+pub fn sha<I, T>(params: ShaParams<T>, msg: I) -> Vec<u8> where I: IntoIterator<Item=u8>, T: std::clone::Clone
 {
     let block_stream = block_splitter::BlockStream::new(params.block_size, msg.into_iter());
     let padded_stream = ShaPaddedStream::new(block_stream, params.block_size, params.length_size);
@@ -37,7 +121,255 @@ pub fn sha<'a, I, T>(params: ShaParams<T>, msg: I) -> Vec<u8> where I: IntoItera
         h = (params.sha_func)(h, &block[..]);
     }
 
-    return (params.convert_func)(h.to_vec());
+    (params.convert_func)(h.to_vec())
+}
+
+// Incremental hashing, for when the message arrives in pieces (or is built from several parts).
+// Whole blocks are compressed directly from the supplied slices; only a partial block is buffered.
+#[derive(Clone)]
+pub struct Hasher<T> {
+    params: ShaParams<T>,
+    hash: [T;8],
+    pending: Vec<u8>,
+    count: u128,
+}
+
+impl<T: Copy> Hasher<T> {
+    pub fn new(params: ShaParams<T>) -> Hasher<T> {
+        Hasher{hash: params.h0, pending: Vec::with_capacity(params.block_size), count: 0, params}
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let block_size = self.params.block_size;
+        self.count += u128::try_from(data.len()).unwrap();
+
+        let mut data = data;
+        if !self.pending.is_empty() {
+            let take = std::cmp::min(block_size - self.pending.len(), data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.pending.len() < block_size {
+                return;
+            }
+            self.hash = (self.params.sha_func)(self.hash, &self.pending);
+            self.pending.clear();
+        }
+
+        let whole = data.len() - data.len() % block_size;
+        self.hash = (self.params.blocks_func)(self.hash, &data[..whole]);
+        self.pending.extend_from_slice(&data[whole..]);
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.finish_reset()
+    }
+
+    // The digest, leaving the hasher ready for the next message, with its buffer kept.
+    pub fn finish_reset(&mut self) -> Vec<u8> {
+        let padder = Padder::new(self.params.block_size, self.params.length_size);
+        let sha_func = self.params.sha_func;
+        let mut hash = self.hash;
+
+        if padder.is_room(self.pending.len()) {
+            hash = sha_func(hash, &padder.single_pad(&self.pending, self.count));
+        } else {
+            hash = sha_func(hash, &padder.double_pad_1st_part(&self.pending));
+            hash = sha_func(hash, &padder.double_pad_2nd_part(self.count));
+        }
+
+        self.hash = self.params.h0;
+        self.pending.clear();
+        self.count = 0;
+        (self.params.convert_func)(hash.to_vec())
+    }
+
+    // The chaining value, the bytes of the partial block and the number of bytes hashed.
+    pub fn state(&self) -> ([T;8], &[u8], u128) {
+        (self.hash, &self.pending, self.count)
+    }
+
+    // A Hasher carrying on from a state given by state(); None if the parts don't fit together.
+    pub fn from_state(params: ShaParams<T>, hash: [T;8], pending: &[u8], count: u128) -> Option<Hasher<T>> {
+        if pending.len() >= params.block_size || count % params.block_size as u128 != pending.len() as u128 {
+            return None;
+        }
+        let mut hasher = Hasher::new(params);
+        hasher.hash = hash;
+        hasher.pending.extend_from_slice(pending);
+        hasher.count = count;
+        Some(hasher)
+    }
+}
+
+// The state of a hasher between updates, to save and carry on from later, in another process
+// if need be: the chaining value as big endian words, the partial block and the byte count.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Midstate {
+    pub algorithm: Algorithm,
+    pub chaining: Vec<u8>,
+    pub pending: Vec<u8>,
+    pub count: u128,
+}
+
+// The words of the chaining value in use: SHA-1 has five of the eight a ShaParams carries.
+fn chaining_words(algorithm: Algorithm) -> usize {
+    match algorithm {
+        Algorithm::Sha1 => 5,
+        _ => 8,
+    }
+}
+
+fn words<const N: usize>(bytes: &[u8], count: usize) -> Option<Vec<[u8; N]>> {
+    if bytes.len() != count * N {
+        return None;
+    }
+    Some(bytes.chunks_exact(N).map(|word| word.try_into().unwrap()).collect())
+}
+
+fn midstate_32(algorithm: Algorithm, h: &Hasher<u32>) -> Midstate {
+    let (hash, pending, count) = h.state();
+    let chaining = hash[..chaining_words(algorithm)].iter().flat_map(|w| w.to_be_bytes()).collect();
+    Midstate{algorithm, chaining, pending: pending.to_vec(), count}
+}
+
+fn midstate_64(algorithm: Algorithm, h: &Hasher<u64>) -> Midstate {
+    let (hash, pending, count) = h.state();
+    Midstate{algorithm, chaining: hash.iter().flat_map(|w| w.to_be_bytes()).collect(), pending: pending.to_vec(), count}
+}
+
+fn resume_32(params: ShaParams<u32>, m: &Midstate) -> Option<Hasher<u32>> {
+    let mut hash: Vec<u32> = words::<4>(&m.chaining, chaining_words(m.algorithm))?.into_iter().map(u32::from_be_bytes).collect();
+    hash.resize(8, 0);
+    Hasher::from_state(params, hash.try_into().ok()?, &m.pending, m.count)
+}
+
+fn resume_64(params: ShaParams<u64>, m: &Midstate) -> Option<Hasher<u64>> {
+    let hash: Vec<u64> = words::<8>(&m.chaining, 8)?.into_iter().map(u64::from_be_bytes).collect();
+    Hasher::from_state(params, hash.try_into().ok()?, &m.pending, m.count)
+}
+
+// The time spent in each algorithm by the MultiHashers added to it, summed over them, and
+// so over the threads they ran on. The algorithms share a pass over the data, so the wall
+// clock can't tell how fast each of them is; this can.
+#[derive(Debug, Default)]
+pub struct BusyTime {
+    spent: Mutex<HashMap<Algorithm, Duration>>,
+}
+
+impl BusyTime {
+    // Adds the time the hasher has spent, if it was timed (see MultiHasher::time).
+    pub fn add(&self, hasher: &MultiHasher) {
+        let mut spent = self.spent.lock().unwrap();
+        for (algorithm, time) in hasher.busy() {
+            *spent.entry(algorithm).or_default() += time;
+        }
+    }
+
+    pub fn spent(&self, algorithm: Algorithm) -> Duration {
+        self.spent.lock().unwrap().get(&algorithm).copied().unwrap_or_default()
+    }
+}
+
+// Several digests of the same message in one pass: every update goes to a Hasher per
+// algorithm, and each of them cuts the data into blocks of its own size.
+#[derive(Clone)]
+pub struct MultiHasher {
+    hashers: Vec<AnyHasher>,
+    // the time update has spent in each hasher, once time has been called
+    busy: Option<Vec<Duration>>,
+}
+
+#[derive(Clone)]
+enum AnyHasher {
+    Sha256(Hasher<u32>),
+    Sha384(Hasher<u64>),
+    Sha512(Hasher<u64>),
+    Sha1(Hasher<u32>),
+}
+
+impl AnyHasher {
+    fn algorithm(&self) -> Algorithm {
+        match self {
+            AnyHasher::Sha256(_) => Algorithm::Sha256,
+            AnyHasher::Sha384(_) => Algorithm::Sha384,
+            AnyHasher::Sha512(_) => Algorithm::Sha512,
+            AnyHasher::Sha1(_) => Algorithm::Sha1,
+        }
+    }
+}
+
+impl MultiHasher {
+    pub fn new(algorithms: &[Algorithm]) -> MultiHasher {
+        let hashers = algorithms.iter().map(|algorithm| match algorithm {
+            Algorithm::Sha256 => AnyHasher::Sha256(Hasher::new(SHA256)),
+            Algorithm::Sha384 => AnyHasher::Sha384(Hasher::new(SHA384)),
+            Algorithm::Sha512 => AnyHasher::Sha512(Hasher::new(SHA512)),
+            Algorithm::Sha1 => AnyHasher::Sha1(Hasher::new(SHA1)),
+        }).collect();
+        MultiHasher{hashers, busy: None}
+    }
+
+    // Counts the time update spends in each algorithm from now on, for busy(); the clock is
+    // only read for those who want to know.
+    pub fn time(&mut self) {
+        let hashers = self.hashers.len();
+        self.busy.get_or_insert_with(|| vec![Duration::ZERO; hashers]);
+    }
+
+    // The time spent in each algorithm, in the order the algorithms were given; none if not timed.
+    pub fn busy(&self) -> Vec<(Algorithm, Duration)> {
+        match &self.busy {
+            Some(busy) => self.hashers.iter().map(AnyHasher::algorithm).zip(busy.iter().copied()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for (i, hasher) in self.hashers.iter_mut().enumerate() {
+            let start = self.busy.as_ref().map(|_| Instant::now());
+            match hasher {
+                AnyHasher::Sha256(h) | AnyHasher::Sha1(h) => h.update(data),
+                AnyHasher::Sha384(h) | AnyHasher::Sha512(h) => h.update(data),
+            }
+            if let (Some(start), Some(busy)) = (start, self.busy.as_mut()) {
+                busy[i] += start.elapsed();
+            }
+        }
+    }
+
+    // The state of each hasher, in the order the algorithms were given.
+    pub fn midstates(&self) -> Vec<Midstate> {
+        self.hashers.iter().map(|hasher| match hasher {
+            AnyHasher::Sha256(h) => midstate_32(Algorithm::Sha256, h),
+            AnyHasher::Sha384(h) => midstate_64(Algorithm::Sha384, h),
+            AnyHasher::Sha512(h) => midstate_64(Algorithm::Sha512, h),
+            AnyHasher::Sha1(h) => midstate_32(Algorithm::Sha1, h),
+        }).collect()
+    }
+
+    // Carrying on from the states given by midstates(); None if one of them doesn't fit its algorithm.
+    pub fn from_midstates(midstates: &[Midstate]) -> Option<MultiHasher> {
+        let hashers = midstates.iter().map(|m| match m.algorithm {
+            Algorithm::Sha256 => resume_32(SHA256, m).map(AnyHasher::Sha256),
+            Algorithm::Sha384 => resume_64(SHA384, m).map(AnyHasher::Sha384),
+            Algorithm::Sha512 => resume_64(SHA512, m).map(AnyHasher::Sha512),
+            Algorithm::Sha1 => resume_32(SHA1, m).map(AnyHasher::Sha1),
+        }).collect::<Option<Vec<_>>>()?;
+        Some(MultiHasher{hashers, busy: None})
+    }
+
+    // The digests, in the order the algorithms were given.
+    pub fn finish(mut self) -> Vec<Vec<u8>> {
+        self.finish_reset()
+    }
+
+    // The digests, leaving the hashers ready for the next message.
+    pub fn finish_reset(&mut self) -> Vec<Vec<u8>> {
+        self.hashers.iter_mut().map(|hasher| match hasher {
+            AnyHasher::Sha256(h) | AnyHasher::Sha1(h) => h.finish_reset(),
+            AnyHasher::Sha384(h) | AnyHasher::Sha512(h) => h.finish_reset(),
+        }).collect()
+    }
 }
 
 #[cfg(test)]
@@ -70,4 +402,138 @@ mod tests {
         assert_eq!(hex::encode(result), "8E959B75DAE313DA8CF4F72814FC143F8F7779C6EB9F7FA17299AEADB6889018501D289E4900F7E4331B99DEC4B5433AC7D329EEB6DD26545E96E55B874BE909".to_lowercase());
     }
 
+    #[test]
+    fn test_sha384_abc_hash() {
+        let result = sha(SHA384, "abc".bytes());
+        assert_eq!(hex::encode(result), "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7");
+    }
+
+    #[test]
+    fn test_sha384_twoblock_hash() {
+        let result = sha(SHA384, "abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu".bytes());
+        assert_eq!(hex::encode(result), "09330c33f71147e83d192fc782cd1b4753111b173b3b05d22fa08086e3b0f712fcc7c71a557e2db966c3e9fa91746039");
+    }
+
+    #[test]
+    fn test_sha384_empty_hash() {
+        let result = sha(SHA384, "".bytes());
+        assert_eq!(hex::encode(result), "38b060a751ac96384cd9327eb1b1e36a21fdb71114be07434c0cc7bf63f6e1da274edebfe76f65fbd51ad2f14898b95b");
+    }
+
+    #[test]
+    fn test_hasher_matches_sha_for_any_split() {
+        let msg: Vec<u8> = (0..300u32).map(|i| i as u8).collect();
+        for split in &[0, 1, 55, 56, 63, 64, 65, 127, 128, 129, 300] {
+            let mut h256 = Hasher::new(SHA256);
+            h256.update(&msg[..*split]);
+            h256.update(&msg[*split..]);
+            assert_eq!(h256.finish(), sha(SHA256, msg.iter().cloned()));
+
+            let mut h512 = Hasher::new(SHA512);
+            h512.update(&msg[..*split]);
+            h512.update(&msg[*split..]);
+            assert_eq!(h512.finish(), sha(SHA512, msg.iter().cloned()));
+        }
+    }
+
+    #[test]
+    fn test_hasher_byte_at_a_time() {
+        let mut hasher = Hasher::new(SHA256);
+        for b in "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq".bytes() {
+            hasher.update(&[b]);
+        }
+        assert_eq!(hex::encode(hasher.finish()), "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+    }
+
+    #[test]
+    fn test_multi_hasher_matches_single() {
+        let msg: Vec<u8> = (0..300u32).map(|i| i as u8).collect();
+        for split in &[0, 1, 63, 64, 65, 127, 128, 129, 300] {
+            let mut hasher = MultiHasher::new(&[Algorithm::Sha512, Algorithm::Sha256, Algorithm::Sha1, Algorithm::Sha384]);
+            hasher.update(&msg[..*split]);
+            hasher.update(&msg[*split..]);
+            assert_eq!(hasher.finish(), vec![
+                sha(SHA512, msg.iter().cloned()),
+                sha(SHA256, msg.iter().cloned()),
+                sha(SHA1, msg.iter().cloned()),
+                sha(SHA384, msg.iter().cloned()),
+            ]);
+        }
+        assert!(MultiHasher::new(&[]).finish().is_empty());
+    }
+
+    #[test]
+    fn test_busy_time() {
+        let mut hasher = MultiHasher::new(&[Algorithm::Sha512, Algorithm::Sha256]);
+        hasher.update(&[0u8; 1 << 16]);
+        assert!(hasher.busy().is_empty());
+
+        hasher.time();
+        hasher.update(&[0u8; 1 << 16]);
+        let busy = hasher.busy();
+        assert_eq!(busy.iter().map(|&(a, _)| a).collect::<Vec<_>>(), vec![Algorithm::Sha512, Algorithm::Sha256]);
+        assert!(busy.iter().all(|&(_, time)| time > Duration::ZERO));
+
+        let total = BusyTime::default();
+        total.add(&hasher);
+        total.add(&hasher);
+        assert_eq!(total.spent(Algorithm::Sha256), busy[1].1 * 2);
+        assert_eq!(total.spent(Algorithm::Sha384), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_finish_reset() {
+        let mut hasher = MultiHasher::new(&[Algorithm::Sha256, Algorithm::Sha512]);
+        hasher.update(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq");
+        hasher.finish_reset();
+        hasher.update(b"abc");
+        assert_eq!(hasher.finish_reset(), vec![sha(SHA256, "abc".bytes()), sha(SHA512, "abc".bytes())]);
+        assert_eq!(hasher.finish(), vec![sha(SHA256, "".bytes()), sha(SHA512, "".bytes())]);
+    }
+
+    #[test]
+    fn test_midstates() {
+        let msg: Vec<u8> = (0..300u32).map(|i| i as u8).collect();
+        let algorithms = [Algorithm::Sha256, Algorithm::Sha384, Algorithm::Sha512, Algorithm::Sha1];
+        for split in &[0, 1, 64, 129, 300] {
+            let mut hasher = MultiHasher::new(&algorithms);
+            hasher.update(&msg[..*split]);
+            let midstates = hasher.midstates();
+            assert_eq!(midstates[0].chaining.len(), 32);
+            assert_eq!(midstates[2].count, *split as u128);
+            assert_eq!(midstates[2].pending.len(), split % 128);
+            // just the five words SHA-1 has
+            assert_eq!(midstates[3].chaining.len(), 20);
+
+            let mut resumed = MultiHasher::from_midstates(&midstates).unwrap();
+            resumed.update(&msg[*split..]);
+            hasher.update(&msg[*split..]);
+            assert_eq!(resumed.finish(), hasher.finish());
+        }
+
+        let mut midstate = MultiHasher::new(&[Algorithm::Sha256]).midstates().remove(0);
+        midstate.count = 3;
+        assert!(MultiHasher::from_midstates(&[midstate.clone()]).is_none());
+        midstate.count = 0;
+        midstate.chaining.pop();
+        assert!(MultiHasher::from_midstates(&[midstate]).is_none());
+    }
+
+    #[test]
+    fn test_algorithm_from_name() {
+        assert_eq!(Algorithm::from_name("sha256"), Some(Algorithm::Sha256));
+        assert_eq!(Algorithm::from_name("SHA-384"), Some(Algorithm::Sha384));
+        assert_eq!(Algorithm::from_name("Sha512"), Some(Algorithm::Sha512));
+        assert_eq!(Algorithm::from_name("SHA-1"), Some(Algorithm::Sha1));
+        assert_eq!(Algorithm::from_name("md5"), None);
+    }
+
+    #[test]
+    fn test_algorithm_from_program_name() {
+        assert_eq!(Algorithm::from_program_name("sha256sum"), Some(Algorithm::Sha256));
+        assert_eq!(Algorithm::from_program_name("/usr/local/bin/sha512sum"), Some(Algorithm::Sha512));
+        assert_eq!(Algorithm::from_program_name("C:\\tools\\sha384sum.exe"), Some(Algorithm::Sha384));
+        assert_eq!(Algorithm::from_program_name("target/debug/hashsum"), None);
+        assert_eq!(Algorithm::from_program_name("sha256"), None);
+    }
 }
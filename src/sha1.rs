@@ -1,7 +1,78 @@
+// SHA-1, for checking files against old checksum lists and manifests, which often have
+// nothing newer, and for the RFC 8017 examples, which all use it. It is broken for
+// collisions, so it is never the default.
 
-const _K : [u32;80]= [
+use std::convert::TryInto;
+
+use crate::sha256::u32_to_u8;
+
+// The state is five words; the last three of the eight a ShaParams carries are left at zero.
+pub const H0: [u32;8] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0, 0, 0, 0];
+
+
+const K : [u32;80]= [
   0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999, 0x5a827999,
   0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1, 0x6ed9eba1,
   0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc, 0x8f1bbcdc,
   0xca62c1d6, 0xca62c1d6, 0xca62c1d6, 0xca62c1d6, 0xca62c1d6, 0xca62c1d6, 0xca62c1d6, 0xca62c1d6, 0xca62c1d6, 0xca62c1d6,0xca62c1d6, 0xca62c1d6, 0xca62c1d6, 0xca62c1d6, 0xca62c1d6,0xca62c1d6, 0xca62c1d6, 0xca62c1d6, 0xca62c1d6, 0xca62c1d6
   ];
+
+pub fn sha1_block(hash: [u32;8], m: &[u8]) -> [u32;8] {
+    let mut w: [u32;80] = [0;80];
+    m.chunks(4)
+        .enumerate()
+        .for_each(|(i, n)| w[i] = u32::from_be_bytes(n.try_into().unwrap()));
+    for t in 16..80 {
+        w[t] = (w[t-3] ^ w[t-8] ^ w[t-14] ^ w[t-16]).rotate_left(1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, ..] = hash;
+    for t in 0..80 {
+        let f = match t {
+            0..=19 => ch!(b, c, d),
+            40..=59 => maj!(b, c, d),
+            _ => b ^ c ^ d,
+        };
+        let temp = a.rotate_left(5)
+            .wrapping_add(f)
+            .wrapping_add(e)
+            .wrapping_add(K[t])
+            .wrapping_add(w[t]);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = temp;
+    }
+
+    [
+        a.wrapping_add(hash[0]),
+        b.wrapping_add(hash[1]),
+        c.wrapping_add(hash[2]),
+        d.wrapping_add(hash[3]),
+        e.wrapping_add(hash[4]),
+        0, 0, 0
+    ]
+}
+
+pub fn sha1_blocks(hash: [u32;8], m: &[u8]) -> [u32;8] {
+    m.chunks_exact(64).fold(hash, sha1_block)
+}
+
+pub fn u32_to_u8_160(wa: Vec<u32>) -> Vec<u8> {
+    u32_to_u8(wa[..5].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sha::{sha, SHA1};
+
+    extern crate hex;
+
+    #[test]
+    fn test_sha1() {
+        assert_eq!(hex::encode(sha(SHA1, b"abc".iter().cloned())), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hex::encode(sha(SHA1, b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq".iter().cloned())),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+    }
+}
@@ -5,6 +5,11 @@ pub const H0:[u64;8] = [
     0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
 ];
 
+pub const H0_384:[u64;8] = [
+    0xcbbb9d5dc1059ed8, 0x629a292a367cd507, 0x9159015a3070dd17, 0x152fecd8f70e5939,
+    0x67332667ffc00b31, 0x8eb44a8768581511, 0xdb0c2e0d64f98fa7, 0x47b5481dbefa4fa4,
+];
+
 const K:[u64;80] = [
     0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc,
     0x3956c25bf348b538, 0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
@@ -44,7 +49,7 @@ fn message_schedule(m: &[u8]) -> [u64;80] {
             .wrapping_add(w[t-16]);
     }
 
-    return w;
+    w
 }
 
 pub fn sha512_block(hash: [u64;8], m: &[u8]) -> [u64;8] {
@@ -75,7 +80,7 @@ pub fn sha512_block(hash: [u64;8], m: &[u8]) -> [u64;8] {
         a = t1.wrapping_add(t2);
     }
 
-    return [
+    [
         a.wrapping_add(hash[0]),
         b.wrapping_add(hash[1]),
         c.wrapping_add(hash[2]),
@@ -84,7 +89,50 @@ pub fn sha512_block(hash: [u64;8], m: &[u8]) -> [u64;8] {
         f.wrapping_add(hash[5]),
         g.wrapping_add(hash[6]),
         h.wrapping_add(hash[7])
-        ];
+        ]
+}
+
+// Compresses a run of whole blocks, with the state and the message schedule kept in place
+// across them rather than passed through sha512_block for each.
+pub fn sha512_blocks(hash: [u64;8], m: &[u8]) -> [u64;8] {
+    let mut hash = hash;
+    let mut w = [0u64;80];
+
+    for block in m.chunks_exact(128) {
+        for (w, word) in w.iter_mut().zip(block.chunks_exact(8)) {
+            *w = u64::from_be_bytes([word[0], word[1], word[2], word[3], word[4], word[5], word[6], word[7]]);
+        }
+        for t in 16..80 {
+            w[t] = sigma!(w[t-2], 19, 61, 6)
+                .wrapping_add(w[t-7])
+                .wrapping_add(sigma!(w[t-15], 1, 8, 7))
+                .wrapping_add(w[t-16]);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = hash;
+        for (&k, &w) in K.iter().zip(w.iter()) {
+            let t1 = h
+                .wrapping_add(Sigma!(e, 14, 18, 41))
+                .wrapping_add(ch!(e, f, g))
+                .wrapping_add(k)
+                .wrapping_add(w);
+            let t2 = Sigma!(a, 28, 34, 39).wrapping_add(maj!(a, b, c));
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (x, y) in hash.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *x = x.wrapping_add(y);
+        }
+    }
+
+    hash
 }
 
 pub fn u64_to_u8(wa: Vec<u64>)->Vec<u8> {
@@ -94,7 +142,12 @@ pub fn u64_to_u8(wa: Vec<u64>)->Vec<u8> {
         result.extend_from_slice(&w.to_be_bytes());
     }
 
-    return result;
+    result
+}
+
+// SHA-384 is SHA-512 with another H0, truncated to the first six words
+pub fn u64_to_u8_384(wa: Vec<u64>)->Vec<u8> {
+    u64_to_u8(wa[..6].to_vec())
 }
 
 
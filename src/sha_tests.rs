@@ -5,7 +5,7 @@ mod tests {
 
     extern crate hex;
 
-    use crate::sha::{sha, SHA256, SHA512};
+    use crate::sha::{sha, SHA256, SHA384, SHA512};
 
     // tests based on SHA byte test vectors: https://csrc.nist.gov/CSRC/media/Projects/Cryptographic-Algorithm-Validation-Program/documents/shs/shabytetestvectors.zip
 
@@ -430,4 +430,65 @@ mod tests {
     }
 
 
+
+    // A reader handing out one byte at a time, like a slow pipe.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    #[rstest(len,
+        case::empty(0),
+        case::short(3),
+        case::one_block(128),
+        case::several_blocks(1000),
+    )]
+    fn sha_sum_reader_short_reads_test(len: usize) {
+        let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+        assert_eq!(crate::sha_sum_reader(SHA256, Trickle(&data)).unwrap(), sha(SHA256, data.iter().cloned()));
+        assert_eq!(crate::sha_sum_reader(SHA512, Trickle(&data)).unwrap(), sha(SHA512, data.iter().cloned()));
+    }
+
+    // Around the size of the read buffer, where the large-buffer path changes reads.
+    #[rstest(len,
+        case::below(crate::READ_BUFFER_SIZE - 1),
+        case::exact(crate::READ_BUFFER_SIZE),
+        case::above(crate::READ_BUFFER_SIZE + 65),
+    )]
+    fn sha_sum_buffer_boundary_test(len: usize) {
+        let dir = crate::test_util::TempDir::new(&format!("buffer-boundary-{}", len));
+        let data: Vec<u8> = (0..len).map(|i| (i * 7) as u8).collect();
+        let file = dir.write("data", &data);
+
+        let expected = sha(SHA512, data.iter().cloned());
+        assert_eq!(crate::sha_sum(SHA512, &file).unwrap(), expected);
+        assert_eq!(crate::sha_sum_blockwise(SHA512, &file).unwrap(), expected);
+    }
+
+    #[rstest(len,
+        case::short(3),
+        case::buffer(crate::READ_BUFFER_SIZE),
+        case::above(crate::READ_BUFFER_SIZE + 1000),
+    )]
+    fn multi_sum_test(len: usize) {
+        use crate::sha::Algorithm;
+        let dir = crate::test_util::TempDir::new(&format!("multi-sum-{}", len));
+        let data: Vec<u8> = (0..len).map(|i| (i * 3) as u8).collect();
+        let file = dir.write("data", &data);
+
+        let algorithms = [Algorithm::Sha256, Algorithm::Sha512, Algorithm::Sha384];
+        let expected = |data: &[u8]| vec![sha(SHA256, data.iter().cloned()), sha(SHA512, data.iter().cloned()), sha(SHA384, data.iter().cloned())];
+        assert_eq!(crate::multi_sum_input(&algorithms, &file).unwrap(), (expected(&data), len as u64));
+
+        let short = &data[..len.min(1000)];
+        assert_eq!(crate::multi_sum_reader(&algorithms, Trickle(short)).unwrap(), (expected(short), short.len() as u64));
+    }
 }
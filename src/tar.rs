@@ -0,0 +1,471 @@
+// Reading tar archives as a stream, to hash the files inside without extracting them.
+// Understands ustar and pax archives and the GNU long name entries; nothing is buffered
+// but the header being read, and the content of an entry is read straight through.
+//
+// An archive is a sequence of 512 byte blocks: a header per entry, followed by its content
+// padded to a whole block, and two zero blocks at the end. Pax ('x' and 'g') and GNU ('L' and
+// 'K') entries carry names and sizes too long for the header, for the entry after them.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use crate::escape::{path_from_bytes, path_to_bytes};
+use crate::read_all;
+use crate::sha::{Algorithm, MultiHasher};
+
+pub const BLOCK_SIZE: usize = 512;
+
+// Pax headers and GNU long names larger than this are taken as damage, not read into memory.
+const MAX_METADATA_SIZE: u64 = 1 << 20;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntryKind {
+    File,
+    // the path linked to
+    HardLink(PathBuf),
+    Symlink(PathBuf),
+    Directory,
+    // devices, fifos and whatever else; the type flag
+    Other(u8),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub kind: EntryKind,
+}
+
+#[derive(Debug)]
+pub enum TarError {
+    Io(io::Error),
+    // the archive ends inside a header or an entry
+    Truncated,
+    // the offset of the header in the archive
+    BadChecksum(u64),
+    BadHeader(u64),
+    BadPax(u64),
+}
+
+impl From<io::Error> for TarError {
+    fn from(e: io::Error) -> TarError {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => TarError::Truncated,
+            _ => TarError::Io(e),
+        }
+    }
+}
+
+fn padding(size: u64) -> u64 {
+    (BLOCK_SIZE as u64 - size % BLOCK_SIZE as u64) % BLOCK_SIZE as u64
+}
+
+// A NUL terminated field, or all of it if there is no NUL.
+fn field(header: &[u8], start: usize, len: usize) -> &[u8] {
+    let field = &header[start..start + len];
+    field.iter().position(|&b| b == 0).map_or(field, |end| &field[..end])
+}
+
+// Octal, or base-256 (big endian, after a first byte with the high bit set) for values too
+// large for octal.
+fn numeric(header: &[u8], start: usize, len: usize) -> Option<u64> {
+    let bytes = &header[start..start + len];
+    if bytes[0] & 0x80 != 0 {
+        if bytes[0] & 0x40 != 0 {
+            return None;
+        }
+        return bytes[1..].iter().try_fold((bytes[0] & 0x3f) as u64, |n, &b| n.checked_mul(256).map(|n| n | b as u64));
+    }
+
+    let text = std::str::from_utf8(field(header, start, len)).ok()?.trim_matches(' ');
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+// The stored checksum is the sum of the header bytes with the checksum field as spaces;
+// some old archivers summed signed bytes.
+fn checksum_ok(header: &[u8]) -> bool {
+    let stored = match numeric(header, 148, 8) {
+        Some(stored) => stored as i64,
+        None => return false,
+    };
+    let blanked = |i: usize, b: u8| if (148..156).contains(&i) { b' ' } else { b };
+    let unsigned: i64 = header.iter().enumerate().map(|(i, &b)| blanked(i, b) as i64).sum();
+    let signed: i64 = header.iter().enumerate().map(|(i, &b)| blanked(i, b) as i8 as i64).sum();
+    stored == unsigned || stored == signed
+}
+
+// Pax records are "<length> <key>=<value>\n", the length counting the whole record.
+fn parse_pax(data: &[u8]) -> Option<HashMap<String, Vec<u8>>> {
+    let mut records = HashMap::new();
+    let mut rest = data;
+    while !rest.is_empty() && rest[0] != 0 {
+        let space = rest.iter().position(|&b| b == b' ')?;
+        let length: usize = std::str::from_utf8(&rest[..space]).ok()?.parse().ok()?;
+        if length <= space + 1 || length > rest.len() || rest[length - 1] != b'\n' {
+            return None;
+        }
+        let record = &rest[space + 1..length - 1];
+        let equals = record.iter().position(|&b| b == b'=')?;
+        let key = String::from_utf8(record[..equals].to_vec()).ok()?;
+        records.insert(key, record[equals + 1..].to_vec());
+        rest = &rest[length..];
+    }
+    Some(records)
+}
+
+pub struct TarReader<R> {
+    reader: R,
+    // where the reader is in the archive
+    offset: u64,
+    // of the content of the current entry, what is left to read, then the padding after it
+    remaining: u64,
+    padding: u64,
+    // from pax global headers, for all entries after them
+    global: HashMap<String, Vec<u8>>,
+    finished: bool,
+}
+
+impl<R: Read> TarReader<R> {
+    pub fn new(reader: R) -> TarReader<R> {
+        TarReader{reader, offset: 0, remaining: 0, padding: 0, global: HashMap::new(), finished: false}
+    }
+
+    fn skip(&mut self, count: u64) -> Result<(), TarError> {
+        let skipped = io::copy(&mut (&mut self.reader).take(count), &mut io::sink())?;
+        self.offset += skipped;
+        if skipped < count {
+            return Err(TarError::Truncated);
+        }
+        Ok(())
+    }
+
+    // None at the end of the archive, also when it just stops between entries.
+    fn read_header(&mut self) -> Result<Option<[u8; BLOCK_SIZE]>, TarError> {
+        let mut header = [0u8; BLOCK_SIZE];
+        let mut len = 0;
+        while len < BLOCK_SIZE {
+            match self.reader.read(&mut header[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(TarError::Io(e)),
+            }
+        }
+        self.offset += len as u64;
+        match len {
+            0 => Ok(None),
+            BLOCK_SIZE => Ok(Some(header)),
+            _ => Err(TarError::Truncated),
+        }
+    }
+
+    // The content of a pax or GNU entry.
+    fn read_metadata(&mut self, size: u64, header_offset: u64) -> Result<Vec<u8>, TarError> {
+        if size > MAX_METADATA_SIZE {
+            return Err(TarError::BadHeader(header_offset));
+        }
+        let mut data = vec![0u8; size as usize];
+        self.reader.read_exact(&mut data)?;
+        self.offset += size;
+        self.skip(padding(size))?;
+        Ok(data)
+    }
+
+    // The next entry; whatever of the content of the previous one wasn't read is skipped.
+    // The content of the entry is then read from the TarReader itself.
+    pub fn next_entry(&mut self) -> Result<Option<Entry>, TarError> {
+        if self.finished {
+            return Ok(None);
+        }
+        let rest = self.remaining + self.padding;
+        self.remaining = 0;
+        self.padding = 0;
+        self.skip(rest)?;
+
+        let mut pax = self.global.clone();
+        let mut long_name = None;
+        let mut long_link = None;
+
+        loop {
+            let header_offset = self.offset;
+            let header = match self.read_header()? {
+                Some(header) if header.iter().any(|&b| b != 0) => header,
+                _ => {
+                    self.finished = true;
+                    return Ok(None);
+                }
+            };
+            if !checksum_ok(&header) {
+                return Err(TarError::BadChecksum(header_offset));
+            }
+
+            let size = numeric(&header, 124, 12).ok_or(TarError::BadHeader(header_offset))?;
+            let flag = header[156];
+            match flag {
+                b'x' | b'g' => {
+                    let data = self.read_metadata(size, header_offset)?;
+                    let records = parse_pax(&data).ok_or(TarError::BadPax(header_offset))?;
+                    if flag == b'g' {
+                        self.global.extend(records.clone());
+                    }
+                    pax.extend(records);
+                    continue;
+                }
+                b'L' | b'K' => {
+                    let mut data = self.read_metadata(size, header_offset)?;
+                    if let Some(end) = data.iter().position(|&b| b == 0) {
+                        data.truncate(end);
+                    }
+                    if flag == b'L' { long_name = Some(data) } else { long_link = Some(data) }
+                    continue;
+                }
+                _ => (),
+            }
+
+            let is_ustar = &header[257..262] == b"ustar";
+            let name = pax.remove("path").or(long_name).unwrap_or_else(|| {
+                let prefix = if is_ustar { field(&header, 345, 155) } else { b"" };
+                let name = field(&header, 0, 100);
+                if prefix.is_empty() { name.to_vec() } else { [prefix, b"/", name].concat() }
+            });
+            let link = pax.remove("linkpath").or(long_link).unwrap_or_else(|| field(&header, 157, 100).to_vec());
+            let size = match pax.remove("size") {
+                Some(size) => std::str::from_utf8(&size).ok().and_then(|s| s.parse().ok()).ok_or(TarError::BadPax(header_offset))?,
+                None => size,
+            };
+
+            let kind = match flag {
+                b'0' | 0 | b'7' if name.ends_with(b"/") => EntryKind::Directory,
+                b'0' | 0 | b'7' => EntryKind::File,
+                b'1' => EntryKind::HardLink(path_from_bytes(&link)),
+                b'2' => EntryKind::Symlink(path_from_bytes(&link)),
+                b'5' => EntryKind::Directory,
+                flag => EntryKind::Other(flag),
+            };
+            // links and directories may carry a size, but no content
+            let content = match kind {
+                EntryKind::HardLink(_) | EntryKind::Symlink(_) | EntryKind::Directory => 0,
+                _ => size,
+            };
+            self.remaining = content;
+            self.padding = padding(content);
+            return Ok(Some(Entry{path: path_from_bytes(&name), size: content, kind}));
+        }
+    }
+}
+
+// Reads the content of the current entry.
+impl<R: Read> Read for TarReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            return Ok(0);
+        }
+        let max = std::cmp::min(buf.len() as u64, self.remaining) as usize;
+        let n = self.reader.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the archive ends inside an entry"));
+        }
+        self.remaining -= n as u64;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+// Where tar would extract an entry: leading slashes are dropped.
+pub fn extracted_path(path: &Path) -> PathBuf {
+    let bytes = path_to_bytes(path);
+    let start = bytes.iter().position(|&b| b != b'/').unwrap_or(bytes.len());
+    path_from_bytes(&bytes[start..])
+}
+
+// Hashes every regular file in the archive, in archive order, handing its path (as extracted),
+// digests and size to emit. Hard links get the digests of the file they link to, if that came
+// earlier in the archive. Directories, symbolic links and special files are skipped.
+pub fn hash_archive<R, F>(algorithms: &[Algorithm], reader: R, mut emit: F) -> Result<(), TarError>
+    where R: Read, F: FnMut(&Path, &[Vec<u8>], u64)
+{
+    let mut tar = TarReader::new(reader);
+    let mut files: HashMap<PathBuf, (Vec<Vec<u8>>, u64)> = HashMap::new();
+
+    while let Some(entry) = tar.next_entry()? {
+        let path = extracted_path(&entry.path);
+        match entry.kind {
+            EntryKind::File => {
+                let mut hasher = MultiHasher::new(algorithms);
+                let size = read_all(&mut tar, |data| hasher.update(data))?;
+                let digests = hasher.finish();
+                emit(&path, &digests, size);
+                files.insert(path, (digests, size));
+            }
+            EntryKind::HardLink(target) => {
+                if let Some((digests, size)) = files.get(&extracted_path(&target)).cloned() {
+                    emit(&path, &digests, size);
+                    files.insert(path, (digests, size));
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha::{sha, SHA256};
+
+    extern crate rstest;
+    use rstest::rstest;
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    // A ustar header, with prefix for names over 100 bytes.
+    fn header(name: &[u8], size: u64, flag: u8, link: &[u8]) -> Vec<u8> {
+        let (prefix, name) = if name.len() > 100 {
+            let split = name.iter().rposition(|&b| b == b'/').unwrap();
+            (&name[..split], &name[split + 1..])
+        } else {
+            (&b""[..], name)
+        };
+        let mut header = vec![0u8; BLOCK_SIZE];
+        header[..name.len()].copy_from_slice(name);
+        header[100..107].copy_from_slice(b"0000644");
+        header[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+        header[156] = flag;
+        header[157..157 + link.len()].copy_from_slice(link);
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[345..345 + prefix.len()].copy_from_slice(prefix);
+        set_checksum(&mut header);
+        header
+    }
+
+    fn set_checksum(header: &mut [u8]) {
+        header[148..156].copy_from_slice(b"        ");
+        let sum: u32 = header.iter().map(|&b| b as u32).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+    }
+
+    fn entry(name: &[u8], flag: u8, link: &[u8], content: &[u8]) -> Vec<u8> {
+        let mut data = header(name, content.len() as u64, flag, link);
+        data.extend_from_slice(content);
+        data.resize(data.len() + padding(content.len() as u64) as usize, 0);
+        data
+    }
+
+    fn pax_record(key: &str, value: &str) -> String {
+        let body = format!(" {}={}\n", key, value);
+        let digits = (1..).find(|&d| (d + body.len()).to_string().len() == d).unwrap();
+        format!("{}{}", digits + body.len(), body)
+    }
+
+    fn archive(entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = entries.concat();
+        data.extend_from_slice(&[0u8; 2 * BLOCK_SIZE]);
+        data
+    }
+
+    fn hashes(data: &[u8]) -> Result<Vec<(String, String, u64)>, TarError> {
+        let mut result = Vec::new();
+        hash_archive(&[Algorithm::Sha256], data, |path, digests, size|
+            result.push((path.to_string_lossy().into_owned(), hex::encode(&digests[0]), size)))?;
+        Ok(result)
+    }
+
+    #[test]
+    fn test_pax_record() {
+        assert_eq!(pax_record("path", "a"), "9 path=a\n");
+        assert_eq!(pax_record("path", "abcdefgh"), "17 path=abcdefgh\n");
+        assert_eq!(parse_pax(b"9 path=a\n17 path=abcdefgh\n").unwrap()["path"], b"abcdefgh");
+        assert_eq!(parse_pax(b"12 path=a\n"), None);
+    }
+
+    #[test]
+    fn test_entries() {
+        let big: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+        let data = archive(&[
+            entry(b"dir/", b'5', b"", b""),
+            entry(b"dir/abc.txt", b'0', b"", b"abc"),
+            entry(b"dir/big", 0, b"", &big),
+            entry(b"dir/link", b'2', b"abc.txt", b""),
+            entry(b"dir/hard", b'1', b"dir/abc.txt", b""),
+            entry(b"/abs/empty", b'0', b"", b""),
+        ]);
+
+        let mut tar = TarReader::new(&data[..]);
+        let mut kinds = Vec::new();
+        while let Some(entry) = tar.next_entry().unwrap() {
+            kinds.push((entry.path.to_string_lossy().into_owned(), entry.kind));
+        }
+        assert_eq!(kinds[0], ("dir/".to_string(), EntryKind::Directory));
+        assert_eq!(kinds[3], ("dir/link".to_string(), EntryKind::Symlink(PathBuf::from("abc.txt"))));
+        assert_eq!(kinds[4], ("dir/hard".to_string(), EntryKind::HardLink(PathBuf::from("dir/abc.txt"))));
+
+        assert_eq!(hashes(&data).unwrap(), vec![
+            ("dir/abc.txt".to_string(), ABC_SHA256.to_string(), 3),
+            ("dir/big".to_string(), hex::encode(sha(SHA256, big.iter().cloned())), 1000),
+            ("dir/hard".to_string(), ABC_SHA256.to_string(), 3),
+            ("abs/empty".to_string(), hex::encode(sha(SHA256, "".bytes())), 0),
+        ]);
+    }
+
+    #[test]
+    fn test_long_names() {
+        let long = format!("{}/{}", "d".repeat(120), "f".repeat(90));
+        let longer = format!("{}/file", "x".repeat(300));
+        let pax = [pax_record("path", &longer), pax_record("mtime", "1700000000.5")].concat();
+        let gnu = format!("{}\0", "g".repeat(200));
+
+        let data = archive(&[
+            entry(long.as_bytes(), b'0', b"", b"abc"),
+            entry(b"PaxHeaders/file", b'x', b"", pax.as_bytes()),
+            entry(b"truncated", b'0', b"", b"abc"),
+            entry(b"././@LongLink", b'L', b"", gnu.as_bytes()),
+            entry(b"ggg", b'0', b"", b"abc"),
+        ]);
+        let names: Vec<String> = hashes(&data).unwrap().into_iter().map(|(name, _, _)| name).collect();
+        assert_eq!(names, vec![long, longer, "g".repeat(200)]);
+    }
+
+    #[test]
+    fn test_pax_size_and_global() {
+        let mut data = entry(b"pax_global_header", b'g', b"", pax_record("comment", "release").as_bytes());
+        data.extend(entry(b"PaxHeaders/big", b'x', b"", pax_record("size", "3").as_bytes()));
+        // the header says 0; the pax size wins
+        data.extend(header(b"big", 0, b'0', b""));
+        data.extend_from_slice(b"abc");
+        data.resize(data.len() + BLOCK_SIZE - 3, 0);
+        assert_eq!(hashes(&archive(&[data])).unwrap(), vec![("big".to_string(), ABC_SHA256.to_string(), 3)]);
+    }
+
+    #[test]
+    fn test_base256_size() {
+        let mut header = vec![0u8; BLOCK_SIZE];
+        header[124] = 0x80;
+        header[130..136].copy_from_slice(&[0, 1, 0, 0, 0, 3]);
+        assert_eq!(numeric(&header, 124, 12), Some((1 << 32) + 3));
+    }
+
+    #[rstest(damage, expected,
+        case::bad_checksum(|data: &mut Vec<u8>| data[0] = b'x', "BadChecksum(0)"),
+        case::truncated_content(|data: &mut Vec<u8>| data.truncate(BLOCK_SIZE + 2), "Truncated"),
+        case::truncated_header(|data: &mut Vec<u8>| data.truncate(100), "Truncated"),
+        case::bad_size(|data: &mut Vec<u8>| { data[124] = b'9'; set_checksum(&mut data[..BLOCK_SIZE]) }, "BadHeader(0)"),
+    )]
+    fn test_damaged(damage: fn(&mut Vec<u8>), expected: &str) {
+        let mut data = archive(&[entry(b"abc.txt", b'0', b"", b"abc")]);
+        damage(&mut data);
+        assert_eq!(format!("{:?}", hashes(&data).unwrap_err()), expected);
+    }
+
+    #[test]
+    fn test_without_end_blocks() {
+        let data = entry(b"abc.txt", b'0', b"", b"abc");
+        assert_eq!(hashes(&data).unwrap().len(), 1);
+    }
+}
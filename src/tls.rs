@@ -0,0 +1,261 @@
+// The hashing parts of TLS:
+// * the TLS 1.2 PRF, P_<hash> (RFC 5246, section 5)
+// * the TLS 1.3 key schedule: HKDF-Expand-Label, Derive-Secret and the secrets chain (RFC 8446, section 7.1)
+
+use crate::hmac::Hmac;
+use crate::kdf::{hkdf_expand, hkdf_extract, KdfError};
+use crate::sha::{Hasher, ShaParams};
+
+pub fn p_hash<T: Copy>(params: ShaParams<T>, secret: &[u8], seed: &[u8], out_len: usize) -> Vec<u8> {
+    let keyed = Hmac::new(params, secret);
+    let mut result = Vec::with_capacity(out_len + params.digest_size);
+
+    // A(0) = seed, A(i) = HMAC_hash(secret, A(i-1))
+    let mut a = seed.to_vec();
+    while result.len() < out_len {
+        let mut mac = keyed.clone();
+        mac.update(&a);
+        a = mac.finish();
+
+        let mut mac = keyed.clone();
+        mac.update(&a);
+        mac.update(seed);
+        result.extend(mac.finish());
+    }
+    result.truncate(out_len);
+
+    result
+}
+
+// PRF(secret, label, seed) = P_<hash>(secret, label + seed); use SHA256 or SHA384 as the cipher suite says.
+pub fn prf_tls12<T: Copy>(params: ShaParams<T>, secret: &[u8], label: &[u8], seed: &[u8], out_len: usize) -> Vec<u8> {
+    let mut label_seed = label.to_vec();
+    label_seed.extend_from_slice(seed);
+    p_hash(params, secret, &label_seed, out_len)
+}
+
+// The running hash of the handshake messages. Snapshots can be taken at any point, as
+// Derive-Secret needs the hash of different prefixes of the same handshake.
+#[derive(Clone)]
+pub struct Transcript<T> {
+    hasher: Hasher<T>,
+}
+
+impl<T: Copy> Transcript<T> {
+    pub fn new(params: ShaParams<T>) -> Transcript<T> {
+        Transcript{hasher: Hasher::new(params)}
+    }
+
+    // msg is a complete handshake message, including its 4 byte header
+    pub fn update(&mut self, msg: &[u8]) {
+        self.hasher.update(msg);
+    }
+
+    pub fn hash(&self) -> Vec<u8> {
+        self.hasher.clone().finish()
+    }
+}
+
+pub fn hkdf_expand_label<T: Copy>(params: ShaParams<T>, secret: &[u8], label: &[u8], context: &[u8], out_len: usize) -> Result<Vec<u8>, KdfError> {
+    let full_label_len = 6 + label.len();
+    if full_label_len > 255 {
+        return Err(KdfError::LabelTooLong);
+    }
+    if context.len() > 255 {
+        return Err(KdfError::ContextTooLong);
+    }
+    if out_len > u16::MAX as usize {
+        return Err(KdfError::OutputTooLong);
+    }
+
+    // struct { uint16 length; opaque label<7..255>; opaque context<0..255>; } HkdfLabel;
+    let mut hkdf_label = (out_len as u16).to_be_bytes().to_vec();
+    hkdf_label.push(full_label_len as u8);
+    hkdf_label.extend_from_slice(b"tls13 ");
+    hkdf_label.extend_from_slice(label);
+    hkdf_label.push(context.len() as u8);
+    hkdf_label.extend_from_slice(context);
+
+    hkdf_expand(params, secret, &hkdf_label, out_len)
+}
+
+// Derive-Secret(Secret, Label, Messages), with the transcript already hashed
+pub fn derive_secret<T: Copy>(params: ShaParams<T>, secret: &[u8], label: &[u8], transcript_hash: &[u8]) -> Result<Vec<u8>, KdfError> {
+    hkdf_expand_label(params, secret, label, transcript_hash, params.digest_size)
+}
+
+// Walks the Early Secret -> Handshake Secret -> Master Secret chain. Absent inputs
+// (no PSK, no (EC)DHE) are replaced by a string of zeros as the RFC prescribes.
+pub struct KeySchedule<T> {
+    params: ShaParams<T>,
+    secret: Vec<u8>,
+}
+
+impl<T: Copy> KeySchedule<T> {
+    pub fn new(params: ShaParams<T>, psk: Option<&[u8]>) -> KeySchedule<T> {
+        let zeros = vec![0u8; params.digest_size];
+        let secret = hkdf_extract(params, &zeros, psk.unwrap_or(&zeros));
+        KeySchedule{params, secret}
+    }
+
+    pub fn secret(&self) -> &[u8] {
+        &self.secret
+    }
+
+    // Moves on to the next stage: Handshake Secret with the (EC)DHE shared secret, then Master Secret with None.
+    pub fn advance(&mut self, ikm: Option<&[u8]>) {
+        let zeros = vec![0u8; self.params.digest_size];
+        let empty_hash = Transcript::new(self.params).hash();
+        let derived = derive_secret(self.params, &self.secret, b"derived", &empty_hash).unwrap();
+        self.secret = hkdf_extract(self.params, &derived, ikm.unwrap_or(&zeros));
+    }
+
+    pub fn derive(&self, label: &[u8], transcript: &Transcript<T>) -> Result<Vec<u8>, KdfError> {
+        derive_secret(self.params, &self.secret, label, &transcript.hash())
+    }
+}
+
+// The write key and IV for a traffic secret
+pub fn traffic_key_iv<T: Copy>(params: ShaParams<T>, traffic_secret: &[u8], key_len: usize, iv_len: usize) -> Result<(Vec<u8>, Vec<u8>), KdfError> {
+    let key = hkdf_expand_label(params, traffic_secret, b"key", &[], key_len)?;
+    let iv = hkdf_expand_label(params, traffic_secret, b"iv", &[], iv_len)?;
+    Ok((key, iv))
+}
+
+// verify_data for the Finished message, from the sender's handshake traffic secret
+pub fn finished_verify_data<T: Copy>(params: ShaParams<T>, base_key: &[u8], transcript_hash: &[u8]) -> Result<Vec<u8>, KdfError> {
+    let finished_key = hkdf_expand_label(params, base_key, b"finished", &[], params.digest_size)?;
+    let mut mac = Hmac::new(params, &finished_key);
+    mac.update(transcript_hash);
+    Ok(mac.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha::{sha, SHA256, SHA384};
+
+    extern crate hex;
+
+    fn unhex(s: &str) -> Vec<u8> {
+        hex::decode(s).unwrap()
+    }
+
+    // the commonly used TLS 1.2 PRF test vectors (posted to the IETF TLS list, and used by most implementations)
+    #[test]
+    fn test_prf_tls12_sha256() {
+        let result = prf_tls12(SHA256, &unhex("9bbe436ba940f017b17652849a71db35"), b"test label", &unhex("a0ba9f936cda311827a6f796ffd5198c"), 100);
+        assert_eq!(hex::encode(result), "e3f229ba727be17b8d122620557cd453c2aab21d07c3d495329b52d4e61edb5a6b301791e90d35c9c9a46b4e14baf9af0fa022f7077def17abfd3797c0564bab4fbc91666e9def9b97fce34f796789baa48082d122ee42c5a72e5a5110fff70187347b66");
+    }
+
+    #[test]
+    fn test_prf_tls12_sha384() {
+        let result = prf_tls12(SHA384, &unhex("b80b733d6ceefcdc71566ea48e5567df"), b"test label", &unhex("cd665cf6a8447dd6ff8b27555edb7465"), 148);
+        assert_eq!(hex::encode(result), "7b0c18e9ced410ed1804f2cfa34a336a1c14dffb4900bb5fd7942107e81c83cde9ca0faa60be9fe34f82b1233c9146a0e534cb400fed2700884f9dc236f80edd8bfa961144c9e8d792eca722a7b32fc3d416d473ebc2c5fd4abfdad05d9184259b5bf8cd4d90fa0d31e2dec479e4f1a26066f2eea9a69236a3e52655c9e9aee691c8f3a26854308d5eaa3be85e0990703d73e56f");
+    }
+
+    #[test]
+    fn test_transcript_snapshots() {
+        let mut transcript = Transcript::new(SHA256);
+        assert_eq!(transcript.hash(), sha(SHA256, "".bytes()));
+        transcript.update(b"client hello");
+        assert_eq!(transcript.hash(), sha(SHA256, "client hello".bytes()));
+        transcript.update(b"server hello");
+        assert_eq!(transcript.hash(), sha(SHA256, "client helloserver hello".bytes()));
+    }
+
+    // RFC 8448, section 3 (simple 1-RTT handshake). The ClientHello and ServerHello are hashed from the trace's
+    // messages; the later transcript hashes are taken from the trace, as it doesn't list the certificate in full.
+    const CLIENT_HELLO: &str = "010000c00303cb34ecb1e78163ba1c38c6dacb196a6dffa21a8d9912ec18a2ef6283024dece7000006130113031302010000910000000b0009000006736572766572ff01000100000a00140012001d0017001800190100010101020103010400230000003300260024001d002099381de560e4bd43d23d8e435a7dbafeb3c06e51c13cae4d5413691e529aaf2c002b0003020304000d0020001e040305030603020308040805080604010501060102010402050206020202002d00020101001c00024001";
+    const SERVER_HELLO: &str = "020000560303a6af06a4121860dc5e6e60249cd34c95930c8ac5cb1434dac155772ed3e2692800130100002e00330024001d0020c9828876112095fe66762bdbf7c672e156d6cc253b833df1dd69b1b04e751f0f002b00020304";
+    const ECDHE_SHARED: &str = "8bd4054fb55b9d63fdfbacf9f04b9f0d35e6d63f537563efd46272900f89492d";
+    const HASH_CH_SH: &str = "860c06edc07858ee8e78f0e7428c58edd6b43f2ca3e6e95f02ed063cf0e1cad8";
+    const HASH_CH_CV: &str = "edb7725fa7a3473b031ec8ef65a2485493900138a2b91291407d7951a06110ed";
+    const HASH_CH_CF: &str = "209145a96ee8e2a122ff810047cc952684658d6049e86429426db87c54ad143d";
+
+    #[test]
+    fn test_rfc8448_secrets_chain() {
+        let mut schedule = KeySchedule::new(SHA256, None);
+        assert_eq!(hex::encode(schedule.secret()), "33ad0a1c607ec03b09e6cd9893680ce210adf300aa1f2660e1b22e10f170f92a");
+
+        schedule.advance(Some(&unhex(ECDHE_SHARED)));
+        assert_eq!(hex::encode(schedule.secret()), "1dc826e93606aa6fdc0aadc12f741b01046aa6b99f691ed221a9f0ca043fbeac");
+
+        schedule.advance(None);
+        assert_eq!(hex::encode(schedule.secret()), "18df06843d13a08bf2a449844c5f8a478001bc4d4c627984d5a41da8d0402919");
+    }
+
+    #[test]
+    fn test_rfc8448_derived() {
+        let early = unhex("33ad0a1c607ec03b09e6cd9893680ce210adf300aa1f2660e1b22e10f170f92a");
+        let empty_hash = Transcript::new(SHA256).hash();
+        let derived = derive_secret(SHA256, &early, b"derived", &empty_hash).unwrap();
+        assert_eq!(hex::encode(derived), "6f2615a108c702c5678f54fc9dbab69716c076189c48250cebeac3576c3611ba");
+    }
+
+    #[test]
+    fn test_rfc8448_handshake_traffic() {
+        let handshake = unhex("1dc826e93606aa6fdc0aadc12f741b01046aa6b99f691ed221a9f0ca043fbeac");
+        let client = derive_secret(SHA256, &handshake, b"c hs traffic", &unhex(HASH_CH_SH)).unwrap();
+        assert_eq!(hex::encode(client), "b3eddb126e067f35a780b3abf45e2d8f3b1a950738f52e9600746a0e27a55a21");
+
+        let server = derive_secret(SHA256, &handshake, b"s hs traffic", &unhex(HASH_CH_SH)).unwrap();
+        assert_eq!(hex::encode(&server), "b67b7d690cc16c4e75e54213cb2d37b4e9c912bcded9105d42befd59d391ad38");
+
+        let (key, iv) = traffic_key_iv(SHA256, &server, 16, 12).unwrap();
+        assert_eq!(hex::encode(key), "3fce516009c21727d0f2e4e86ee403bc");
+        assert_eq!(hex::encode(iv), "5d313eb2671276ee13000b30");
+    }
+
+    #[test]
+    fn test_rfc8448_hello_transcript() {
+        let mut transcript = Transcript::new(SHA256);
+        transcript.update(&unhex(CLIENT_HELLO));
+        transcript.update(&unhex(SERVER_HELLO));
+        assert_eq!(hex::encode(transcript.hash()), HASH_CH_SH);
+
+        let mut schedule = KeySchedule::new(SHA256, None);
+        schedule.advance(Some(&unhex(ECDHE_SHARED)));
+        let client = schedule.derive(b"c hs traffic", &transcript).unwrap();
+        assert_eq!(hex::encode(client), "b3eddb126e067f35a780b3abf45e2d8f3b1a950738f52e9600746a0e27a55a21");
+        let server = schedule.derive(b"s hs traffic", &transcript).unwrap();
+        assert_eq!(hex::encode(server), "b67b7d690cc16c4e75e54213cb2d37b4e9c912bcded9105d42befd59d391ad38");
+    }
+
+    #[test]
+    fn test_traffic_key_iv_too_long() {
+        assert_eq!(traffic_key_iv(SHA256, &[0; 32], 9000, 12), Err(KdfError::OutputTooLong));
+        assert_eq!(traffic_key_iv(SHA256, &[0; 32], 16, 9000), Err(KdfError::OutputTooLong));
+    }
+
+    #[test]
+    fn test_rfc8448_server_finished() {
+        let server_hs = unhex("b67b7d690cc16c4e75e54213cb2d37b4e9c912bcded9105d42befd59d391ad38");
+        let finished_key = hkdf_expand_label(SHA256, &server_hs, b"finished", &[], 32).unwrap();
+        assert_eq!(hex::encode(finished_key), "008d3b66f816ea559f96b537e885c31fc068bf492c652f01f288a1d8cdc19fc8");
+
+        let verify_data = finished_verify_data(SHA256, &server_hs, &unhex(HASH_CH_CV)).unwrap();
+        assert_eq!(hex::encode(verify_data), "9b9b141d906337fbd2cbdce71df4deda4ab42c309572cb7fffee5454b78f0718");
+    }
+
+    #[test]
+    fn test_rfc8448_resumption_master() {
+        let master = unhex("18df06843d13a08bf2a449844c5f8a478001bc4d4c627984d5a41da8d0402919");
+        let resumption = derive_secret(SHA256, &master, b"res master", &unhex(HASH_CH_CF)).unwrap();
+        assert_eq!(hex::encode(resumption), "7df235f2031d2a051287d02b0241b0bfdaf86cc856231f2d5aba46c434ec196c");
+    }
+
+    #[test]
+    fn test_expand_label_too_long() {
+        assert_eq!(hkdf_expand_label(SHA256, &[0; 32], b"key", &[], 65536), Err(KdfError::OutputTooLong));
+    }
+
+    #[test]
+    fn test_expand_label_long_label_and_context() {
+        // "tls13 " takes 6 of the 255 bytes the label may have
+        assert!(hkdf_expand_label(SHA256, &[0; 32], &[b'x'; 249], &[0; 255], 32).is_ok());
+        assert_eq!(hkdf_expand_label(SHA256, &[0; 32], &[b'x'; 250], &[], 32), Err(KdfError::LabelTooLong));
+        assert_eq!(hkdf_expand_label(SHA256, &[0; 32], b"key", &[0; 256], 32), Err(KdfError::ContextTooLong));
+        assert_eq!(derive_secret(SHA256, &[0; 32], &[b'x'; 250], &[0; 32]), Err(KdfError::LabelTooLong));
+    }
+}
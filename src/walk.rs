@@ -0,0 +1,210 @@
+// Walking directory trees for the recursive mode of the checksum binaries.
+// Entries are visited in sorted order, so the same tree always gives the same list.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::glob::Pattern;
+use crate::is_stdin;
+
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct WalkOptions {
+    pub follow_symlinks: bool,
+    // Files are taken if they match one of these; all of them if empty.
+    pub include: Vec<Pattern>,
+    // Files and directories matching one of these are skipped.
+    pub exclude: Vec<Pattern>,
+}
+
+#[derive(Debug)]
+pub enum WalkWarning {
+    SpecialFile(PathBuf),
+    SymlinkLoop(PathBuf),
+    Unreadable(PathBuf, io::Error),
+}
+
+#[derive(Default, Debug)]
+pub struct Walk {
+    pub files: Vec<PathBuf>,
+    pub warnings: Vec<WalkWarning>,
+}
+
+// Roots that are files are taken as they are; directories are walked. Symlinks given as
+// roots are always followed, those found during the walk only with follow_symlinks.
+pub fn walk(roots: &[PathBuf], options: &WalkOptions) -> Walk {
+    let mut result = Walk::default();
+
+    for root in roots {
+        if is_stdin(root) {
+            result.files.push(root.clone());
+            continue;
+        }
+
+        match fs::metadata(root) {
+            Ok(meta) if meta.is_dir() => {
+                let mut ancestors = Vec::new();
+                walk_dir(root, "", options, &mut ancestors, &mut result);
+            }
+            Ok(meta) if meta.is_file() => result.files.push(root.clone()),
+            Ok(_) => result.warnings.push(WalkWarning::SpecialFile(root.clone())),
+            Err(e) => result.warnings.push(WalkWarning::Unreadable(root.clone(), e)),
+        }
+    }
+
+    result
+}
+
+fn sorted_entries(dir: &Path) -> io::Result<Vec<fs::DirEntry>> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    Ok(entries)
+}
+
+// relative is the path of dir below the root, with / as separator; it is what patterns are matched against.
+fn walk_dir(dir: &Path, relative: &str, options: &WalkOptions, ancestors: &mut Vec<PathBuf>, result: &mut Walk) {
+    // only followed symlinks can lead back into a directory we are already in
+    if options.follow_symlinks {
+        match fs::canonicalize(dir) {
+            Ok(canonical) if ancestors.contains(&canonical) => {
+                result.warnings.push(WalkWarning::SymlinkLoop(dir.to_path_buf()));
+                return;
+            }
+            Ok(canonical) => ancestors.push(canonical),
+            Err(e) => {
+                result.warnings.push(WalkWarning::Unreadable(dir.to_path_buf(), e));
+                return;
+            }
+        }
+    }
+
+    let entries = match sorted_entries(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            result.warnings.push(WalkWarning::Unreadable(dir.to_path_buf(), e));
+            if options.follow_symlinks {
+                ancestors.pop();
+            }
+            return;
+        }
+    };
+
+    for entry in entries {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let child_relative = if relative.is_empty() { name.to_string() } else { format!("{}/{}", relative, name) };
+
+        if options.exclude.iter().any(|p| p.matches(&child_relative)) {
+            continue;
+        }
+
+        let meta = match entry.file_type() {
+            Ok(t) if t.is_symlink() && !options.follow_symlinks => continue,
+            Ok(t) if t.is_symlink() => fs::metadata(&path),
+            Ok(_) => fs::symlink_metadata(&path),
+            Err(e) => Err(e),
+        };
+
+        match meta {
+            Ok(meta) if meta.is_dir() => walk_dir(&path, &child_relative, options, ancestors, result),
+            Ok(meta) if meta.is_file() => {
+                if options.include.is_empty() || options.include.iter().any(|p| p.matches(&child_relative)) {
+                    result.files.push(path);
+                }
+            }
+            Ok(_) => result.warnings.push(WalkWarning::SpecialFile(path)),
+            Err(e) => result.warnings.push(WalkWarning::Unreadable(path, e)),
+        }
+    }
+
+    if options.follow_symlinks {
+        ancestors.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn relative_names(dir: &TempDir, walk: &Walk) -> Vec<String> {
+        walk.files.iter()
+            .map(|f| f.strip_prefix(dir.path()).unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    fn sample_tree(name: &str) -> TempDir {
+        let dir = TempDir::new(name);
+        dir.write("b.txt", b"b");
+        dir.write("a.o", b"a");
+        dir.write("sub/z.txt", b"z");
+        dir.write("sub/deeper/c.txt", b"c");
+        dir.write("Sub2/y.o", b"y");
+        dir
+    }
+
+    #[test]
+    fn test_walk_sorted() {
+        let dir = sample_tree("walk-sorted");
+        let walk = walk(&[dir.path().to_path_buf()], &WalkOptions::default());
+        assert_eq!(relative_names(&dir, &walk), vec!["Sub2/y.o", "a.o", "b.txt", "sub/deeper/c.txt", "sub/z.txt"]);
+        assert!(walk.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_walk_include_exclude() {
+        let dir = sample_tree("walk-patterns");
+        let options = WalkOptions{
+            include: vec![Pattern::new("*.txt").unwrap()],
+            exclude: vec![Pattern::new("deeper").unwrap()],
+            ..Default::default()
+        };
+        let walk = walk(&[dir.path().to_path_buf()], &options);
+        assert_eq!(relative_names(&dir, &walk), vec!["b.txt", "sub/z.txt"]);
+
+        let options = WalkOptions{exclude: vec![Pattern::new("sub/**").unwrap()], ..Default::default()};
+        let walk = super::walk(&[dir.path().to_path_buf()], &options);
+        assert_eq!(relative_names(&dir, &walk), vec!["Sub2/y.o", "a.o", "b.txt"]);
+    }
+
+    #[test]
+    fn test_walk_file_roots_and_missing() {
+        let dir = sample_tree("walk-roots");
+        let roots = vec![dir.path().join("b.txt"), dir.path().join("missing"), dir.path().join("a.o")];
+        let walk = walk(&roots, &WalkOptions::default());
+        assert_eq!(relative_names(&dir, &walk), vec!["b.txt", "a.o"]);
+        assert!(matches!(&walk.warnings[..], [WalkWarning::Unreadable(p, _)] if p.ends_with("missing")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_symlinks() {
+        let dir = sample_tree("walk-symlinks");
+        std::os::unix::fs::symlink(dir.path().join("sub"), dir.path().join("link")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("sub/loop")).unwrap();
+
+        let walk = walk(&[dir.path().to_path_buf()], &WalkOptions::default());
+        assert_eq!(relative_names(&dir, &walk), vec!["Sub2/y.o", "a.o", "b.txt", "sub/deeper/c.txt", "sub/z.txt"]);
+
+        let options = WalkOptions{follow_symlinks: true, ..Default::default()};
+        let walk = super::walk(&[dir.path().to_path_buf()], &options);
+        assert_eq!(relative_names(&dir, &walk), vec!["Sub2/y.o", "a.o", "b.txt",
+            "link/deeper/c.txt", "link/z.txt", "sub/deeper/c.txt", "sub/z.txt"]);
+        assert_eq!(walk.warnings.len(), 2);
+        assert!(walk.warnings.iter().all(|w| matches!(w, WalkWarning::SymlinkLoop(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_special_file() {
+        let dir = TempDir::new("walk-special");
+        dir.write("a", b"a");
+        let socket = std::os::unix::net::UnixListener::bind(dir.path().join("socket")).unwrap();
+
+        let walk = walk(&[dir.path().to_path_buf()], &WalkOptions::default());
+        assert_eq!(relative_names(&dir, &walk), vec!["a"]);
+        assert!(matches!(&walk.warnings[..], [WalkWarning::SpecialFile(p)] if p.ends_with("socket")));
+        drop(socket);
+    }
+}
@@ -0,0 +1,268 @@
+// Watching files to hash them again whenever they have been written, for a live view of the
+// outputs of a build. Uses inotify, so Linux only; the few calls needed are declared here
+// rather than pulling in a crate for them.
+//
+// The directories holding the files are watched, not the files themselves, so that a file
+// replaced by a rename, or deleted and written anew, is still seen. A file counts as written
+// when a writer closes it (IN_CLOSE_WRITE) or another file is renamed over it (IN_MOVED_TO).
+
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::read_all;
+use crate::sha::MultiHasher;
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::ffi::CString;
+    use std::fs::File;
+    use std::io;
+    use std::os::raw::{c_char, c_int, c_short, c_ulong};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    use std::path::Path;
+    use std::time::Duration;
+
+    pub const IN_CLOSE_WRITE: u32 = 0x8;
+    pub const IN_MOVED_TO: u32 = 0x80;
+    pub const IN_Q_OVERFLOW: u32 = 0x4000;
+    const IN_CLOEXEC: c_int = 0o2000000;
+    const POLLIN: c_short = 1;
+
+    #[repr(C)]
+    struct PollFd {
+        fd: c_int,
+        events: c_short,
+        revents: c_short,
+    }
+
+    extern "C" {
+        fn inotify_init1(flags: c_int) -> c_int;
+        fn inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int;
+        fn poll(fds: *mut PollFd, nfds: c_ulong, timeout: c_int) -> c_int;
+    }
+
+    // The inotify instance, read like a file.
+    pub fn init() -> io::Result<File> {
+        let fd = unsafe { inotify_init1(IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(unsafe { File::from_raw_fd(fd) })
+    }
+
+    pub fn add_watch(inotify: &File, dir: &Path, mask: u32) -> io::Result<i32> {
+        let path = CString::new(dir.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let wd = unsafe { inotify_add_watch(inotify.as_raw_fd(), path.as_ptr(), mask) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(wd)
+    }
+
+    // Whether there are events to read, waiting at most timeout, or for ever if None.
+    pub fn wait(inotify: &File, timeout: Option<Duration>) -> io::Result<bool> {
+        let mut fd = PollFd{fd: inotify.as_raw_fd(), events: POLLIN, revents: 0};
+        let timeout = timeout.map_or(-1, |t| std::cmp::min(t.as_millis(), c_int::MAX as u128) as c_int);
+        loop {
+            match unsafe { poll(&mut fd, 1, timeout) } {
+                n if n >= 0 => return Ok(n > 0),
+                _ => {
+                    let e = io::Error::last_os_error();
+                    if e.kind() != io::ErrorKind::Interrupted {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::fs::File;
+    use std::io;
+    use std::path::Path;
+    use std::time::Duration;
+
+    pub const IN_CLOSE_WRITE: u32 = 0x8;
+    pub const IN_MOVED_TO: u32 = 0x80;
+    pub const IN_Q_OVERFLOW: u32 = 0x4000;
+
+    pub fn init() -> io::Result<File> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "watching files needs inotify, which only Linux has"))
+    }
+
+    pub fn add_watch(_inotify: &File, _dir: &Path, _mask: u32) -> io::Result<i32> {
+        unreachable!("there is no inotify instance to add to")
+    }
+
+    pub fn wait(_inotify: &File, _timeout: Option<Duration>) -> io::Result<bool> {
+        unreachable!("there is no inotify instance to wait for")
+    }
+}
+
+pub struct Watcher {
+    inotify: File,
+    // the files watched, as given, with the watch on their directory and their name in it
+    files: Vec<(i32, OsString, PathBuf)>,
+    buffer: Vec<u8>,
+}
+
+impl Watcher {
+    pub fn new() -> io::Result<Watcher> {
+        Ok(Watcher{inotify: sys::init()?, files: Vec::new(), buffer: vec![0u8; 64 * 1024]})
+    }
+
+    // The file need not exist yet, but its directory must.
+    pub fn add(&mut self, path: &Path) -> io::Result<()> {
+        let name = path.file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file name"))?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        // the same directory gives the same watch
+        let wd = sys::add_watch(&self.inotify, dir, sys::IN_CLOSE_WRITE | sys::IN_MOVED_TO)?;
+        self.files.push((wd, name.to_os_string(), path.to_path_buf()));
+        Ok(())
+    }
+
+    // Which of the files the events read are for, by index; all of them if events were lost.
+    fn read_events(&mut self) -> io::Result<Vec<usize>> {
+        let len = self.inotify.read(&mut self.buffer)?;
+        let mut written = Vec::new();
+        let mut rest = &self.buffer[..len];
+        // struct inotify_event: wd, mask, cookie and len, then len bytes of NUL padded name
+        while rest.len() >= 16 {
+            let int = |i: usize| u32::from_ne_bytes([rest[i], rest[i + 1], rest[i + 2], rest[i + 3]]);
+            let (wd, mask, name_len) = (int(0) as i32, int(4), int(12) as usize);
+            let name = &rest[16..16 + name_len];
+            let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+            if mask & sys::IN_Q_OVERFLOW != 0 {
+                written.extend(0..self.files.len());
+            }
+            for (i, (file_wd, file_name, _)) in self.files.iter().enumerate() {
+                if *file_wd == wd && crate::escape::path_to_bytes(Path::new(file_name)) == name {
+                    written.push(i);
+                }
+            }
+            rest = &rest[16 + name_len..];
+        }
+        Ok(written)
+    }
+
+    // Waits for files to be written, then until none has been for debounce, so that a burst
+    // of writes gives one hash. The files written, each once, in the order they were added.
+    pub fn wait(&mut self, debounce: Duration) -> io::Result<Vec<PathBuf>> {
+        let mut written = Vec::new();
+        while written.is_empty() {
+            sys::wait(&self.inotify, None)?;
+            written = self.read_events()?;
+        }
+
+        let mut quiet_since = Instant::now();
+        loop {
+            let left = debounce.saturating_sub(quiet_since.elapsed());
+            if left.is_zero() || !sys::wait(&self.inotify, Some(left))? {
+                break;
+            }
+            let more = self.read_events()?;
+            if !more.is_empty() {
+                written.extend(more);
+                quiet_since = Instant::now();
+            }
+        }
+
+        written.sort_unstable();
+        written.dedup();
+        Ok(written.into_iter().map(|i| self.files[i].2.clone()).collect())
+    }
+}
+
+// Hashes the file from the start with hasher, which is left ready for the next file. A file
+// written again is hashed in full even if it was only appended to: nothing short of reading
+// it again would show that the part hashed before is unchanged.
+pub fn hash_with(hasher: &mut MultiHasher, path: &Path) -> io::Result<(Vec<Vec<u8>>, u64)> {
+    let read = File::open(path).and_then(|mut file| read_all(&mut file, |data| hasher.update(data)));
+    // finished either way, so that what was read of a file that failed doesn't stay behind
+    let digests = hasher.finish_reset();
+    Ok((digests, read?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha::{sha, Algorithm, SHA256, SHA512};
+    use crate::test_util::TempDir;
+    use std::fs;
+    use std::fs::OpenOptions;
+
+    fn digests(data: &[u8]) -> Vec<Vec<u8>> {
+        vec![sha(SHA256, data.iter().cloned()), sha(SHA512, data.iter().cloned())]
+    }
+
+    #[test]
+    fn test_hash_with() {
+        let mut hasher = MultiHasher::new(&[Algorithm::Sha256, Algorithm::Sha512]);
+        let dir = TempDir::new("watch-hash");
+        let data: Vec<u8> = (0..10000u32).map(|i| (i * 7) as u8).collect();
+        let file = dir.write("log", &data[..5000]);
+        assert_eq!(hash_with(&mut hasher, &file).unwrap(), (digests(&data[..5000]), 5000));
+
+        OpenOptions::new().append(true).open(&file).unwrap().write_all(&data[5000..]).unwrap();
+        assert_eq!(hash_with(&mut hasher, &file).unwrap(), (digests(&data), 10000));
+
+        // rewritten in place, the same inode and just as long, with only the start changed: the
+        // end is the same, which tells nothing about the rest
+        let mut changed = data.clone();
+        changed[..5000].iter_mut().for_each(|b| *b ^= 0xff);
+        fs::write(&file, &changed).unwrap();
+        assert_eq!(hash_with(&mut hasher, &file).unwrap(), (digests(&changed), 10000));
+
+        fs::write(&file, b"abc").unwrap();
+        assert_eq!(hash_with(&mut hasher, &file).unwrap(), (digests(b"abc"), 3));
+
+        // a file that can't be read leaves nothing behind in the hasher
+        assert!(hash_with(&mut hasher, &dir.path().join("missing")).is_err());
+        assert_eq!(hash_with(&mut hasher, &file).unwrap(), (digests(b"abc"), 3));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_watcher() {
+        let dir = TempDir::new("watch-events");
+        let a = dir.write("a", b"");
+        let b = dir.path().join("b");
+        let mut watcher = Watcher::new().unwrap();
+        watcher.add(&a).unwrap();
+        watcher.add(&b).unwrap();
+
+        fs::write(dir.path().join("other"), b"x").unwrap();
+        fs::write(&b, b"1").unwrap();
+        fs::write(&a, b"2").unwrap();
+        fs::write(&b, b"3").unwrap();
+        assert_eq!(wait_for(&mut watcher, &[&a, &b]), vec![a.clone(), b.clone()]);
+
+        fs::write(dir.path().join("tmp"), b"4").unwrap();
+        fs::rename(dir.path().join("tmp"), &a).unwrap();
+        assert_eq!(wait_for(&mut watcher, &[&a]), vec![a]);
+    }
+
+    // The files written, gathered over as many waits as it takes for all of expected to turn up:
+    // a busy machine may let the debounce run out in the middle of a burst.
+    #[cfg(target_os = "linux")]
+    fn wait_for(watcher: &mut Watcher, expected: &[&PathBuf]) -> Vec<PathBuf> {
+        let mut written: Vec<PathBuf> = Vec::new();
+        while !expected.iter().all(|path| written.contains(path)) {
+            written.extend(watcher.wait(Duration::from_millis(50)).unwrap());
+        }
+        written.sort();
+        written.dedup();
+        written
+    }
+}
@@ -0,0 +1,441 @@
+// Runs the checksum binaries over files in a temp directory.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+const ABC_SHA512: &str = "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f";
+const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    fn new(name: &str) -> TempDir {
+        let path = env::temp_dir().join(format!("rust-crypto-cli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(path.join("sub")).unwrap();
+        fs::write(path.join("abc.txt"), b"abc").unwrap();
+        fs::write(path.join("sub/empty"), b"").unwrap();
+        TempDir{path}
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+fn run(binary: &str, dir: &Path, args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(binary)
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn sha256sum(dir: &Path, args: &[&str]) -> Output {
+    run(env!("CARGO_BIN_EXE_sha256sum"), dir, args, b"")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn test_recursive_text() {
+    let dir = TempDir::new("text");
+    let output = sha256sum(&dir.path, &["-r", "."]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), format!("{}  ./abc.txt\n{}  ./sub/empty\n", ABC_SHA256, EMPTY_SHA256));
+}
+
+#[test]
+fn test_json() {
+    let dir = TempDir::new("json");
+    let output = sha256sum(&dir.path, &["--format=json", "abc.txt", "missing", "sub/empty"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), format!("[\n  \
+        {{\"path\":\"abc.txt\",\"algorithm\":\"SHA256\",\"digest\":\"{}\",\"size\":3,\"error\":null}},\n  \
+        {{\"path\":\"missing\",\"algorithm\":\"SHA256\",\"digest\":null,\"size\":null,\"error\":\"No such file or directory\"}},\n  \
+        {{\"path\":\"sub/empty\",\"algorithm\":\"SHA256\",\"digest\":\"{}\",\"size\":0,\"error\":null}}\n]\n",
+        ABC_SHA256, EMPTY_SHA256));
+}
+
+#[test]
+fn test_jsonl_and_csv() {
+    let dir = TempDir::new("jsonl-csv");
+    let output = sha256sum(&dir.path, &["-r", "--format", "jsonl", "--encoding=base64", "sub"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output),
+        "{\"path\":\"sub/empty\",\"algorithm\":\"SHA256\",\"digest\":\"47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=\",\"size\":0,\"error\":null}\n");
+
+    let output = run(env!("CARGO_BIN_EXE_hashsum"), &dir.path, &["-a", "sha256", "--format=csv", "abc.txt", "-"], b"abc");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), format!("path,algorithm,digest,size,error\nabc.txt,SHA256,{},3,\n-,SHA256,{},3,\n", ABC_SHA256, ABC_SHA256));
+}
+
+#[test]
+fn test_several_algorithms() {
+    let dir = TempDir::new("multi");
+    let output = run(env!("CARGO_BIN_EXE_hashsum"), &dir.path, &["-a", "sha256,sha512", "--tag", "abc.txt", "-"], b"abc");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), format!("SHA256 (abc.txt) = {}\nSHA512 (abc.txt) = {}\nSHA256 (-) = {}\nSHA512 (-) = {}\n",
+        ABC_SHA256, ABC_SHA512, ABC_SHA256, ABC_SHA512));
+
+    let output = run(env!("CARGO_BIN_EXE_hashsum"), &dir.path, &["-a", "sha1,sha256", "abc.txt"], b"");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), format!("a9993e364706816aba3e25717850c26c9cd0d89d  abc.txt\n{}  abc.txt\n", ABC_SHA256));
+    fs::write(dir.path.join("SHA1SUMS"), "a9993e364706816aba3e25717850c26c9cd0d89d  abc.txt\n").unwrap();
+    assert_eq!(stdout(&run(env!("CARGO_BIN_EXE_hashsum"), &dir.path, &["-a", "sha1", "-c", "SHA1SUMS"], b"")), "abc.txt: OK\n");
+
+    let output = sha256sum(&dir.path, &["-a", "sha512,sha256", "--format=csv", "missing"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "path,algorithm,digest,size,error\n\
+                                 missing,SHA512,,,No such file or directory\nmissing,SHA256,,,No such file or directory\n");
+}
+
+#[test]
+fn test_check_round_trip() {
+    let dir = TempDir::new("check");
+    let sums = sha256sum(&dir.path, &["-r", "--tag", "."]);
+    fs::write(dir.path.join("SUMS"), &sums.stdout).unwrap();
+
+    let output = sha256sum(&dir.path, &["-c", "SUMS"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "./abc.txt: OK\n./sub/empty: OK\n");
+
+    fs::write(dir.path.join("abc.txt"), b"abd").unwrap();
+    let output = sha256sum(&dir.path, &["-c", "--quiet", "SUMS"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "./abc.txt: FAILED\n");
+}
+
+#[test]
+fn test_usage_error() {
+    let dir = TempDir::new("usage");
+    let output = sha256sum(&dir.path, &["--format=xml"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown format 'xml'"));
+}
+
+#[test]
+fn test_manifest_audit() {
+    let dir = TempDir::new("manifest");
+    let manifest = |args: &[&str]| run(env!("CARGO_BIN_EXE_manifest"), &dir.path, args, b"");
+
+    let output = manifest(&["generate", "-o", "MANIFEST"]);
+    assert!(output.status.success());
+    let text = fs::read_to_string(dir.path.join("MANIFEST")).unwrap();
+    assert!(text.starts_with("%%%% HASHDEEP-1.0\n%%%% size,sha256,sha512,filename\n"));
+    assert!(text.contains(&format!("3,{},", ABC_SHA256)));
+    assert!(!text.contains(",MANIFEST\n"));
+
+    let output = manifest(&["audit", "MANIFEST"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "2 matched, 0 changed, 0 moved, 0 new, 0 missing\n");
+
+    fs::rename(dir.path.join("abc.txt"), dir.path.join("sub/abc.txt")).unwrap();
+    fs::write(dir.path.join("sub/empty"), b"x").unwrap();
+    fs::write(dir.path.join("new"), b"new").unwrap();
+    let output = manifest(&["audit", "MANIFEST"]);
+    assert_eq!(output.status.code(), Some(2 | 4 | 8));
+    assert_eq!(stdout(&output), "new: new\nmoved: sub/abc.txt (from abc.txt)\nchanged: sub/empty\n\
+                                 0 matched, 1 changed, 1 moved, 1 new, 0 missing\n");
+
+    fs::remove_file(dir.path.join("sub/abc.txt")).unwrap();
+    let output = manifest(&["audit", "MANIFEST"]);
+    assert_eq!(output.status.code(), Some(2 | 8 | 16));
+}
+
+#[test]
+fn test_manifest_audit_hashdeep_names() {
+    let dir = TempDir::new("manifest-hashdeep");
+    let manifest = |args: &[&str]| run(env!("CARGO_BIN_EXE_manifest"), &dir.path, args, b"");
+
+    // as written by hashdeep -r ./sub from the directory above
+    let line = format!("3,{},{},./sub/abc.txt\n", ABC_SHA256, ABC_SHA512);
+    fs::write(dir.path.join("MANIFEST"), format!("%%%% HASHDEEP-1.0\n%%%% size,sha256,sha512,filename\n##\n{}", line)).unwrap();
+    fs::write(dir.path.join("sub/abc.txt"), b"abc").unwrap();
+    fs::remove_file(dir.path.join("abc.txt")).unwrap();
+    fs::remove_file(dir.path.join("sub/empty")).unwrap();
+
+    let output = manifest(&["audit", "MANIFEST", "sub"]);
+    assert_eq!(stdout(&output), "1 matched, 0 changed, 0 moved, 0 new, 0 missing
+");
+    assert!(output.status.success());
+    let output = manifest(&["audit", "MANIFEST", "./sub/"]);
+    assert_eq!(stdout(&output), "1 matched, 0 changed, 0 moved, 0 new, 0 missing
+");
+}
+
+#[test]
+fn test_dupfind() {
+    let dir = TempDir::new("dupfind");
+    fs::write(dir.path.join("sub/abc.txt"), b"abc").unwrap();
+    fs::write(dir.path.join("sub/other"), b"abd").unwrap();
+    let dupfind = |args: &[&str]| run(env!("CARGO_BIN_EXE_dupfind"), &dir.path, args, b"");
+
+    let report = format!("# 2 files of 3 bytes, 3 bytes wasted, SHA256 {}\n./abc.txt\n./sub/abc.txt\n", ABC_SHA256);
+    let output = dupfind(&["."]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), format!("{}\n1 duplicate sets, 1 duplicate files, 3 bytes wasted\n", report));
+
+    let output = dupfind(&["--link", "."]);
+    assert_eq!(stdout(&output), format!("{}would link ./sub/abc.txt => ./abc.txt\n\n\
+                                         1 duplicate sets, 1 duplicate files, 3 bytes wasted\n", report));
+
+    let output = dupfind(&["--link", "--no-dry-run", "."]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("linked ./sub/abc.txt => ./abc.txt\n"));
+    assert_eq!(stdout(&dupfind(&["."])), "0 duplicate sets, 0 duplicate files, 0 bytes wasted\n");
+}
+
+#[test]
+fn test_digest_cache() {
+    let dir = TempDir::new("cache");
+    let abc = dir.path.join("abc.txt");
+    let an_hour_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+    fs::File::options().write(true).open(&abc).unwrap().set_modified(an_hour_ago).unwrap();
+
+    let output = sha256sum(&dir.path, &["--cache=cache", "abc.txt"]);
+    assert!(output.status.success());
+    assert!(fs::read_to_string(dir.path.join("cache")).unwrap().contains(&format!(" SHA256 {} ", ABC_SHA256)));
+
+    // new content behind the same size and modification time is only seen without the cache
+    fs::write(&abc, b"abd").unwrap();
+    fs::File::options().write(true).open(&abc).unwrap().set_modified(an_hour_ago).unwrap();
+    assert_eq!(stdout(&sha256sum(&dir.path, &["--cache=cache", "abc.txt"])), format!("{}  abc.txt\n", ABC_SHA256));
+    assert_ne!(stdout(&sha256sum(&dir.path, &["--cache=cache", "--no-cache", "abc.txt"])), format!("{}  abc.txt\n", ABC_SHA256));
+    let rehashed = stdout(&sha256sum(&dir.path, &["--cache=cache", "--rehash", "abc.txt"]));
+    assert_ne!(rehashed, format!("{}  abc.txt\n", ABC_SHA256));
+    assert_eq!(stdout(&sha256sum(&dir.path, &["--cache=cache", "abc.txt"])), rehashed);
+
+    fs::write(dir.path.join("cache"), b"# rust-crypto digest cache 1\n12 34 garbage\n").unwrap();
+    let output = sha256sum(&dir.path, &["--cache=cache", "abc.txt"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), rehashed);
+}
+
+#[test]
+fn test_ranges_and_chunks() {
+    let dir = TempDir::new("ranges");
+    fs::write(dir.path.join("data"), b"xxabcabcab").unwrap();
+
+    let output = sha256sum(&dir.path, &["--offset=2", "--length=3", "data"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), format!("{}  data\n", ABC_SHA256));
+
+    let output = sha256sum(&dir.path, &["--offset", "2", "--split", "3", "data"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), format!("{}  data@2+3\n{}  data@5+3\n\
+        fb8e20fc2e4c3f248c60c39bd652f3c1347298bb977b8b4d5903b85055620603  data@8+2\n", ABC_SHA256, ABC_SHA256));
+
+    let output = run(env!("CARGO_BIN_EXE_sha256sum"), &dir.path, &["--offset=3", "--length=3", "-"], b"..abc");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("-: the input ends before the end of the range"));
+}
+
+// A ustar entry for a regular file with short name.
+fn tar_entry(name: &str, content: &[u8]) -> Vec<u8> {
+    let mut header = vec![0u8; 512];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..107].copy_from_slice(b"0000644");
+    header[124..135].copy_from_slice(format!("{:011o}", content.len()).as_bytes());
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[148..156].copy_from_slice(b"        ");
+    let sum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+    header.extend_from_slice(content);
+    header.resize(header.len().div_ceil(512) * 512, 0);
+    header
+}
+
+#[test]
+fn test_tar() {
+    let dir = TempDir::new("tar");
+    let archive = [tar_entry("abc.txt", b"abc"), tar_entry("sub/empty", b""), vec![0; 1024]].concat();
+    fs::write(dir.path.join("files.tar"), &archive).unwrap();
+
+    // the files in the archive are the ones in the directory, so the lines check against it
+    let output = sha256sum(&dir.path, &["--tar", "files.tar"]);
+    assert!(output.status.success());
+    let sums = format!("{}  abc.txt\n{}  sub/empty\n", ABC_SHA256, EMPTY_SHA256);
+    assert_eq!(stdout(&output), sums);
+    fs::write(dir.path.join("sums"), &sums).unwrap();
+    assert!(sha256sum(&dir.path, &["-c", "sums"]).status.success());
+
+    let output = run(env!("CARGO_BIN_EXE_sha256sum"), &dir.path, &["--tar"], &archive);
+    assert_eq!(stdout(&output), sums);
+
+    let output = run(env!("CARGO_BIN_EXE_sha256sum"), &dir.path, &["--tar", "-"], &archive[..514]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("-: the archive is truncated"));
+}
+
+#[test]
+fn test_watch() {
+    let dir = TempDir::new("watch");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sha256sum"))
+        .args(["--watch", "abc.txt", "new.txt"])
+        .current_dir(&dir.path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let (sender, lines) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    thread::spawn(move || {
+        for line in io::BufReader::new(stdout).lines() {
+            let _ = sender.send(line.unwrap());
+        }
+    });
+    let next = || lines.recv_timeout(Duration::from_secs(10)).unwrap();
+
+    // the watches are in place once the first line is out
+    assert_eq!(next(), format!("{}  abc.txt", ABC_SHA256));
+    fs::write(dir.path.join("new.txt"), b"").unwrap();
+    assert_eq!(next(), format!("{}  new.txt", EMPTY_SHA256));
+    fs::write(dir.path.join("abc.txt"), b"ab").unwrap();
+    fs::OpenOptions::new().append(true).open(dir.path.join("abc.txt")).unwrap().write_all(b"c").unwrap();
+    // on a busy machine the two writes may be hashed apart, or the second one hashed again
+    let abc = format!("{}  abc.txt", ABC_SHA256);
+    loop {
+        let line = next();
+        if line == abc {
+            break;
+        }
+        assert!(line.ends_with("  abc.txt"), "{}", line);
+    }
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    while let Ok(line) = lines.recv_timeout(Duration::from_millis(500)) {
+        assert_eq!(line, abc);
+    }
+}
+
+#[test]
+fn test_progress_and_stats() {
+    let dir = TempDir::new("stats");
+    // standard error isn't a terminal here, so there is no bar, only the summary
+    let output = sha256sum(&dir.path, &["--progress", "--stats", "abc.txt", "sub/empty"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), format!("{}  abc.txt\n{}  sub/empty\n", ABC_SHA256, EMPTY_SHA256));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("files:   2\nbytes:   3 (3 B)\nelapsed: "), "{}", stderr);
+    assert!(stderr.contains("\nrate:    "));
+    assert!(stderr.contains("\nSHA256:  ") && stderr.ends_with(" s hashing)\n"));
+    assert!(!stderr.contains('\r'));
+
+    // a rate per algorithm, each by the time spent in it
+    let output = sha256sum(&dir.path, &["--stats", "-a", "sha256,sha512", "abc.txt"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let rates: Vec<&str> = stderr.lines().filter(|l| l.starts_with("SHA")).collect();
+    assert_eq!(rates.len(), 2);
+    assert!(rates[0].starts_with("SHA256:  ") && rates[1].starts_with("SHA512:  "), "{}", stderr);
+}
+
+#[test]
+fn test_checkpoint() {
+    let dir = TempDir::new("checkpoint");
+    let output = sha256sum(&dir.path, &["--checkpoint=cp", "abc.txt"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), format!("{}  abc.txt\n", ABC_SHA256));
+    assert!(!dir.path.join("cp").exists());
+
+    fs::write(dir.path.join("cp"), "# rust-crypto checkpoint 1\nfile 1 2 3 4\n").unwrap();
+    let output = sha256sum(&dir.path, &["--checkpoint", "cp", "abc.txt"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).ends_with(": abc.txt: the checkpoint cp is damaged; remove it to start over\n"));
+    assert!(dir.path.join("cp").exists());
+}
+
+#[test]
+fn test_hashdiff() {
+    let dir = TempDir::new("hashdiff");
+    fs::create_dir_all(dir.path.join("a/sub")).unwrap();
+    fs::create_dir_all(dir.path.join("b/sub")).unwrap();
+    fs::create_dir_all(dir.path.join("b/moved")).unwrap();
+    fs::write(dir.path.join("a/abc.txt"), b"abc").unwrap();
+    fs::write(dir.path.join("a/sub/empty"), b"").unwrap();
+    fs::write(dir.path.join("b/moved/abc.txt"), b"abc").unwrap();
+    fs::write(dir.path.join("b/sub/empty"), b"x").unwrap();
+    fs::write(dir.path.join("b/new.log"), b"").unwrap();
+    let hashdiff = |args: &[&str]| run(env!("CARGO_BIN_EXE_hashdiff"), &dir.path, args, b"");
+
+    let output = hashdiff(&["-x", "*.log", "a", "b"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "renamed: abc.txt => moved/abc.txt\nmodified: sub/empty\n\
+        0 added, 0 removed, 1 modified, 1 renamed, 0 identical\n");
+
+    let output = hashdiff(&["--json", "a", "b"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "{\"added\":[\"new.log\"],\"removed\":[],\"modified\":[\"sub/empty\"],\
+        \"renamed\":[{\"from\":\"abc.txt\",\"to\":\"moved/abc.txt\"}],\"identical\":0}\n");
+
+    let output = hashdiff(&["a", "a"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "0 added, 0 removed, 0 modified, 0 renamed, 2 identical\n");
+
+    assert_eq!(hashdiff(&["a", "missing"]).status.code(), Some(2));
+}
+
+#[test]
+fn test_bench() {
+    let dir = TempDir::new("bench");
+    let output = run(env!("CARGO_BIN_EXE_hashsum"), &dir.path, &["bench", "--sizes=16,4K", "--time=0.01", "--json"], b"");
+    assert_eq!(output.status.code(), Some(0));
+    let lines: Vec<String> = stdout(&output).lines().map(String::from).collect();
+    assert_eq!(lines.len(), 24);
+    assert!(lines[0].starts_with("{\"algorithm\":\"SHA256\",\"entry\":\"sha\",\"size\":16,"));
+    assert!(lines[23].starts_with("{\"algorithm\":\"SHA1\",\"entry\":\"incremental\",\"size\":4096,"));
+
+    let output = run(env!("CARGO_BIN_EXE_hashsum"), &dir.path, &["bench", "-a", "sha256", "--entry=incremental", "--sizes=1M", "--time=0.01"], b"");
+    assert_eq!(output.status.code(), Some(0));
+    let text = stdout(&output);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("algorithm entry point"));
+    assert!(lines[1].starts_with("SHA256    incremental     1 MiB "));
+
+    // to the tools named for an algorithm, and to hashsum anywhere but first, bench is a file
+    fs::write(dir.path.join("bench"), b"abc").unwrap();
+    assert_eq!(stdout(&sha256sum(&dir.path, &["bench"])), format!("{}  bench\n", ABC_SHA256));
+    let output = run(env!("CARGO_BIN_EXE_hashsum"), &dir.path, &["-a", "sha256", "abc.txt", "bench"], b"");
+    assert_eq!(stdout(&output), format!("{}  abc.txt\n{}  bench\n", ABC_SHA256, ABC_SHA256));
+}
+
+#[test]
+fn test_round_trip_unusual_names() {
+    let dir = TempDir::new("round-trip");
+    fs::write(dir.path.join("*star"), b"abc").unwrap();
+    fs::write(dir.path.join(" lead"), b"").unwrap();
+    fs::write(dir.path.join("back\\slash"), b"abc").unwrap();
+
+    let output = sha256sum(&dir.path, &["*star", " lead", "back\\slash"]);
+    assert!(output.status.success());
+    let list = stdout(&output);
+    assert_eq!(list, format!("{}  *star\n{}   lead\n\\{}  back\\\\slash\n", ABC_SHA256, EMPTY_SHA256, ABC_SHA256));
+
+    fs::write(dir.path.join("list"), &list).unwrap();
+    let output = sha256sum(&dir.path, &["-c", "list"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "*star: OK\n lead: OK\n\\back\\\\slash: OK\n");
+}